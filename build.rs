@@ -1,8 +1,9 @@
 use std::env;
-use std::fs::{self, File};
+use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
 fn main() {
+    println!("cargo:rerun-if-changed=src/stdlib/usblib.h");
     build_stdlib_definition();
 }
 
@@ -25,38 +26,54 @@ fn build_stdlib_definition() {
                 current_section = "positional";
             } else if line.contains("enclosed") {
                 current_section = "enclosed";
+            } else if line.contains("internal") {
+                // runtime helpers only the code generator calls, not visible to USB code
+                current_section = "internal";
             }
             continue;
         }
 
-        if line.ends_with(";") {
-            if let Some((ret_and_name, args_str)) = line.trim_end_matches(';').split_once('(') {
-                let args = args_str
-                    .trim_end_matches(')')
-                    .split(',')
-                    .filter(|s| !s.trim().is_empty())
-                    .map(|s| s.trim().split_whitespace().next().unwrap())
-                    .collect::<Vec<_>>();
+        if line.ends_with(";")
+            && let Some((ret_and_name, args_str)) = line.trim_end_matches(';').split_once('(')
+        {
+            let params = args_str
+                .trim_end_matches(')')
+                .split(',')
+                .filter(|s| !s.trim().is_empty())
+                .collect::<Vec<_>>();
+            let args = params
+                .iter()
+                .map(|s| s.split_whitespace().next().unwrap())
+                .collect::<Vec<_>>();
+            // parameters named `optional_<name>` may be left out by USB code
+            let optional = params
+                .iter()
+                .filter(|s| {
+                    s.split_whitespace()
+                        .nth(1)
+                        .is_some_and(|name| name.starts_with("optional_"))
+                })
+                .count();
 
-                let mut parts = ret_and_name.trim().split_whitespace();
-                let return_type = parts.next().unwrap();
-                let name = parts.next().unwrap();
+            let mut parts = ret_and_name.split_whitespace();
+            let return_type = parts.next().unwrap();
+            let name = parts.next().unwrap();
 
-                let fn_line = format!(
-                    r#"StdLibFunction {{ name: "{}", return_type: "{}", param_types: &[{}] }}"#,
-                    name,
-                    return_type,
-                    args.iter()
-                        .map(|a| format!(r#""{}""#, a))
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                );
+            let fn_line = format!(
+                r#"StdLibFunction {{ name: "{}", return_type: "{}", param_types: &[{}], optional_parameters: {} }}"#,
+                name,
+                return_type,
+                args.iter()
+                    .map(|a| format!(r#""{}""#, a))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                optional
+            );
 
-                match current_section {
-                    "positional" => positional.push(fn_line),
-                    "enclosed" => enclosed.push(fn_line),
-                    _ => {}
-                }
+            match current_section {
+                "positional" => positional.push(fn_line),
+                "enclosed" => enclosed.push(fn_line),
+                _ => {}
             }
         }
     }
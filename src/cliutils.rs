@@ -1,16 +1,33 @@
-use std::{collections::hash_map::Keys, env, error::Error, fs, path::PathBuf};
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process,
+};
 
 use pico_args::Arguments;
 
 use crate::{
-    usbcompiler::compiler::{self, compiler::Compiler},
+    usbcompiler::compiler::compiler::{ColorDepth, Compiler},
     version::Version,
 };
 #[derive(Debug)]
 pub enum Command {
-    Init { working_dir: String },
-    Build { entry: String },
-    Run { entry: String },
+    Init {
+        working_dir: String,
+    },
+    Build {
+        entry: String,
+        color: ColorDepth,
+    },
+    /// `arguments` are passed on to the program, see `ARGV$` in the spec
+    Run {
+        entry: String,
+        color: ColorDepth,
+        arguments: Vec<OsString>,
+    },
     Version,
     Help,
 }
@@ -43,10 +60,19 @@ impl Command {
                             .unwrap_or_else(|| String::from("./")),
                     },
                     "build" => Command::Build {
+                        color: parse_color_depth(&mut args)?,
+                        entry: args
+                            .opt_free_from_str()
+                            .unwrap()
+                            .unwrap_or_else(|| String::from("./")),
+                    },
+                    "run" => Command::Run {
+                        color: parse_color_depth(&mut args)?,
                         entry: args
                             .opt_free_from_str()
                             .unwrap()
                             .unwrap_or_else(|| String::from("./")),
+                        arguments: program_arguments(args),
                     },
                     "version" => Command::Version,
                     _ => Command::Help,
@@ -61,8 +87,21 @@ impl Command {
     pub fn run(&self) -> Result<(), u8> {
         match self {
             Command::Init { working_dir } => generate_default_project(PathBuf::from(working_dir)),
-            Command::Build { entry } => Compiler::new(PathBuf::from(entry)).compile().unwrap(),
-            Command::Run { entry } => todo!(),
+            Command::Build { entry, color } => {
+                build(entry, *color)?;
+            }
+            Command::Run {
+                entry,
+                color,
+                arguments,
+            } => {
+                let binary = build(entry, *color)?;
+                // the status of the program is passed on as is, it isn't an error of usbasic
+                let code = run_binary(&binary, arguments)?;
+                if code != 0 {
+                    process::exit(code);
+                }
+            }
             Command::Help => show_help(),
             Command::Version => Version::print(),
         }
@@ -70,6 +109,48 @@ impl Command {
     }
 }
 
+/// parse the `--color=<4-bit|8-bit|24-bit>` option, defaulting to 8-bit
+fn parse_color_depth(args: &mut Arguments) -> Result<ColorDepth, ()> {
+    return match args.opt_value_from_str("--color") {
+        Ok(depth) => Ok(depth.unwrap_or(ColorDepth::Ansi256)),
+        Err(error) => {
+            eprintln!("{}", error);
+            Err(())
+        }
+    };
+}
+/// the arguments left after the options and the entry, an optional `--` separates them from the ones of usbasic
+fn program_arguments(args: Arguments) -> Vec<OsString> {
+    let mut arguments = args.finish();
+    if arguments.first().is_some_and(|a| a == "--") {
+        arguments.remove(0);
+    }
+    return arguments;
+}
+/// compile the project or file at `entry`, printing any errors. Returns the path of the final binary
+fn build(entry: &str, color: ColorDepth) -> Result<PathBuf, u8> {
+    let mut compiler = Compiler::new(PathBuf::from(entry)).with_color_depth(color);
+    let result = compiler.compile();
+    for warning in compiler.warnings() {
+        eprintln!("warning: {}", warning);
+    }
+    return result.map_err(|errors| {
+        for error in errors {
+            eprintln!("error: {}", error);
+        }
+        1
+    });
+}
+/// run a built program and return its exit status, a program killed by a signal gets the status a shell would report for it
+fn run_binary(binary: &Path, arguments: &[OsString]) -> Result<i32, u8> {
+    let status = process::Command::new(binary)
+        .args(arguments)
+        .status()
+        .map_err(|_| 1)?;
+    return Ok(status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or(0)));
+}
 fn help_message(command_name: &str) {
     println!("\n{}\n", command_name);
     match command_name {
@@ -77,10 +158,10 @@ fn help_message(command_name: &str) {
             "Create a new USB project in a given directory and create it if it does not exist.\nUSAGE: usbasic init [directory=./]\n"
         ),
         "build" => println!(
-            "Build the current USB project and output the final binary into the build/ directory, must be used in an existing USB project.\nUSAGE: usbasic build"
+            "Build the current USB project and output the final binary into the build/ directory, must be used in an existing USB project.\nUSAGE: usbasic build [--color=4-bit|8-bit|24-bit]"
         ),
         "run" => println!(
-            "Builds the current USB project using usbasic build and runs the final binary or runs the given .usb file as if it were a script\nUSAGE: usbasic run [usb_file]"
+            "Builds the current USB project using usbasic build and runs the final binary or runs the given .usb file as if it were a script\nUSAGE: usbasic run [--color=4-bit|8-bit|24-bit] [usb_file] [[--] arguments...]"
         ),
         "version" => println!("Shows version information\nUSAGE: usbasic version"),
         _ => show_help(),
@@ -96,12 +177,12 @@ fn relative_to_absolute(path: PathBuf) -> PathBuf {
     }
 }
 fn create_project_directory(dir: PathBuf) {
-    fs::create_dir(&dir.join("build")).unwrap();
-    fs::create_dir(&dir.join("obj")).unwrap();
-    fs::create_dir(&dir.join("src")).unwrap();
-    fs::write(&dir.join("src/main.usb"), "10 PRINT \"Hello, World\"").unwrap();
+    fs::create_dir(dir.join("build")).unwrap();
+    fs::create_dir(dir.join("obj")).unwrap();
+    fs::create_dir(dir.join("src")).unwrap();
+    fs::write(dir.join("src/main.usb"), "10 PRINT \"Hello, World\"").unwrap();
     fs::write(
-        &dir.join(format!(
+        dir.join(format!(
             "{}.usbp.json",
             dir.file_name().unwrap().to_string_lossy()
         )),
@@ -112,7 +193,7 @@ fn create_project_directory(dir: PathBuf) {
     "#,
     )
     .unwrap();
-    fs::write(&dir.join(".gitignore"), "obj\nbuild\n").unwrap();
+    fs::write(dir.join(".gitignore"), "obj\nbuild\n").unwrap();
 }
 pub fn generate_default_project(dir: PathBuf) {
     let dir = relative_to_absolute(dir);
@@ -135,3 +216,20 @@ pub fn generate_default_project(dir: PathBuf) {
         panic!("IO error");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_passes_on_the_exit_status() {
+        let dir = env::temp_dir().join("usbasic-tests").join("exit_status");
+        fs::create_dir_all(&dir).unwrap();
+        for (source, status) in [("THROW 7", 7), ("PRINT ARGC", 0)] {
+            let entry = dir.join("exit_status.usb");
+            fs::write(&entry, source).unwrap();
+            let binary = build(entry.to_str().unwrap(), ColorDepth::Ansi256).unwrap();
+            assert_eq!(run_binary(&binary, &[]), Ok(status), "{}", source);
+        }
+    }
+}
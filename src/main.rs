@@ -1,3 +1,9 @@
+// the codebase consistently uses explicit returns and SCREAMING_CASE AST variants
+#![allow(
+    clippy::needless_return,
+    clippy::upper_case_acronyms,
+    clippy::module_inception
+)]
 use cliutils::Command;
 use pico_args::Arguments;
 
@@ -1,7 +1,120 @@
+use crate::usbcompiler::ast::ast::DataType;
+
 #[derive(Debug)]
 pub struct StdLibFunction {
     pub name: &'static str,
     pub return_type: &'static str,
     pub param_types: &'static [&'static str], // use slice instead of Vec
+    /// the number of trailing parameters that may be left out, see `USB_MISSING` in usblib.h
+    pub optional_parameters: usize,
+}
+impl StdLibFunction {
+    /// the name of the function as written in USB code. C names are prefixed with `_` when they collide with a C keyword or libc symbol (e.g. `_int` is `INT`)
+    /// and float overloads of integer functions are suffixed with `_f` (e.g. `_abs_f` is `ABS`)
+    pub fn usb_name(&self) -> String {
+        let name = self.name.trim_start_matches('_');
+        return name.strip_suffix("_f").unwrap_or(name).to_uppercase();
+    }
+    pub fn is_positional(&self) -> bool {
+        return POS_FUNCTIONS.iter().any(|f| f.name == self.name);
+    }
+    pub fn return_data_type(&self) -> Option<DataType> {
+        return c_type_to_data_type(self.return_type);
+    }
+    /// the parameters as seen from USB code. An `unsigned int type, void* value` pair is a single argument of any type and is represented by [`None`]
+    pub fn parameters(&self) -> Vec<Option<DataType>> {
+        let mut parameters = Vec::new();
+        let mut index = 0;
+        while index < self.param_types.len() {
+            let param = self.param_types[index];
+            if param == "unsigned" && self.param_types.get(index + 1) == Some(&"void*") {
+                parameters.push(None);
+                index += 2;
+                continue;
+            }
+            parameters.push(c_type_to_data_type(param));
+            index += 1;
+        }
+        return parameters;
+    }
+}
+/// map a C type from usblib.h onto a USB type, `void` and raw pointers map to [`None`].
+/// The only lists usblib hands out are lists of strings e.g. of `SPLIT`
+pub fn c_type_to_data_type(c_type: &str) -> Option<DataType> {
+    return match c_type {
+        "usb_list*" => Some(DataType::LIST(Box::new(DataType::STRING))),
+        "long" | "int" | "unsigned" => Some(DataType::INT),
+        "double" | "float" => Some(DataType::FLOAT),
+        "char*" => Some(DataType::STRING),
+        "bool" => Some(DataType::BOOL),
+        "usb_date" => Some(DataType::DATE),
+        _ => None,
+    };
+}
+/// find a stdlib function by its USB name. Names are case insensitive and the AppleSoft `$` suffix of string functions is optional (`LEFT$` is `LEFT`)
+pub fn find_function(name: &str) -> Option<&'static StdLibFunction> {
+    return find_overloads(name).next();
+}
+fn find_overloads(name: &str) -> impl Iterator<Item = &'static StdLibFunction> {
+    let name = name.trim_end_matches('$').to_uppercase();
+    return POS_FUNCTIONS
+        .iter()
+        .chain(ENC_FUNCTIONS.iter())
+        .filter(move |f| f.usb_name() == name);
+}
+/// find the overload of a stdlib function that takes the given argument types, e.g. `ABS(-1.5)` calls `_abs_f`.
+/// Overloads are tried in the order usblib.h declares them so integer arguments pick the integer overload, unknown argument types fit any parameter
+pub fn resolve_function(
+    name: &str,
+    argument_types: &[Option<DataType>],
+) -> Option<&'static StdLibFunction> {
+    let fits = |function: &&StdLibFunction| {
+        function
+            .parameters()
+            .iter()
+            .zip(argument_types)
+            .all(|(parameter, argument)| match (parameter, argument) {
+                (Some(DataType::STRING), _) | (None, _) | (_, None) => true,
+                (Some(parameter), Some(argument)) => parameter.accepts(argument),
+            })
+    };
+    return find_overloads(name)
+        .find(fits)
+        .or_else(|| find_function(name));
+}
+/// the names of the 16 colors of the terminal palette, usable wherever a color is expected (e.g. `FCOLOR RED`)
+pub const NAMED_COLORS: [&str; 16] = [
+    "BLACK",
+    "RED",
+    "GREEN",
+    "YELLOW",
+    "BLUE",
+    "MAGENTA",
+    "CYAN",
+    "WHITE",
+    "GRAY",
+    "BRIGHTRED",
+    "BRIGHTGREEN",
+    "BRIGHTYELLOW",
+    "BRIGHTBLUE",
+    "BRIGHTMAGENTA",
+    "BRIGHTCYAN",
+    "BRIGHTWHITE",
+];
+/// the value of every named color. RGB colors are positive, so palette colors are stored as `-(index + 1)` to tell them apart
+pub fn named_colors() -> impl Iterator<Item = (&'static str, i64)> {
+    return NAMED_COLORS
+        .iter()
+        .enumerate()
+        .map(|(index, name)| (*name, -(index as i64) - 1));
+}
+/// every constant predeclared in a USB program, the value of a float constant is the bits of its double
+pub fn builtin_constants() -> impl Iterator<Item = (&'static str, DataType, i64)> {
+    let math = [("PI", std::f64::consts::PI), ("E", std::f64::consts::E)]
+        .into_iter()
+        .map(|(name, value)| (name, DataType::FLOAT, value.to_bits() as i64));
+    return named_colors()
+        .map(|(name, value)| (name, DataType::INT, value))
+        .chain(math);
 }
 include!(concat!(env!("OUT_DIR"), "/generated_usblib.rs"));
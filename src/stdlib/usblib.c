@@ -0,0 +1,1910 @@
+#include <ctype.h>
+#include <dirent.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <locale.h>
+#include <math.h>
+#include <poll.h>
+#include <spawn.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <strings.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <sys/wait.h>
+#include <termios.h>
+#include <time.h>
+#include <unistd.h>
+#include <wctype.h>
+
+#include "usblib.h"
+
+// === internal ===
+
+char* str_concat(char* left, char* right) {
+    size_t left_length = strlen(left);
+    size_t right_length = strlen(right);
+    char* result = malloc(left_length + right_length + 1);
+    memcpy(result, left, left_length);
+    memcpy(result + left_length, right, right_length + 1);
+    return result;
+}
+
+long str_compare(char* left, char* right) {
+    return strcmp(left, right);
+}
+
+static const char* error_name(long code) {
+    switch (code) {
+    case USB_ERR_FORMAT:
+        return "Format";
+    case USB_ERR_OUT_OF_RANGE:
+        return "OutOfRange";
+    case USB_ERR_FILE_NOT_FOUND:
+        return "FileNotFound";
+    case USB_ERR_INVALID_MODE:
+        return "InvalidMode";
+    case USB_ERR_INVALID_FILE:
+        return "InvalidFile";
+    case USB_ERR_OUT_OF_DATA:
+        return "OutOfData";
+    case USB_ERR_TYPE_MISMATCH:
+        return "TypeMismatch";
+    case USB_ERR_PROCESS:
+        return "ProcessFailed";
+    case USB_ERR_PERMISSION_DENIED:
+        return "PermissionDenied";
+    case USB_ERR_FILESYSTEM:
+        return "FileSystem";
+    case USB_ERR_DIVISION_BY_ZERO:
+        return "DivisionByZero";
+    default:
+        return "Error";
+    }
+}
+
+// the statements of the last ONERR, NULL until one ran
+static usb_handler error_handler = NULL;
+static long error_code = 0;
+
+void onerr(usb_handler handler) {
+    error_handler = handler;
+}
+
+// the code of the last error thrown, ERR in USB
+long err() {
+    return error_code;
+}
+
+// hand the error to the ONERR handler and return to the caller once it is done, which is why builtins return a default value after throwing.
+// Without a handler the program terminates with the error code as its status, clamped to 1..255 so an error never exits successfully
+void usb_throw(long code) {
+    error_code = code;
+    if (error_handler != NULL) {
+        error_handler();
+        return;
+    }
+    fflush(stdout);
+    fprintf(stderr, "?%s error (%ld)\n", error_name(code), code);
+    exit(code < 1 ? 1 : code > 255 ? 255 : (int)code);
+}
+
+// STOP ends the program with the code as its status, 0 is a success and any other code is clamped to 1..255 like an unhandled error's
+void usb_stop(long code) {
+    exit(code == 0 ? 0 : code < 1 ? 1 : code > 255 ? 255 : (int)code);
+}
+
+// validate the size of a dimension of a DIM, an array can be empty but not smaller than that
+long dim_size(long size) {
+    if (size < 0) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return 0;
+    }
+    return size;
+}
+
+// validate the (1 based) choice of a MENU, returning the 0 based index of the statement to run
+long menu(long choice, long count) {
+    if (choice < 1 || choice > count) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return -1;
+    }
+    return choice - 1;
+}
+
+// read the next value of a data pool into a variable of the given type, integers are promoted to floats and dates
+long read_data(usb_value* pool, long count, long* pointer, unsigned int type) {
+    if (*pointer >= count) {
+        usb_throw(USB_ERR_OUT_OF_DATA);
+        return 0;
+    }
+    usb_value entry = pool[(*pointer)++];
+    if (entry.type == type || (type == USB_DATE && entry.type == USB_INT)) {
+        return entry.value;
+    }
+    if (type == USB_FLOAT && entry.type == USB_INT) {
+        double promoted = (double)entry.value;
+        long bits;
+        memcpy(&bits, &promoted, sizeof(bits));
+        return bits;
+    }
+    usb_throw(USB_ERR_TYPE_MISMATCH);
+    return 0;
+}
+
+// read a line from stdin without its newline, returns NULL on EOF
+static char* read_line() {
+    char* line = NULL;
+    size_t capacity = 0;
+    ssize_t length = getline(&line, &capacity, stdin);
+    if (length < 0) {
+        free(line);
+        return NULL;
+    }
+    if (length > 0 && line[length - 1] == '\n') {
+        line[length - 1] = '\0';
+    }
+    return line;
+}
+
+long poptions(char** options, long count, char* prompt) {
+    for (long i = 0; i < count; i++) {
+        printf("%ld. %s\n", i + 1, options[i]);
+    }
+    if (strlen(prompt) > 0) {
+        print(prompt);
+    }
+    fputs("> ", stdout);
+    fflush(stdout);
+
+    char* line = read_line();
+    putchar('\n');
+    if (line == NULL) {
+        usb_throw(USB_ERR_FORMAT);
+        return 0;
+    }
+    char* end;
+    long choice = strtol(line, &end, 10);
+    bool valid = end != line && *end == '\0';
+    free(line);
+    if (!valid) {
+        usb_throw(USB_ERR_FORMAT);
+        return 0;
+    }
+    if (choice < 1 || choice > count) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return 0;
+    }
+    return choice;
+}
+
+// === print ===
+
+// AppleSoft's tab zones, a `,` in PRINT moves to the next one
+#define USB_TAB_ZONE 16
+
+// the column the cursor is in, used for tab zones and TAB
+static long output_column = 0;
+
+static void track_column(const char* text) {
+    for (; *text != '\0'; text++) {
+        if (*text == '\n' || *text == '\r') {
+            output_column = 0;
+        } else if ((*text & 0xC0) != 0x80) {
+            output_column++;
+        }
+    }
+}
+
+void print_text(char* text) {
+    fputs(text, stdout);
+    track_column(text);
+}
+
+void print_newline() {
+    fputc('\n', stdout);
+    output_column = 0;
+}
+
+void print_zone() {
+    long spaces = USB_TAB_ZONE - output_column % USB_TAB_ZONE;
+    for (long i = 0; i < spaces; i++) {
+        fputc(' ', stdout);
+    }
+    output_column += spaces;
+}
+
+void print(char* text) {
+    print_text(text);
+    print_newline();
+}
+
+// floats of a plain magnitude are written without an exponent, very large or small ones are written with one
+#define USB_PLAIN_MAX 1e16
+#define USB_PLAIN_MIN 1e-5
+
+// the shortest representation that reads back as the same double, e.g. 0.1 rather than 0.10000000000000001.
+// Whole numbers keep one decimal so they still read as floats, 90.0 rather than 90 or 9e+01
+static void format_float(double value, char* buffer, size_t size) {
+    double magnitude = fabs(value);
+    if (isfinite(value) && magnitude < USB_PLAIN_MAX && value == trunc(value)) {
+        snprintf(buffer, size, "%.1f", value);
+        return;
+    }
+    if (isfinite(value) && magnitude < USB_PLAIN_MAX && magnitude >= USB_PLAIN_MIN) {
+        // 17 significant digits always read back, below 1 the leading zeros come on top of them
+        for (int decimals = 1; decimals <= 22; decimals++) {
+            snprintf(buffer, size, "%.*f", decimals, value);
+            if (strtod(buffer, NULL) == value) {
+                return;
+            }
+        }
+    }
+    for (int precision = 1; precision <= 17; precision++) {
+        snprintf(buffer, size, "%.*g", precision, value);
+        if (strtod(buffer, NULL) == value) {
+            return;
+        }
+    }
+}
+
+// write a value padded to width, numbers are aligned right and everything else left unless an alignment is given
+static void format_value(FILE* out, usb_value* value, char align, bool zero, long width, long precision) {
+    bool numeric = value->type == USB_INT || value->type == USB_FLOAT;
+    char number[350];
+    char* text;
+    if (numeric && precision >= 0) {
+        double fixed = (double)value->value;
+        if (value->type == USB_FLOAT) {
+            memcpy(&fixed, &value->value, sizeof(fixed));
+        }
+        snprintf(number, sizeof(number), "%.*f", (int)precision, fixed);
+        text = number;
+    } else {
+        text = str(value->type, &value->value);
+        if (precision >= 0) {
+            text = left(text, precision);
+        }
+    }
+    long padding = width - len(text);
+    if (padding <= 0) {
+        fputs(text, out);
+        return;
+    }
+    if (align == 0) {
+        align = numeric ? '>' : '<';
+        // zeros go between the sign and the digits
+        if (zero && numeric) {
+            if (*text == '-') {
+                fputc(*text++, out);
+            }
+            align = '0';
+        }
+    }
+    long before = align == '<' ? 0 : align == '^' ? padding / 2 : padding;
+    for (long i = 0; i < before; i++) {
+        fputc(align == '0' ? '0' : ' ', out);
+    }
+    fputs(text, out);
+    for (long i = before; i < padding; i++) {
+        fputc(' ', out);
+    }
+}
+
+// fill in the `{}` placeholders of a format, the compiler made sure there is a value for every placeholder
+void print_format(char* format, usb_value* values, long count) {
+    char* output;
+    size_t size;
+    FILE* out = open_memstream(&output, &size);
+    long index = 0;
+    for (char* c = format; *c != '\0'; c++) {
+        if ((*c == '{' || *c == '}') && c[1] == *c) {
+            fputc(*c++, out);
+            continue;
+        }
+        if (*c != '{') {
+            fputc(*c, out);
+            continue;
+        }
+        char align = 0;
+        bool zero = false;
+        long width = 0;
+        long precision = -1;
+        if (*++c == ':') {
+            c++;
+            if (*c == '<' || *c == '>' || *c == '^') {
+                align = *c++;
+            }
+            if (*c == '0') {
+                zero = true;
+                c++;
+            }
+            for (; isdigit((unsigned char)*c); c++) {
+                width = width * 10 + (*c - '0');
+            }
+            if (*c == '.') {
+                precision = 0;
+                for (c++; isdigit((unsigned char)*c); c++) {
+                    precision = precision * 10 + (*c - '0');
+                }
+            }
+        }
+        if (index < count) {
+            format_value(out, &values[index++], align, zero, width, precision);
+        }
+    }
+    fclose(out);
+    print_text(output);
+    free(output);
+}
+
+// === input ===
+
+// read a single key without waiting for enter or echoing it, a UTF-8 character is read as a whole. Returns "" on EOF
+char* get(char* prompt) {
+    print_text(prompt);
+    fflush(stdout);
+    struct termios original;
+    bool raw = isatty(STDIN_FILENO) && tcgetattr(STDIN_FILENO, &original) == 0;
+    if (raw) {
+        struct termios settings = original;
+        settings.c_lflag &= ~(ICANON | ECHO);
+        settings.c_cc[VMIN] = 1;
+        settings.c_cc[VTIME] = 0;
+        tcsetattr(STDIN_FILENO, TCSANOW, &settings);
+    }
+    char key[5] = {0};
+    int c = getchar();
+    if (c != EOF) {
+        key[0] = (char)c;
+        long length = (c & 0xE0) == 0xC0 ? 2 : (c & 0xF0) == 0xE0 ? 3 : (c & 0xF8) == 0xF0 ? 4 : 1;
+        for (long i = 1; i < length && (c = getchar()) != EOF; i++) {
+            key[i] = (char)c;
+        }
+    }
+    if (raw) {
+        tcsetattr(STDIN_FILENO, TCSANOW, &original);
+    }
+    return strdup(key);
+}
+
+// read a line, the prompt is printed as is. EOF is a Format error
+char* input(char* prompt) {
+    print_text(prompt);
+    fflush(stdout);
+    char* line = read_line();
+    output_column = 0;
+    if (line == NULL) {
+        usb_throw(USB_ERR_FORMAT);
+        return "";
+    }
+    return line;
+}
+
+// what a variable is set to when its input is missing, e.g. after an error was handed to ONERR
+static void default_value(usb_value* value) {
+    value->value = value->type == USB_STRING ? (long)"" : 0;
+}
+
+// parse text into the type the value is tagged with, returns false if it isn't a valid value of that type
+static bool parse_value(char* text, usb_value* value) {
+    char* end;
+    int consumed = -1;
+    long parts[6] = {0};
+    switch (value->type) {
+    case USB_STRING:
+        value->value = (long)strdup(text);
+        return true;
+    case USB_INT:
+        errno = 0;
+        value->value = strtol(text, &end, 10);
+        return end != text && *end == '\0' && errno == 0;
+    case USB_FLOAT: {
+        double number = strtod(text, &end);
+        memcpy(&value->value, &number, sizeof(number));
+        return end != text && *end == '\0';
+    }
+    case USB_BOOL:
+        value->value = strcasecmp(text, "TRUE") == 0;
+        return value->value || strcasecmp(text, "FALSE") == 0;
+    case USB_DATE: {
+        // the format dates are printed in, the time is optional
+        sscanf(text, "%ld-%ld-%ld%n %ld:%ld:%ld%n", &parts[0], &parts[1], &parts[2], &consumed, &parts[3], &parts[4], &parts[5], &consumed);
+        struct tm date = {
+            .tm_year = (int)parts[0] - 1900,
+            .tm_mon = (int)parts[1] - 1,
+            .tm_mday = (int)parts[2],
+            .tm_hour = (int)parts[3],
+            .tm_min = (int)parts[4],
+            .tm_sec = (int)parts[5],
+            .tm_isdst = -1,
+        };
+        value->value = mktime(&date);
+        if (consumed < 0 || text[consumed] != '\0') {
+            return false;
+        }
+        // mktime normalises out of range fields, 2024-02-30 would become March 1st
+        long year = parts[0];
+        bool leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+        long days[] = {31, leap ? 29 : 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31};
+        return parts[1] >= 1 && parts[1] <= 12 && parts[2] >= 1 && parts[2] <= days[parts[1] - 1] &&
+               parts[3] >= 0 && parts[3] <= 23 && parts[4] >= 0 && parts[4] <= 59 && parts[5] >= 0 && parts[5] <= 59;
+    }
+    default:
+        return false;
+    }
+}
+
+// split off the next comma separated field of a line of input, moving line past it or to NULL after the last field.
+// Spaces around a field are dropped, a field in double quotes may contain commas and spaces
+static char* next_field(char** line) {
+    char* field = *line;
+    while (*field == ' ') {
+        field++;
+    }
+    char* end;
+    if (*field == '"' && (end = strchr(field + 1, '"')) != NULL) {
+        field++;
+        *end++ = '\0';
+        end += strcspn(end, ",");
+    } else {
+        end = field + strcspn(field, ",");
+        for (char* last = end; last > field && last[-1] == ' '; last--) {
+            last[-1] = '\0';
+        }
+    }
+    *line = *end == ',' ? end + 1 : NULL;
+    *end = '\0';
+    return field;
+}
+
+// INPUT into variables of the types the values are tagged with, the values of a line are separated by commas.
+// Missing values are asked for with `??`, values that don't fit their variable are asked for again with `?REENTER` like AppleSoft does,
+// unless an ONERR handler takes care of the Format error
+void input_values(char* prompt, usb_value* values, long count) {
+    for (long i = 0; i < count; i++) {
+        default_value(&values[i]);
+    }
+    while (true) {
+        long index = 0;
+        bool valid = true;
+        while (index < count && valid) {
+            print_text(index == 0 ? (strlen(prompt) > 0 ? prompt : "? ") : "?? ");
+            fflush(stdout);
+            char* line = read_line();
+            output_column = 0;
+            if (line == NULL) {
+                usb_throw(USB_ERR_FORMAT);
+                return;
+            }
+            char* rest = line;
+            while (valid && rest != NULL) {
+                char* field = next_field(&rest);
+                if (index == count) {
+                    print("?EXTRA IGNORED");
+                    break;
+                }
+                valid = parse_value(field, &values[index++]);
+            }
+            free(line);
+        }
+        if (valid) {
+            return;
+        }
+        if (error_handler != NULL) {
+            default_value(&values[index - 1]);
+            usb_throw(USB_ERR_FORMAT);
+            return;
+        }
+        print("?REENTER");
+    }
+}
+
+// GET into a variable of the type the value is tagged with, keys that don't fit the variable are ignored unless an ONERR handler takes care of the Format error
+void get_value(usb_value* value) {
+    default_value(value);
+    while (true) {
+        char* key = get("");
+        if (*key == '\0') {
+            usb_throw(USB_ERR_FORMAT);
+            return;
+        }
+        if (parse_value(key, value)) {
+            return;
+        }
+        default_value(value);
+        if (error_handler != NULL) {
+            usb_throw(USB_ERR_FORMAT);
+            return;
+        }
+    }
+}
+
+// === collections ===
+
+usb_list* list_new() {
+    return calloc(1, sizeof(usb_list));
+}
+
+static void list_reserve(usb_list* list, long length) {
+    if (length <= list->capacity) {
+        return;
+    }
+    list->capacity = list->capacity == 0 ? 8 : list->capacity * 2;
+    if (list->capacity < length) {
+        list->capacity = length;
+    }
+    list->items = realloc(list->items, list->capacity * sizeof(long));
+}
+
+void list_push(usb_list* list, long value) {
+    list_reserve(list, list->length + 1);
+    list->items[list->length++] = value;
+}
+
+long list_pop(usb_list* list) {
+    if (list->length == 0) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return 0;
+    }
+    return list->items[--list->length];
+}
+
+// insert a value before the (0 based) index, the length of the list appends it
+void list_insert(usb_list* list, long index, long value) {
+    if (index < 0 || index > list->length) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return;
+    }
+    list_reserve(list, list->length + 1);
+    memmove(list->items + index + 1, list->items + index, (list->length - index) * sizeof(long));
+    list->items[index] = value;
+    list->length++;
+}
+
+void list_remove(usb_list* list, long index) {
+    if (index < 0 || index >= list->length) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return;
+    }
+    list->length--;
+    memmove(list->items + index, list->items + index + 1, (list->length - index) * sizeof(long));
+}
+
+long list_get(usb_list* list, long index) {
+    if (index < 0 || index >= list->length) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return 0;
+    }
+    return list->items[index];
+}
+
+void list_set(usb_list* list, long index, long value) {
+    if (index < 0 || index >= list->length) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return;
+    }
+    list->items[index] = value;
+}
+
+usb_map* map_new() {
+    return calloc(1, sizeof(usb_map));
+}
+
+// FNV-1a
+static unsigned long hash_key(char* key) {
+    unsigned long hash = 14695981039346656037UL;
+    for (; *key != '\0'; key++) {
+        hash = (hash ^ (unsigned char)*key) * 1099511628211UL;
+    }
+    return hash;
+}
+
+// the slot of the hash table that holds the key, or the empty slot it would go in
+static long map_slot(usb_map* map, char* key) {
+    long mask = map->slot_count - 1;
+    long slot = hash_key(key) & mask;
+    while (map->slots[slot] != 0 && strcmp(map->keys[map->slots[slot] - 1], key) != 0) {
+        slot = (slot + 1) & mask;
+    }
+    return slot;
+}
+
+// rebuild the hash table from the keys, it is kept at most half full
+static void map_rehash(usb_map* map) {
+    long slot_count = 16;
+    while (slot_count < 2 * map->capacity) {
+        slot_count *= 2;
+    }
+    free(map->slots);
+    map->slot_count = slot_count;
+    map->slots = calloc(slot_count, sizeof(long));
+    for (long i = 0; i < map->length; i++) {
+        map->slots[map_slot(map, map->keys[i])] = i + 1;
+    }
+}
+
+// the index of the key in the order the keys were added, -1 if the map doesn't hold it
+static long map_find(usb_map* map, char* key) {
+    if (map->length == 0) {
+        return -1;
+    }
+    return map->slots[map_slot(map, key)] - 1;
+}
+
+long map_get(usb_map* map, char* key) {
+    long index = map_find(map, key);
+    if (index < 0) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return 0;
+    }
+    return map->values[index];
+}
+
+void map_set(usb_map* map, char* key, long value) {
+    long index = map_find(map, key);
+    if (index >= 0) {
+        map->values[index] = value;
+        return;
+    }
+    if (map->length == map->capacity) {
+        map->capacity = map->capacity == 0 ? 8 : map->capacity * 2;
+        map->keys = realloc(map->keys, map->capacity * sizeof(char*));
+        map->values = realloc(map->values, map->capacity * sizeof(long));
+        map_rehash(map);
+    }
+    map->keys[map->length] = strdup(key);
+    map->values[map->length] = value;
+    map->length++;
+    map->slots[map_slot(map, key)] = map->length;
+}
+
+bool map_has(usb_map* map, char* key) {
+    return map_find(map, key) >= 0;
+}
+
+// the keys after the removed one move up to keep the order they were added in, so the hash table is rebuilt
+void map_remove(usb_map* map, char* key) {
+    long index = map_find(map, key);
+    if (index < 0) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return;
+    }
+    free(map->keys[index]);
+    map->length--;
+    memmove(map->keys + index, map->keys + index + 1, (map->length - index) * sizeof(char*));
+    memmove(map->values + index, map->values + index + 1, (map->length - index) * sizeof(long));
+    map_rehash(map);
+}
+
+usb_list* map_keys(usb_map* map) {
+    usb_list* keys = list_new();
+    list_reserve(keys, map->length);
+    for (long i = 0; i < map->length; i++) {
+        keys->items[i] = (long)map->keys[i];
+    }
+    keys->length = map->length;
+    return keys;
+}
+
+// records are stored in collections as a pointer to a copy of them, so changing the variable the record came from doesn't change the element
+void* copy_record(void* record, long size) {
+    void* copy = malloc(size);
+    memcpy(copy, record, size);
+    return copy;
+}
+
+// === positional ===
+
+void penum(usb_enum_entry* table, long count, long enum_value) {
+    for (long i = 0; i < count; i++) {
+        if (table[i].value == enum_value) {
+            print(table[i].name);
+            return;
+        }
+    }
+    // values outside of the enum are printed as the plain integer they are
+    printf("%ld\n", enum_value);
+}
+
+// === date and time ===
+
+long day(long days) {
+    return days * 86400;
+}
+
+long hour(long hours) {
+    return hours * 3600;
+}
+
+long minute(long minutes) {
+    return minutes * 60;
+}
+
+long second(long seconds) {
+    return seconds;
+}
+
+usb_date date() {
+    return (usb_date)time(NULL);
+}
+
+// dates are stored in UTC and broken down in the local timezone (TZ)
+static struct tm local_time(usb_date date) {
+    time_t seconds = (time_t)date;
+    struct tm broken_down;
+    localtime_r(&seconds, &broken_down);
+    return broken_down;
+}
+
+double _time(usb_date date) {
+    struct tm broken_down = local_time(date);
+    return broken_down.tm_hour + broken_down.tm_min / 60.0 + broken_down.tm_sec / 3600.0;
+}
+
+// === file io ===
+
+#define USB_MAX_FILES 64
+
+// a file opened with OPEN, every read continues at the cursor of the previous one
+typedef struct {
+    FILE* handle;
+    bool readable;
+    bool writable;
+    bool appendable;
+    long cursor;
+} usb_file;
+
+// descriptors handed to USB code are the index into this table + 1, so 0 is never a valid file
+static usb_file files[USB_MAX_FILES];
+
+static void close_files() {
+    for (long i = 0; i < USB_MAX_FILES; i++) {
+        _close(i + 1);
+    }
+}
+
+// the mode of OPEN is made out of the characters r (read), w (write) and a (append), each used at most once
+long _open(char* path, char* mode) {
+    static bool registered = false;
+    bool readable = false, writable = false, appendable = false;
+    bool valid = strlen(mode) > 0;
+    for (char* c = mode; *c != '\0' && valid; c++) {
+        bool* flag = *c == 'r' ? &readable : *c == 'w' ? &writable : *c == 'a' ? &appendable : NULL;
+        valid = flag != NULL && !*flag;
+        if (valid) {
+            *flag = true;
+        }
+    }
+    if (!valid) {
+        usb_throw(USB_ERR_INVALID_MODE);
+        return 0;
+    }
+
+    long index = 0;
+    while (index < USB_MAX_FILES && files[index].handle != NULL) {
+        index++;
+    }
+    if (index == USB_MAX_FILES) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return 0;
+    }
+    // files opened for writing are created, but never truncated until WRITE is used
+    FILE* handle = fopen(path, writable || appendable ? "r+" : "r");
+    if (handle == NULL && (writable || appendable)) {
+        handle = fopen(path, "w+");
+    }
+    if (handle == NULL) {
+        usb_throw(USB_ERR_FILE_NOT_FOUND);
+        return 0;
+    }
+    if (!registered) {
+        atexit(close_files);
+        registered = true;
+    }
+    files[index] = (usb_file){handle, readable, writable, appendable, 0};
+    return index + 1;
+}
+
+// look up an open file, throwing if the descriptor is invalid or the file wasn't opened with a mode that allows the operation
+static usb_file* get_file(long file, bool read, bool write, bool append) {
+    if (file < 1 || file > USB_MAX_FILES || files[file - 1].handle == NULL) {
+        usb_throw(USB_ERR_INVALID_FILE);
+        return NULL;
+    }
+    usb_file* f = &files[file - 1];
+    if ((read && !f->readable) || (write && !f->writable) ||
+        (append && !f->writable && !f->appendable)) {
+        usb_throw(USB_ERR_INVALID_MODE);
+        return NULL;
+    }
+    fseek(f->handle, f->cursor, SEEK_SET);
+    return f;
+}
+
+char* _read(long file, long count) {
+    usb_file* f = get_file(file, true, false, false);
+    if (f == NULL) {
+        return "";
+    }
+    if (count < 0) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return "";
+    }
+    char* buffer = malloc(count + 1);
+    size_t length = fread(buffer, 1, count, f->handle);
+    buffer[length] = '\0';
+    f->cursor += length;
+    return buffer;
+}
+
+char* readline(long file) {
+    usb_file* f = get_file(file, true, false, false);
+    if (f == NULL) {
+        return "";
+    }
+    char* line = NULL;
+    size_t capacity = 0;
+    ssize_t length = getline(&line, &capacity, f->handle);
+    if (length < 0) {
+        free(line);
+        return "";
+    }
+    f->cursor += length;
+    if (line[length - 1] == '\n') {
+        line[length - 1] = '\0';
+    }
+    return line;
+}
+
+char* readall(long file) {
+    usb_file* f = get_file(file, true, false, false);
+    if (f == NULL) {
+        return "";
+    }
+    size_t length = 0;
+    size_t capacity = 256;
+    char* buffer = malloc(capacity);
+    size_t read;
+    while ((read = fread(buffer + length, 1, capacity - length - 1, f->handle)) > 0) {
+        length += read;
+        if (length + 1 == capacity) {
+            capacity *= 2;
+            buffer = realloc(buffer, capacity);
+        }
+    }
+    buffer[length] = '\0';
+    f->cursor += length;
+    return buffer;
+}
+
+// WRITE replaces the content of the file and moves the cursor back to its start
+void _write(long file, char* text) {
+    usb_file* f = get_file(file, false, true, false);
+    if (f == NULL) {
+        return;
+    }
+    fflush(f->handle);
+    if (ftruncate(fileno(f->handle), 0) != 0) {
+        usb_throw(USB_ERR_INVALID_FILE);
+        return;
+    }
+    rewind(f->handle);
+    fputs(text, f->handle);
+    fflush(f->handle);
+    f->cursor = 0;
+}
+
+void append(long file, char* text) {
+    usb_file* f = get_file(file, false, false, true);
+    if (f == NULL) {
+        return;
+    }
+    fseek(f->handle, 0, SEEK_END);
+    fputs(text, f->handle);
+    fflush(f->handle);
+}
+
+void _close(long file) {
+    if (file < 1 || file > USB_MAX_FILES || files[file - 1].handle == NULL) {
+        return;
+    }
+    fclose(files[file - 1].handle);
+    files[file - 1].handle = NULL;
+}
+
+// === filesystem ===
+
+// throws the error matching errno after a failed call
+static void throw_errno() {
+    switch (errno) {
+    case ENOENT:
+    case ENOTDIR:
+        usb_throw(USB_ERR_FILE_NOT_FOUND);
+        break;
+    case EACCES:
+    case EPERM:
+    case EROFS:
+        usb_throw(USB_ERR_PERMISSION_DENIED);
+        break;
+    default:
+        usb_throw(USB_ERR_FILESYSTEM);
+    }
+}
+
+static int compare_names(const void* left, const void* right) {
+    return strcmp(*(char**)left, *(char**)right);
+}
+
+// the sorted names in the directory without . and .., the current directory if no path is given
+usb_list* dir(char* optional_path) {
+    usb_list* names = list_new();
+    DIR* directory = opendir(strlen(optional_path) > 0 ? optional_path : ".");
+    if (directory == NULL) {
+        throw_errno();
+        return names;
+    }
+    for (struct dirent* entry = readdir(directory); entry != NULL; entry = readdir(directory)) {
+        if (strcmp(entry->d_name, ".") != 0 && strcmp(entry->d_name, "..") != 0) {
+            list_push(names, (long)strdup(entry->d_name));
+        }
+    }
+    closedir(directory);
+    if (names->length > 0) {
+        qsort(names->items, names->length, sizeof(long), compare_names);
+    }
+    return names;
+}
+
+bool exists(char* path) {
+    struct stat info;
+    return stat(path, &info) == 0;
+}
+
+long filesize(char* path) {
+    struct stat info;
+    if (stat(path, &info) != 0) {
+        throw_errno();
+        return 0;
+    }
+    if (!S_ISREG(info.st_mode)) {
+        usb_throw(USB_ERR_FILESYSTEM);
+        return 0;
+    }
+    return info.st_size;
+}
+
+void _mkdir(char* path) {
+    if (mkdir(path, 0777) != 0) {
+        throw_errno();
+    }
+}
+
+// removes a file or an empty directory
+void delete(char* path) {
+    if (remove(path) != 0) {
+        throw_errno();
+    }
+}
+
+void _rename(char* from, char* to) {
+    if (rename(from, to) != 0) {
+        throw_errno();
+    }
+}
+
+// the copy gets the permissions of the original, an existing file is overwritten
+void copy(char* from, char* to) {
+    struct stat info;
+    int source = open(from, O_RDONLY);
+    int target = -1;
+    bool copied = source >= 0 && fstat(source, &info) == 0;
+    if (copied) {
+        target = open(to, O_WRONLY | O_CREAT | O_TRUNC, info.st_mode & 0777);
+        copied = target >= 0;
+    }
+    char buffer[65536];
+    ssize_t count = 0;
+    while (copied && (count = read(source, buffer, sizeof(buffer))) > 0) {
+        copied = write(target, buffer, count) == count;
+    }
+    copied = copied && count == 0;
+    // the files are closed before the handler runs, it may never come back here
+    int error = errno;
+    if (source >= 0) {
+        close(source);
+    }
+    if (target >= 0) {
+        close(target);
+    }
+    if (!copied) {
+        errno = error;
+        throw_errno();
+    }
+}
+
+// === terminal colors ===
+
+// the most bits per color the program was compiled for, see --color
+#ifndef USB_COLOR_DEPTH
+#define USB_COLOR_DEPTH 8
+#endif
+
+// the RGB values of the 16 palette colors (as used by xterm), used to find the closest palette color of an RGB color
+static const long palette[16] = {
+    0x000000, 0xcd0000, 0x00cd00, 0xcdcd00, 0x0000ee, 0xcd00cd, 0x00cdcd, 0xe5e5e5,
+    0x7f7f7f, 0xff0000, 0x00ff00, 0xffff00, 0x5c5cff, 0xff00ff, 0x00ffff, 0xffffff,
+};
+
+// escape sequences are only written to terminals, not to files or pipes
+static bool is_terminal() {
+    char* term = getenv("TERM");
+    return isatty(STDOUT_FILENO) && term != NULL && strcmp(term, "dumb") != 0;
+}
+
+static void reset_colors() {
+    fputs("\x1b[0m", stdout);
+}
+
+// the color depth (in bits) that is actually used, 0 when colors are disabled by NO_COLOR or because stdout isn't a terminal
+static long color_depth() {
+    static long depth = -1;
+    if (depth >= 0) {
+        return depth;
+    }
+    char* no_color = getenv("NO_COLOR");
+    char* colorterm = getenv("COLORTERM");
+    if ((no_color != NULL && no_color[0] != '\0') || !is_terminal()) {
+        depth = 0;
+        return depth;
+    }
+    depth = 4;
+    if (colorterm != NULL && (strcmp(colorterm, "truecolor") == 0 || strcmp(colorterm, "24bit") == 0)) {
+        depth = 24;
+    } else if (strstr(getenv("TERM"), "256color") != NULL) {
+        depth = 8;
+    }
+    if (depth > USB_COLOR_DEPTH) {
+        depth = USB_COLOR_DEPTH;
+    }
+    atexit(reset_colors);
+    return depth;
+}
+
+static long closest_palette_color(long r, long g, long b) {
+    long closest = 0;
+    long closest_distance = -1;
+    for (long i = 0; i < 16; i++) {
+        long dr = r - ((palette[i] >> 16) & 0xff);
+        long dg = g - ((palette[i] >> 8) & 0xff);
+        long db = b - (palette[i] & 0xff);
+        long distance = dr * dr + dg * dg + db * db;
+        if (closest_distance < 0 || distance < closest_distance) {
+            closest = i;
+            closest_distance = distance;
+        }
+    }
+    return closest;
+}
+
+// the index of an RGB color in the 6x6x6 color cube or the grayscale ramp of the 256 color palette
+static long closest_256_color(long r, long g, long b) {
+    if (r == g && g == b) {
+        if (r < 8) {
+            return 16;
+        }
+        if (r > 238) {
+            return 231;
+        }
+        return 232 + (r - 8) / 10;
+    }
+    long cube[3] = {r, g, b};
+    for (int i = 0; i < 3; i++) {
+        cube[i] = cube[i] < 48 ? 0 : cube[i] < 115 ? 1 : (cube[i] - 35) / 40;
+    }
+    return 16 + 36 * cube[0] + 6 * cube[1] + cube[2];
+}
+
+// emit the escape sequence selecting a color, `base` is 30 for the foreground and 40 for the background.
+// Palette colors (the named colors, stored as -(index + 1)) always use the terminal palette, RGB colors are downgraded to the available depth
+static void set_color(long color, long base) {
+    long depth = color_depth();
+    if (color < -16 || color > 0xffffff) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return;
+    }
+    if (depth == 0) {
+        return;
+    }
+    long r = (color >> 16) & 0xff, g = (color >> 8) & 0xff, b = color & 0xff;
+    if (color >= 0 && depth == 24) {
+        printf("\x1b[%ld;2;%ld;%ld;%ldm", base + 8, r, g, b);
+        return;
+    }
+    if (color >= 0 && depth == 8) {
+        printf("\x1b[%ld;5;%ldm", base + 8, closest_256_color(r, g, b));
+        return;
+    }
+    long index = color < 0 ? -color - 1 : closest_palette_color(r, g, b);
+    printf("\x1b[%ldm", index < 8 ? base + index : base + 60 + index - 8);
+}
+
+void fcolor(long color) {
+    set_color(color, 30);
+}
+
+void bcolor(long color) {
+    set_color(color, 40);
+}
+
+void icolor() {
+    if (color_depth() > 0) {
+        fputs("\x1b[7m", stdout);
+    }
+}
+
+void rcolor() {
+    if (color_depth() > 0) {
+        fputs("\x1b[27;39;49m", stdout);
+    }
+}
+
+// === cursor and screen ===
+
+// the size of the terminal, 80x24 when stdout isn't a terminal
+void screen(long* columns, long* rows) {
+    struct winsize size;
+    if (ioctl(STDOUT_FILENO, TIOCGWINSZ, &size) == 0 && size.ws_col > 0 && size.ws_row > 0) {
+        *columns = size.ws_col;
+        *rows = size.ws_row;
+        return;
+    }
+    *columns = 80;
+    *rows = 24;
+}
+
+static long clamp(long value, long min, long max) {
+    return value < min ? min : value > max ? max : value;
+}
+
+void home() {
+    if (is_terminal()) {
+        fputs("\x1b[2J\x1b[H", stdout);
+        output_column = 0;
+    }
+}
+
+// HTAB and VTAB count from 0, positions outside of the screen are clamped to its edges
+void htab(long column) {
+    long columns, rows;
+    screen(&columns, &rows);
+    if (is_terminal()) {
+        output_column = clamp(column, 0, columns - 1);
+        printf("\x1b[%ldG", output_column + 1);
+    }
+}
+
+void vtab(long row) {
+    long columns, rows;
+    screen(&columns, &rows);
+    if (is_terminal()) {
+        printf("\x1b[%ldd", clamp(row, 0, rows - 1) + 1);
+    }
+}
+
+void flash(unsigned int type, void* value) {
+    if (is_terminal()) {
+        fputs(_bool(type, value) ? "\x1b[?12h" : "\x1b[?12l", stdout);
+    }
+}
+
+void inverse() {
+    if (is_terminal()) {
+        fputs("\x1b[7m", stdout);
+    }
+}
+
+void normal() {
+    if (is_terminal()) {
+        fputs("\x1b[27m", stdout);
+    }
+}
+
+// === enclosed ===
+
+char* str(unsigned int type, void* value) {
+    char buffer[64];
+    switch (type) {
+    case USB_STRING:
+        return *(char**)value;
+    case USB_BOOL:
+        return *(long*)value ? "TRUE" : "FALSE";
+    case USB_DATE:
+        return formatdate(*(usb_date*)value, "%Y-%m-%d %H:%M:%S");
+    case USB_FLOAT:
+        format_float(*(double*)value, buffer, sizeof(buffer));
+        break;
+    default:
+        snprintf(buffer, sizeof(buffer), "%ld", *(long*)value);
+        break;
+    }
+    return strdup(buffer);
+}
+
+// a whole float as an integer, NaN and values outside of the range of an integer throw an OutOfRange error
+static long to_long(double value) {
+    // -2^63 is the smallest integer, 2^63 is one past the largest
+    if (isnan(value) || value < -0x1p63 || value >= 0x1p63) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return 0;
+    }
+    return (long)value;
+}
+
+long _int(unsigned int type, void* value) {
+    switch (type) {
+    case USB_FLOAT:
+        // like AppleSoft INT rounds down, INT(-1.5) is -2
+        return to_long(floor(*(double*)value));
+    case USB_STRING:
+        return strtol(*(char**)value, NULL, 10);
+    default:
+        return *(long*)value;
+    }
+}
+
+double _float(unsigned int type, void* value) {
+    switch (type) {
+    case USB_FLOAT:
+        return *(double*)value;
+    case USB_STRING:
+        return strtod(*(char**)value, NULL);
+    default:
+        return (double)*(long*)value;
+    }
+}
+
+bool _bool(unsigned int type, void* value) {
+    switch (type) {
+    case USB_FLOAT:
+        return *(double*)value != 0.0;
+    case USB_STRING:
+        return strlen(*(char**)value) > 0;
+    default:
+        return *(long*)value != 0;
+    }
+}
+
+long sgn(long value) {
+    return (value > 0) - (value < 0);
+}
+
+long sgn_f(double value) {
+    return (value > 0) - (value < 0);
+}
+
+long _abs(long value) {
+    return value < 0 ? -value : value;
+}
+
+double _abs_f(double value) {
+    return fabs(value);
+}
+
+long min(long left, long right) {
+    return left < right ? left : right;
+}
+
+double min_f(double left, double right) {
+    return fmin(left, right);
+}
+
+long max(long left, long right) {
+    return left > right ? left : right;
+}
+
+double max_f(double left, double right) {
+    return fmax(left, right);
+}
+
+// === math ===
+// angles are in radians, arguments outside of a function's domain throw an OutOfRange error like AppleSoft's ILLEGAL QUANTITY
+
+double sqr(double value) {
+    if (value < 0) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return 0;
+    }
+    return sqrt(value);
+}
+
+double _sin(double value) {
+    return sin(value);
+}
+
+double _cos(double value) {
+    return cos(value);
+}
+
+double _tan(double value) {
+    return tan(value);
+}
+
+double atn(double value) {
+    return atan(value);
+}
+
+double _log(double value) {
+    if (value <= 0) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return 0;
+    }
+    return log(value);
+}
+
+double _exp(double value) {
+    return exp(value);
+}
+
+long _floor(double value) {
+    return to_long(floor(value));
+}
+
+long _ceil(double value) {
+    return to_long(ceil(value));
+}
+
+// halfway values round away from zero, ROUND(2.5) is 3 and ROUND(-2.5) is -3
+long _round(double value) {
+    return to_long(round(value));
+}
+
+// the pattern uses the strftime conversions e.g. `%d.%m.%Y`
+char* formatdate(usb_date date, char* pattern) {
+    char buffer[256];
+    struct tm broken_down = local_time(date);
+    if (strftime(buffer, sizeof(buffer), pattern, &broken_down) == 0) {
+        buffer[0] = '\0';
+    }
+    return strdup(buffer);
+}
+
+// === strings ===
+// strings are UTF-8, positions and lengths count code points rather than bytes. Positions are 1 based like in AppleSoft
+
+static bool is_continuation(char byte) {
+    return (byte & 0xC0) == 0x80;
+}
+
+// the byte offset of the code point at the (0 based) index, or of the terminator if the string is shorter
+static size_t utf8_offset(const char* text, long index) {
+    size_t offset = 0;
+    while (index > 0 && text[offset] != '\0') {
+        offset++;
+        while (is_continuation(text[offset])) {
+            offset++;
+        }
+        index--;
+    }
+    return offset;
+}
+
+// decode the code point at the start of text and store the length of its sequence, invalid sequences decode to -1
+static long utf8_decode(const char* text, size_t* width) {
+    unsigned char lead = (unsigned char)text[0];
+    long code;
+    size_t length;
+    if (lead < 0x80) {
+        *width = 1;
+        return lead;
+    } else if ((lead & 0xE0) == 0xC0) {
+        code = lead & 0x1F;
+        length = 2;
+    } else if ((lead & 0xF0) == 0xE0) {
+        code = lead & 0x0F;
+        length = 3;
+    } else if ((lead & 0xF8) == 0xF0) {
+        code = lead & 0x07;
+        length = 4;
+    } else {
+        *width = 1;
+        return -1;
+    }
+    for (size_t i = 1; i < length; i++) {
+        if (!is_continuation(text[i])) {
+            *width = 1;
+            return -1;
+        }
+        code = (code << 6) | (text[i] & 0x3F);
+    }
+    *width = length;
+    return code;
+}
+
+// encode a code point into out, returning the number of bytes written or 0 if it isn't a valid code point
+static size_t utf8_encode(long code, char* out) {
+    if (code < 0 || code > 0x10FFFF || (code >= 0xD800 && code <= 0xDFFF)) {
+        return 0;
+    }
+    if (code < 0x80) {
+        out[0] = (char)code;
+        return 1;
+    }
+    if (code < 0x800) {
+        out[0] = (char)(0xC0 | (code >> 6));
+        out[1] = (char)(0x80 | (code & 0x3F));
+        return 2;
+    }
+    if (code < 0x10000) {
+        out[0] = (char)(0xE0 | (code >> 12));
+        out[1] = (char)(0x80 | ((code >> 6) & 0x3F));
+        out[2] = (char)(0x80 | (code & 0x3F));
+        return 3;
+    }
+    out[0] = (char)(0xF0 | (code >> 18));
+    out[1] = (char)(0x80 | ((code >> 12) & 0x3F));
+    out[2] = (char)(0x80 | ((code >> 6) & 0x3F));
+    out[3] = (char)(0x80 | (code & 0x3F));
+    return 4;
+}
+
+long len(char* text) {
+    long count = 0;
+    for (size_t i = 0; text[i] != '\0'; i++) {
+        count += !is_continuation(text[i]);
+    }
+    return count;
+}
+
+long asc(char* text) {
+    if (text[0] == '\0') {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return 0;
+    }
+    size_t width;
+    long code = utf8_decode(text, &width);
+    return code < 0 ? (unsigned char)text[0] : code;
+}
+
+char* chr(long code) {
+    char buffer[5] = {0};
+    if (utf8_encode(code, buffer) == 0) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return "";
+    }
+    return strdup(buffer);
+}
+
+// the number at the start of the text like AppleSoft's VAL, text that doesn't start with a number is 0
+double val(char* text) {
+    return strtod(text, NULL);
+}
+
+char* left(char* text, long count) {
+    if (count < 0) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return "";
+    }
+    return strndup(text, utf8_offset(text, count));
+}
+
+char* right(char* text, long count) {
+    if (count < 0) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return "";
+    }
+    long length = len(text);
+    if (count >= length) {
+        return strdup(text);
+    }
+    return strdup(text + utf8_offset(text, length - count));
+}
+
+char* mid(char* text, long start, long optional_length) {
+    if (start < 1 || (optional_length < 0 && optional_length != USB_MISSING)) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return "";
+    }
+    char* begin = text + utf8_offset(text, start - 1);
+    if (optional_length == USB_MISSING) {
+        return strdup(begin);
+    }
+    return strndup(begin, utf8_offset(begin, optional_length));
+}
+
+// the position of search in text starting at optional_start, or 0 if text doesn't contain it
+long instr(char* text, char* search, long optional_start) {
+    long start = optional_start == USB_MISSING ? 1 : optional_start;
+    if (start < 1) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return 0;
+    }
+    char* begin = text + utf8_offset(text, start - 1);
+    char* found = strstr(begin, search);
+    if (found == NULL) {
+        return 0;
+    }
+    long position = start;
+    for (char* c = begin; c < found; c++) {
+        position += !is_continuation(*c);
+    }
+    return position;
+}
+
+// case mapping needs a UTF-8 locale, without one only ASCII letters are mapped
+static char* map_case(char* text, bool upper) {
+    static locale_t locale = (locale_t)0;
+    static bool initialized = false;
+    if (!initialized) {
+        locale = newlocale(LC_CTYPE_MASK, "C.UTF-8", (locale_t)0);
+        initialized = true;
+    }
+    // a mapped code point never needs more than 4 bytes
+    char* result = malloc(4 * strlen(text) + 1);
+    size_t length = 0;
+    for (size_t i = 0; text[i] != '\0';) {
+        size_t width;
+        long code = utf8_decode(text + i, &width);
+        size_t written = 0;
+        if (code >= 0) {
+            wint_t mapped;
+            if (locale != (locale_t)0) {
+                mapped = upper ? towupper_l((wint_t)code, locale) : towlower_l((wint_t)code, locale);
+            } else {
+                mapped = upper ? towupper((wint_t)code) : towlower((wint_t)code);
+            }
+            written = utf8_encode((long)mapped, result + length);
+        }
+        if (written == 0) {
+            // invalid sequences are copied as they are
+            memcpy(result + length, text + i, width);
+            written = width;
+        }
+        length += written;
+        i += width;
+    }
+    result[length] = '\0';
+    return result;
+}
+
+char* ucase(char* text) {
+    return map_case(text, true);
+}
+
+char* lcase(char* text) {
+    return map_case(text, false);
+}
+
+char* trim(char* text) {
+    while (isspace((unsigned char)*text)) {
+        text++;
+    }
+    size_t length = strlen(text);
+    while (length > 0 && isspace((unsigned char)text[length - 1])) {
+        length--;
+    }
+    return strndup(text, length);
+}
+
+char* replace(char* text, char* search, char* replacement) {
+    size_t search_length = strlen(search);
+    if (search_length == 0) {
+        return strdup(text);
+    }
+    size_t count = 0;
+    for (char* found = strstr(text, search); found != NULL; found = strstr(found + search_length, search)) {
+        count++;
+    }
+    size_t replacement_length = strlen(replacement);
+    char* result = malloc(strlen(text) + count * replacement_length - count * search_length + 1);
+    char* out = result;
+    for (char* found = strstr(text, search); found != NULL; found = strstr(text, search)) {
+        memcpy(out, text, found - text);
+        out += found - text;
+        memcpy(out, replacement, replacement_length);
+        out += replacement_length;
+        text = found + search_length;
+    }
+    strcpy(out, text);
+    return result;
+}
+
+char* repeat(char* text, long count) {
+    if (count < 0) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return "";
+    }
+    size_t length = strlen(text);
+    char* result = malloc(length * count + 1);
+    for (long i = 0; i < count; i++) {
+        memcpy(result + i * length, text, length);
+    }
+    result[length * count] = '\0';
+    return result;
+}
+
+// an empty separator splits the string into its characters
+usb_list* split(char* text, char* separator) {
+    usb_list* parts = list_new();
+    size_t separator_length = strlen(separator);
+    if (separator_length == 0) {
+        while (*text != '\0') {
+            size_t width = utf8_offset(text, 1);
+            list_push(parts, (long)strndup(text, width));
+            text += width;
+        }
+        return parts;
+    }
+    for (char* found = strstr(text, separator); found != NULL; found = strstr(text, separator)) {
+        list_push(parts, (long)strndup(text, found - text));
+        text = found + separator_length;
+    }
+    list_push(parts, (long)strdup(text));
+    return parts;
+}
+
+// === random ===
+// a xorshift64* generator, seeded from USB_SEED if it is set so the output of a program can be reproduced
+
+static unsigned long random_state = 0;
+static double last_random = 0;
+
+// spread a seed over all bits of the state, xorshift can't leave a state of 0
+static void seed_random(unsigned long seed) {
+    seed += 0x9E3779B97F4A7C15UL;
+    seed = (seed ^ (seed >> 30)) * 0xBF58476D1CE4E5B9UL;
+    seed = (seed ^ (seed >> 27)) * 0x94D049BB133111EBUL;
+    seed ^= seed >> 31;
+    random_state = seed != 0 ? seed : 1;
+}
+
+static void seed_default() {
+    char* seed = getenv("USB_SEED");
+    char* end;
+    if (seed != NULL && *seed != '\0') {
+        long value = strtol(seed, &end, 10);
+        if (*end == '\0') {
+            seed_random((unsigned long)value);
+            return;
+        }
+    }
+    // mixing in the current state keeps two seeds in the same nanosecond apart
+    struct timespec now;
+    clock_gettime(CLOCK_REALTIME, &now);
+    unsigned long entropy = (unsigned long)now.tv_sec * 1000000000UL + (unsigned long)now.tv_nsec;
+    seed_random(entropy ^ ((unsigned long)getpid() << 32) ^ random_state);
+}
+
+static unsigned long next_random() {
+    if (random_state == 0) {
+        seed_default();
+    }
+    random_state ^= random_state >> 12;
+    random_state ^= random_state << 25;
+    random_state ^= random_state >> 27;
+    return random_state * 0x2545F4914F6CDD1DUL;
+}
+
+void randomize(long optional_seed) {
+    if (optional_seed == USB_MISSING) {
+        seed_default();
+        return;
+    }
+    seed_random((unsigned long)optional_seed);
+}
+
+// like AppleSoft a positive value gives the next number in [0, 1), 0 repeats the last number and a negative value reseeds first
+double rnd(double value) {
+    if (value == 0) {
+        return last_random;
+    }
+    if (value < 0) {
+        unsigned long seed;
+        memcpy(&seed, &value, sizeof(seed));
+        seed_random(seed);
+    }
+    // the upper 53 bits fill the mantissa of a double
+    last_random = (next_random() >> 11) * (1.0 / 9007199254740992.0);
+    return last_random;
+}
+
+// a number from low to high (inclusive)
+long rndint(long low, long high) {
+    if (low > high) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return low;
+    }
+    unsigned long range = (unsigned long)high - (unsigned long)low + 1;
+    if (range == 0) {
+        // the whole range of a long
+        return (long)next_random();
+    }
+    // reject the numbers past the last multiple of range so every number is equally likely
+    unsigned long limit = -range % range;
+    unsigned long number;
+    do {
+        number = next_random();
+    } while (number < limit);
+    return (long)((unsigned long)low + number % range);
+}
+
+// spaces up to the (1 based) column, nothing if the cursor is already past it. Meant for the AppleSoft form of PRINT, where every item is printed before the next one is evaluated
+char* tab(long column) {
+    if (column < 1) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return "";
+    }
+    long spaces = column - 1 - output_column;
+    return spc(spaces > 0 ? spaces : 0);
+}
+
+char* spc(long count) {
+    return repeat(" ", count);
+}
+
+// === environment ===
+
+// the arguments the program was started with, the first one is the path of the program
+static long argument_count = 0;
+static char** arguments = NULL;
+
+// called at the start of main with the arguments main got
+void usb_start(long count, char** values) {
+    argument_count = count;
+    arguments = values;
+}
+
+// the number of arguments after the path of the program, like $# in a shell
+long argc() {
+    return argument_count > 0 ? argument_count - 1 : 0;
+}
+
+// ARGV$(0) is the path of the program and ARGV$(1) the first argument
+char* argv(long index) {
+    if (index < 0 || index >= argument_count) {
+        usb_throw(USB_ERR_OUT_OF_RANGE);
+        return "";
+    }
+    return arguments[index];
+}
+
+// an unset variable is ""
+char* env(char* name) {
+    char* value = getenv(name);
+    return value != NULL ? value : "";
+}
+
+// the name can't be empty or contain a `=`
+void _setenv(char* name, char* value) {
+    if (setenv(name, value, 1) != 0) {
+        usb_throw(USB_ERR_FORMAT);
+    }
+}
+
+// === processes ===
+
+extern char** environ;
+
+// the standard error of the last SHELL or EXEC
+static char* process_errors = "";
+
+// like $(...) in a shell, captured output doesn't end with newlines
+static void trim_newlines(char* text) {
+    size_t length = strlen(text);
+    while (length > 0 && text[length - 1] == '\n') {
+        text[--length] = '\0';
+    }
+}
+
+// reads both pipes until they are closed, a single blocking read could deadlock on a child filling the other pipe
+static void drain_pipes(int output, int errors, char** captured_output) {
+    size_t output_size, errors_size;
+    FILE* output_stream = open_memstream(captured_output, &output_size);
+    FILE* errors_stream = open_memstream(&process_errors, &errors_size);
+    struct pollfd fds[2] = {{output, POLLIN, 0}, {errors, POLLIN, 0}};
+    FILE* streams[2] = {output_stream, errors_stream};
+    char buffer[4096];
+    int remaining = (output >= 0) + 1;
+    while (remaining > 0 && poll(fds, 2, -1) > 0) {
+        for (int i = 0; i < 2; i++) {
+            if (fds[i].fd < 0 || fds[i].revents == 0) {
+                continue;
+            }
+            ssize_t count = read(fds[i].fd, buffer, sizeof(buffer));
+            if (count > 0) {
+                fwrite(buffer, 1, count, streams[i]);
+                continue;
+            }
+            close(fds[i].fd);
+            fds[i].fd = -1;
+            remaining--;
+        }
+    }
+    fclose(output_stream);
+    fclose(errors_stream);
+    trim_newlines(process_errors);
+}
+
+// runs a program looked up in PATH with its standard error captured, the standard output too if `output` isn't NULL;
+// returns the exit status, 128 plus the signal if it was killed, or -1 if it couldn't be started
+static long run_process(char** arguments, char** output) {
+    int output_pipe[2] = {-1, -1};
+    int errors_pipe[2];
+    if (pipe(errors_pipe) != 0 || (output != NULL && pipe(output_pipe) != 0)) {
+        return -1;
+    }
+    posix_spawn_file_actions_t actions;
+    posix_spawn_file_actions_init(&actions);
+    posix_spawn_file_actions_adddup2(&actions, errors_pipe[1], STDERR_FILENO);
+    posix_spawn_file_actions_addclose(&actions, errors_pipe[0]);
+    posix_spawn_file_actions_addclose(&actions, errors_pipe[1]);
+    if (output != NULL) {
+        posix_spawn_file_actions_adddup2(&actions, output_pipe[1], STDOUT_FILENO);
+        posix_spawn_file_actions_addclose(&actions, output_pipe[0]);
+        posix_spawn_file_actions_addclose(&actions, output_pipe[1]);
+    }
+    // an uncaptured child writes to the same terminal, so anything PRINTed has to come first
+    fflush(stdout);
+    pid_t pid;
+    int failure = posix_spawnp(&pid, arguments[0], &actions, NULL, arguments, environ);
+    posix_spawn_file_actions_destroy(&actions);
+    close(errors_pipe[1]);
+    if (output != NULL) {
+        close(output_pipe[1]);
+    }
+    if (failure != 0) {
+        close(errors_pipe[0]);
+        if (output != NULL) {
+            close(output_pipe[0]);
+        }
+        process_errors = strdup(strerror(failure));
+        return -1;
+    }
+    char* ignored = NULL;
+    drain_pipes(output_pipe[0], errors_pipe[0], output != NULL ? output : &ignored);
+    free(ignored);
+    int status;
+    waitpid(pid, &status, 0);
+    return WIFEXITED(status) ? WEXITSTATUS(status) : 128 + WTERMSIG(status);
+}
+
+// runs the command with /bin/sh and returns what it printed
+char* shell(char* command) {
+    char* arguments[] = {"/bin/sh", "-c", command, NULL};
+    char* output = NULL;
+    long status = run_process(arguments, &output);
+    if (output == NULL) {
+        output = "";
+    }
+    trim_newlines(output);
+    if (status != 0) {
+        usb_throw(USB_ERR_PROCESS);
+        return "";
+    }
+    return output;
+}
+
+// the first item is the program, its output isn't captured
+long exec(usb_list* arguments) {
+    if (arguments->length == 0) {
+        usb_throw(USB_ERR_PROCESS);
+        return 0;
+    }
+    char** values = malloc((arguments->length + 1) * sizeof(char*));
+    for (long i = 0; i < arguments->length; i++) {
+        values[i] = (char*)arguments->items[i];
+    }
+    values[arguments->length] = NULL;
+    long status = run_process(values, NULL);
+    free(values);
+    if (status < 0) {
+        usb_throw(USB_ERR_PROCESS);
+        return 0;
+    }
+    return status;
+}
+
+char* _stderr() {
+    return process_errors;
+}
@@ -1,27 +1,186 @@
+#ifndef USBLIB_H
+#define USBLIB_H
+#include <limits.h>
+#include <stdbool.h>
+
+// type tags of dynamically typed (unsigned int type, void* value) arguments
+#define USB_INT 0
+#define USB_FLOAT 1
+#define USB_STRING 2
+#define USB_BOOL 3
+#define USB_DATE 4
+
+// functions suffixed with `_f` are the float overloads of the integer function without the suffix, e.g. `_abs_f` is `ABS` of a float
+
+// passed for an optional integer parameter (named `optional_<name>`) that was left out
+#define USB_MISSING LONG_MIN
+
+// error codes thrown by builtins, see usb_throw
+#define USB_ERR_FORMAT 1
+#define USB_ERR_OUT_OF_RANGE 2
+#define USB_ERR_FILE_NOT_FOUND 3
+#define USB_ERR_INVALID_MODE 4
+#define USB_ERR_INVALID_FILE 5
+#define USB_ERR_OUT_OF_DATA 6
+#define USB_ERR_TYPE_MISMATCH 7
+#define USB_ERR_PROCESS 8
+#define USB_ERR_PERMISSION_DENIED 9
+#define USB_ERR_FILESYSTEM 10
+#define USB_ERR_DIVISION_BY_ZERO 11
+
+// a single key of an enum table as generated for PENUM
+typedef struct {
+    long value;
+    char* name;
+} usb_enum_entry;
+
+// a value along with its type tag, e.g. of the data pool generated for DATA or the arguments of a PRINT format
+typedef struct {
+    long type;
+    long value;
+} usb_value;
+
+// a growable list of 8 byte values (the bits of a float, a pointer to a string or record, ...), see LIST.
+// The code generator reads `length` straight from the list and the items of a FOR EACH from `items`
+typedef struct {
+    long length;
+    long capacity;
+    long* items;
+} usb_list;
+
+// a map from strings to 8 byte values that keeps its keys in the order they were added, see MAP.
+// `slots` is an open addressing hash table of indices (+ 1) into `keys`, 0 marks an empty slot
+typedef struct {
+    long length;
+    long capacity;
+    char** keys;
+    long* values;
+    long* slots;
+    long slot_count;
+} usb_map;
+
+// a Unix epoch in seconds, stringified as a local date by str
+typedef long usb_date;
+
+// the statements of an ONERR, called by usb_throw
+typedef void (*usb_handler)();
+
+//internal
+char* str_concat(char* left, char* right);
+long str_compare(char* left, char* right);
+void usb_throw(long code);
+void onerr(usb_handler handler);
+long menu(long choice, long count);
+long dim_size(long size);
+long poptions(char** options, long count, char* prompt);
+void screen(long* columns, long* rows);
+long read_data(usb_value* pool, long count, long* pointer, unsigned int type);
+void print(char* text);
+void print_text(char* text);
+void print_zone();
+void print_newline();
+void print_format(char* format, usb_value* values, long count);
+void input_values(char* prompt, usb_value* values, long count);
+void get_value(usb_value* value);
+usb_list* list_new();
+void list_push(usb_list* list, long value);
+long list_pop(usb_list* list);
+void list_insert(usb_list* list, long index, long value);
+void list_remove(usb_list* list, long index);
+long list_get(usb_list* list, long index);
+void list_set(usb_list* list, long index, long value);
+usb_map* map_new();
+long map_get(usb_map* map, char* key);
+void map_set(usb_map* map, char* key, long value);
+bool map_has(usb_map* map, char* key);
+void map_remove(usb_map* map, char* key);
+usb_list* map_keys(usb_map* map);
+void* copy_record(void* record, long size);
+void usb_start(long count, char** values);
 //positional
-void print(char* input);
 char* input(char* prompt);
 char* get(char* prompt);
+long err();
 long day(long days);
 long hour(long hours);
-long minutes(long minutes);
-long seconds(long seconds);
-long date();
-void cscope();
-long poptions(char options[], char* prompt);
-long menu(long choice);
-void penum(long enum_value);
-void time(long date_value);
+long minute(long minutes);
+long second(long seconds);
+usb_date date();
+void penum(usb_enum_entry* table, long count, long enum_value);
+double _time(usb_date date);
+long _open(char* path, char* mode);
+char* _read(long file, long count);
+char* readline(long file);
+char* readall(long file);
+void _write(long file, char* text);
+void append(long file, char* text);
+void _close(long file);
+void _mkdir(char* path);
+void delete(char* path);
+void _rename(char* from, char* to);
+void copy(char* from, char* to);
+void fcolor(long color);
+void bcolor(long color);
+void icolor();
+void rcolor();
+void home();
+void htab(long column);
+void vtab(long row);
+void flash(unsigned int type, void* value);
+void inverse();
+void normal();
+void randomize(long optional_seed);
+long argc();
+void _setenv(char* name, char* value);
+char* shell(char* command);
+long exec(usb_list* arguments);
+char* _stderr();
 //enclosed
 char* str(unsigned int type, void* value);
 long _int(unsigned int type, void* value);
-float _float(unsigned int type, void* value);
+double _float(unsigned int type, void* value);
 bool _bool(unsigned int type, void* value);
 long sgn(long value);
-long abs(long value);
-long sqr(long value);
-long rnd(long value);
-char* left(char* original);
-char* right(char* original);
-char* mid(char* original);
+long sgn_f(double value);
+long _abs(long value);
+double _abs_f(double value);
+long min(long left, long right);
+double min_f(double left, double right);
+long max(long left, long right);
+double max_f(double left, double right);
+double sqr(double value);
+double _sin(double value);
+double _cos(double value);
+double _tan(double value);
+double atn(double value);
+double _log(double value);
+double _exp(double value);
+long _floor(double value);
+long _ceil(double value);
+long _round(double value);
+double rnd(double value);
+long rndint(long low, long high);
+long len(char* text);
+long asc(char* text);
+char* chr(long code);
+double val(char* text);
+char* left(char* text, long count);
+char* right(char* text, long count);
+char* mid(char* text, long start, long optional_length);
+long instr(char* text, char* search, long optional_start);
+char* ucase(char* text);
+char* lcase(char* text);
+char* trim(char* text);
+char* replace(char* text, char* search, char* replacement);
+char* repeat(char* text, long count);
+usb_list* split(char* text, char* separator);
+usb_list* dir(char* optional_path);
+bool exists(char* path);
+long filesize(char* path);
+char* argv(long index);
+char* env(char* name);
+char* tab(long column);
+char* spc(long count);
+char* formatdate(usb_date date, char* pattern);
 
+#endif
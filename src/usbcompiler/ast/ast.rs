@@ -1,29 +1,97 @@
 #![allow(non_camel_case_types)]
 
+use std::fmt;
+
+use crate::usbcompiler::tokenizer::lexer::Token;
+
 // === Core Types ===
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DataType {
     INT,
     FLOAT,
     STRING,
     BOOL,
+    /// an integer annotated with the enum it belongs to, see `ENUM` in the spec
+    ENUM(String),
+    /// an integer storing a Unix epoch in seconds, stringified as `YYYY-MM-DD HH:MM:SS` see `DATE` in the spec
+    DATE,
+    /// a value made of the typed fields of the record it is named after, see `RECORD` in the spec
+    RECORD(String),
+    /// a growable list of values of the element type, see `LIST` in the spec
+    LIST(Box<DataType>),
+    /// a map from strings to values of the value type, see `MAP` in the spec
+    MAP(Box<DataType>),
 }
 
-#[derive(Debug)]
+impl DataType {
+    pub fn is_numeric(&self) -> bool {
+        return matches!(
+            self,
+            DataType::INT | DataType::FLOAT | DataType::ENUM(_) | DataType::DATE
+        );
+    }
+    pub fn is_integer(&self) -> bool {
+        return matches!(self, DataType::INT | DataType::ENUM(_) | DataType::DATE);
+    }
+    pub fn is_record(&self) -> bool {
+        return matches!(self, DataType::RECORD(_));
+    }
+    pub fn is_collection(&self) -> bool {
+        return matches!(self, DataType::LIST(_) | DataType::MAP(_));
+    }
+    /// returns true for the types that fit into a single value, records and collections can't be stringified or compared
+    pub fn is_primitive(&self) -> bool {
+        return !self.is_record() && !self.is_collection();
+    }
+    /// the type of the elements of a list or the values of a map
+    pub fn element_type(&self) -> Option<&DataType> {
+        return match self {
+            DataType::LIST(element) | DataType::MAP(element) => Some(element),
+            _ => None,
+        };
+    }
+    /// returns true if a value of type `other` can be stored in a variable of this type.
+    /// Integers are promoted to floats and casting between integers, enums and dates is implicit
+    pub fn accepts(&self, other: &DataType) -> bool {
+        if self.is_integer() && other.is_integer() {
+            return true;
+        }
+        return self == other || (*self == DataType::FLOAT && other.is_integer());
+    }
+}
+/// the type the way it is annotated in a program, e.g. `LIST OF INTEGER`
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            DataType::INT => write!(f, "INTEGER"),
+            DataType::FLOAT => write!(f, "FLOAT"),
+            DataType::STRING => write!(f, "STRING"),
+            DataType::BOOL => write!(f, "BOOL"),
+            DataType::DATE => write!(f, "DATE"),
+            DataType::ENUM(name) | DataType::RECORD(name) => write!(f, "{}", name),
+            DataType::LIST(element) => write!(f, "LIST OF {}", element),
+            DataType::MAP(value) => write!(f, "MAP OF STRING TO {}", value),
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct IdentifierNode {
     pub name: String,
+    pub token: Token,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ConstantNode {
     pub data_type: DataType,
     pub value: String,
+    pub token: Token,
 }
 
 // === Expressions ===
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BinaryOpKind {
     ADD,
     SUB,
@@ -37,51 +105,279 @@ pub enum BinaryOpKind {
     GTE,
     AND,
     OR,
+    MOD,
+    /// `\`, integer division rounding towards zero
+    IDIV,
+    BAND,
+    BOR,
+    BXOR,
+    SHL,
+    SHR,
+}
+
+impl BinaryOpKind {
+    /// binding power of the operator, higher binds tighter.
+    /// Bitwise operators bind tighter than comparisons so `X BAND 1 = 0` tests a bit
+    pub fn precedence(&self) -> u8 {
+        return match self {
+            BinaryOpKind::OR => 1,
+            BinaryOpKind::AND => 2,
+            BinaryOpKind::EQ
+            | BinaryOpKind::NEQ
+            | BinaryOpKind::LT
+            | BinaryOpKind::GT
+            | BinaryOpKind::LTE
+            | BinaryOpKind::GTE => 3,
+            BinaryOpKind::BOR => 4,
+            BinaryOpKind::BXOR => 5,
+            BinaryOpKind::BAND => 6,
+            BinaryOpKind::SHL | BinaryOpKind::SHR => 7,
+            BinaryOpKind::ADD | BinaryOpKind::SUB => 8,
+            BinaryOpKind::MUL | BinaryOpKind::DIV | BinaryOpKind::MOD | BinaryOpKind::IDIV => 9,
+        };
+    }
+    pub fn is_comparison(&self) -> bool {
+        return self.precedence() == 3;
+    }
+    /// the type an operation on the given operand types evaluates to, or [`None`] if the operation is invalid for those types
+    pub fn result_type(&self, left: &DataType, right: &DataType) -> Option<DataType> {
+        let numeric = left.is_numeric() && right.is_numeric();
+        let floating = *left == DataType::FLOAT || *right == DataType::FLOAT;
+        return match self {
+            // non string operands get stringified when added to a string
+            BinaryOpKind::ADD
+                if (*left == DataType::STRING || *right == DataType::STRING)
+                    && left.is_primitive()
+                    && right.is_primitive() =>
+            {
+                Some(DataType::STRING)
+            }
+            // moving a date by an offset keeps it a date, the difference of two dates is a plain offset
+            BinaryOpKind::ADD
+                if (*left == DataType::DATE) != (*right == DataType::DATE)
+                    && !floating
+                    && numeric =>
+            {
+                Some(DataType::DATE)
+            }
+            BinaryOpKind::SUB
+                if *left == DataType::DATE && *right != DataType::DATE && !floating && numeric =>
+            {
+                Some(DataType::DATE)
+            }
+            BinaryOpKind::ADD | BinaryOpKind::SUB | BinaryOpKind::MUL if numeric => {
+                Some(if floating {
+                    DataType::FLOAT
+                } else {
+                    DataType::INT
+                })
+            }
+            BinaryOpKind::DIV if numeric => Some(DataType::FLOAT),
+            BinaryOpKind::MOD
+            | BinaryOpKind::IDIV
+            | BinaryOpKind::BAND
+            | BinaryOpKind::BOR
+            | BinaryOpKind::BXOR
+            | BinaryOpKind::SHL
+            | BinaryOpKind::SHR
+                if left.is_integer() && right.is_integer() =>
+            {
+                Some(DataType::INT)
+            }
+            BinaryOpKind::EQ | BinaryOpKind::NEQ
+                if numeric || (left == right && left.is_primitive()) =>
+            {
+                Some(DataType::BOOL)
+            }
+            BinaryOpKind::LT | BinaryOpKind::GT | BinaryOpKind::LTE | BinaryOpKind::GTE
+                if numeric || (*left == DataType::STRING && *right == DataType::STRING) =>
+            {
+                Some(DataType::BOOL)
+            }
+            BinaryOpKind::AND | BinaryOpKind::OR
+                if *left == DataType::BOOL && *right == DataType::BOOL =>
+            {
+                Some(DataType::BOOL)
+            }
+            _ => None,
+        };
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum UnaryOpKind {
     NEG,
     NOT,
 }
 
-#[derive(Debug)]
+impl UnaryOpKind {
+    pub fn result_type(&self, target: &DataType) -> Option<DataType> {
+        return match self {
+            UnaryOpKind::NEG if target.is_numeric() => Some(if *target == DataType::FLOAT {
+                DataType::FLOAT
+            } else {
+                DataType::INT
+            }),
+            UnaryOpKind::NOT if *target == DataType::BOOL => Some(DataType::BOOL),
+            _ => None,
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct BinaryOpNode {
     pub left: Box<Expression>,
     pub right: Box<Expression>,
     pub operation: BinaryOpKind,
+    pub token: Token,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UnaryOpNode {
     pub target: Box<Expression>,
     pub operation: UnaryOpKind,
+    pub token: Token,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FunctionCallNode {
     pub name: String,
     pub arguments: Vec<Expression>,
+    pub token: Token,
+}
+
+/// `<record>.<field>`, a field of a record valued expression
+#[derive(Debug, Clone)]
+pub struct FieldAccessNode {
+    pub record: Box<Expression>,
+    pub field: IdentifierNode,
+}
+
+/// `<collection>[<index>]`, an element of a list by its (0 based) index or a value of a map by its key
+#[derive(Debug, Clone)]
+pub struct IndexNode {
+    pub collection: Box<Expression>,
+    pub index: Box<Expression>,
+    pub token: Token,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CollectionOpKind {
+    /// `PUSH <list>, <value>`, appends a value
+    PUSH,
+    /// `POP <list>`, removes the last value and evaluates to it
+    POP,
+    /// `INSERT <list>, <index>, <value>`, inserts a value before the index
+    INSERT,
+    /// `REMOVE <list>, <index>` or `REMOVE <map>, <key>`
+    REMOVE,
+    /// `HAS(<map>, <key>)`
+    HAS,
+    /// `KEYS(<map>)`, a list of the keys in the order they were added
+    KEYS,
+}
+
+impl CollectionOpKind {
+    /// the number of arguments the operation takes, the collection included
+    pub fn arity(&self) -> usize {
+        return match self {
+            CollectionOpKind::POP | CollectionOpKind::KEYS => 1,
+            CollectionOpKind::PUSH | CollectionOpKind::REMOVE | CollectionOpKind::HAS => 2,
+            CollectionOpKind::INSERT => 3,
+        };
+    }
+}
+
+/// a builtin operation on a list or map, the collection is the first argument
+#[derive(Debug, Clone)]
+pub struct CollectionNode {
+    pub operation: CollectionOpKind,
+    pub arguments: Vec<Expression>,
+    pub token: Token,
 }
 
-#[derive(Debug)]
+/// `POPTIONS <option>, [option, ...] <prompt>`, lets the user pick one of the options and evaluates to its (1 based) index
+#[derive(Debug, Clone)]
+pub struct OptionsNode {
+    pub options: Vec<Expression>,
+    pub prompt: Box<Expression>,
+    pub token: Token,
+}
+
+#[derive(Debug, Clone)]
 pub enum Expression {
     IDENTIFIER(IdentifierNode),
     CONSTANT(ConstantNode),
     UNARY_OP(UnaryOpNode),
     BINARY_OP(BinaryOpNode),
     FUNC_CALL(FunctionCallNode),
+    POPTIONS(OptionsNode),
+    FIELD(FieldAccessNode),
+    INDEX(IndexNode),
+    COLLECTION(CollectionNode),
+}
+
+impl Expression {
+    /// the token the expression starts at or is centered around, used for error reporting
+    pub fn token(&self) -> &Token {
+        return match self {
+            Expression::IDENTIFIER(node) => &node.token,
+            Expression::CONSTANT(node) => &node.token,
+            Expression::UNARY_OP(node) => &node.token,
+            Expression::BINARY_OP(node) => &node.token,
+            Expression::FUNC_CALL(node) => &node.token,
+            Expression::POPTIONS(node) => &node.token,
+            Expression::FIELD(node) => &node.field.token,
+            Expression::INDEX(node) => &node.token,
+            Expression::COLLECTION(node) => &node.token,
+        };
+    }
 }
 
 // === Statements ===
 
-#[derive(Debug)]
+/// a step from an assigned variable to the field or element that is assigned to
+#[derive(Debug, Clone)]
+pub enum TargetAccess {
+    /// `.<field>` of a record
+    FIELD(IdentifierNode),
+    /// `[<index>]` of a list or map, along with the token of the opening bracket
+    INDEX(Box<Expression>, Token),
+}
+
+#[derive(Debug, Clone)]
 pub struct AssignmentNode {
     pub target: IdentifierNode,
+    /// the fields and elements on the way from the variable to what is assigned to, e.g. `[0]`, `.age` of `people[0].age = 99`
+    pub path: Vec<TargetAccess>,
     pub value: Expression,
 }
 
-#[derive(Debug)]
+impl AssignmentNode {
+    /// the assigned variable, field or element read as an expression
+    pub fn target_expression(&self) -> Expression {
+        return Self::access(Expression::IDENTIFIER(self.target.clone()), &self.path);
+    }
+    /// the expression reached by following a path from a variable, e.g. the record holding the assigned field
+    pub fn access(mut expression: Expression, path: &[TargetAccess]) -> Expression {
+        for access in path {
+            expression = match access {
+                TargetAccess::FIELD(field) => Expression::FIELD(FieldAccessNode {
+                    record: Box::new(expression),
+                    field: field.clone(),
+                }),
+                TargetAccess::INDEX(index, token) => Expression::INDEX(IndexNode {
+                    collection: Box::new(expression),
+                    index: index.clone(),
+                    token: token.clone(),
+                }),
+            };
+        }
+        return expression;
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct VariableDeclarationNode {
     pub variable_name: IdentifierNode,
     pub data_type: Option<DataType>,
@@ -89,36 +385,444 @@ pub struct VariableDeclarationNode {
     pub initial_value: Option<Box<Expression>>,
 }
 
-#[derive(Debug)]
+/// `DIM <name> (<columns> [, <rows>]) AS <type>`, a list of `columns` values or a list of `rows` lists of `columns` values
+#[derive(Debug, Clone)]
+pub struct DimNode {
+    pub variable_name: IdentifierNode,
+    pub columns: Box<Expression>,
+    pub rows: Option<Box<Expression>>,
+    pub element_type: DataType,
+    pub token: Token,
+}
+
+impl DimNode {
+    /// the type of the array, a list of rows for two dimensions
+    pub fn data_type(&self) -> DataType {
+        let row = DataType::LIST(Box::new(self.element_type.clone()));
+        if self.rows.is_some() {
+            return DataType::LIST(Box::new(row));
+        }
+        return row;
+    }
+}
+
+/// `DEF <name> [<param1>, <param2>, ...] = <expression>`, a function whose parameters take the types of the arguments it is called with
+#[derive(Debug, Clone)]
+pub struct DefNode {
+    pub name: IdentifierNode,
+    pub parameters: Vec<IdentifierNode>,
+    pub body: Box<Expression>,
+    pub token: Token,
+}
+
+#[derive(Debug, Clone)]
 pub struct IfStatementNode {
     pub condition: Box<Expression>,
     pub action: Box<Statement>,
     pub else_action: Option<Box<Statement>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ScopeNode {
     pub statements: Vec<Statement>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ReturnNode {
-    return_expression: Option<Box<Expression>>,
+    pub return_expression: Option<Box<Expression>>,
+    pub token: Token,
+}
+
+/// the target of a `GOTO` or `GOSUB`, optionally a line of the module imported as `module`
+#[derive(Debug, Clone)]
+pub struct JumpNode {
+    pub module: Option<IdentifierNode>,
+    pub line_number: usize,
+    pub token: Token,
+}
+
+/// `IMPORT "<path>" AS <alias>`, the path is relative to the importing file
+#[derive(Debug, Clone)]
+pub struct ImportNode {
+    pub path: String,
+    pub alias: IdentifierNode,
+    pub token: Token,
+}
+
+#[derive(Debug, Clone)]
+pub struct EndNode {
+    pub token: Token,
+}
+
+/// `MENU <expression> <statement1> [, <statement2>, ...]`, runs the statement at the (1 based) index the selector evaluates to.
+/// The `GOTO 10, 20` shorthand is parsed into a `GOTO` statement per line number
+#[derive(Debug, Clone)]
+pub struct MenuNode {
+    pub selector: Box<Expression>,
+    pub actions: Vec<Statement>,
+    pub token: Token,
+}
+
+/// marks the start of a (numbered or inferred) line, every statement following it up to the next [`LineNode`] is part of that line
+#[derive(Debug, Clone)]
+pub struct LineNode {
+    pub line_number: usize,
+    pub token: Token,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumMemberNode {
+    pub name: IdentifierNode,
+    pub value: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumDeclarationNode {
+    pub name: IdentifierNode,
+    pub members: Vec<EnumMemberNode>,
+}
+
+impl EnumDeclarationNode {
+    /// resolve the value of every member. Unannotated members continue counting from the previous member (starting at 0).
+    /// Once the values stop being sequential every member after that must be annotated, the first member missing one is returned as the error
+    pub fn values(&self) -> Result<Vec<(String, i64)>, &EnumMemberNode> {
+        let mut values: Vec<(String, i64)> = Vec::new();
+        let mut sequential = true;
+        for member in &self.members {
+            let value = match (member.value, values.last()) {
+                (Some(value), Some((_, previous))) => {
+                    sequential = value > *previous;
+                    value
+                }
+                (Some(value), None) => value,
+                (None, Some((_, previous))) => {
+                    if !sequential {
+                        return Err(member);
+                    }
+                    previous + 1
+                }
+                (None, None) => 0,
+            };
+            values.push((member.name.name.clone(), value));
+        }
+        return Ok(values);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PrintEnumNode {
+    pub enum_name: IdentifierNode,
+    pub value: Box<Expression>,
+}
+
+/// `SCREEN <columns>, <rows>`, stores the size of the terminal into two variables
+#[derive(Debug, Clone)]
+pub struct ScreenNode {
+    pub columns: IdentifierNode,
+    pub rows: IdentifierNode,
+    pub token: Token,
+}
+
+/// `DATA <constant1> [, <constant2>, ...]`, adds constants to the data pool of the module
+#[derive(Debug, Clone)]
+pub struct DataNode {
+    pub values: Vec<ConstantNode>,
+    pub token: Token,
+}
+
+/// `READ <variable1> [, <variable2>, ...]`, reads the next values of the data pool into variables
+#[derive(Debug, Clone)]
+pub struct ReadDataNode {
+    pub targets: Vec<IdentifierNode>,
+    pub token: Token,
+}
+
+/// `RESTORE [linenumber]`, moves the pointer into the data pool back to the start or to the first value on or after a line
+#[derive(Debug, Clone)]
+pub struct RestoreNode {
+    pub target: Option<JumpNode>,
+    pub token: Token,
+}
+
+#[derive(Debug, Clone)]
+pub enum PrintItem {
+    EXPRESSION(Expression),
+    /// `,` moves to the next tab zone
+    ZONE,
+}
+
+/// `PRINT <format> [, <expression> ...]` or `PRINT [<expression>] [{; | ,} <expression> ...] [; | ,]`
+#[derive(Debug, Clone)]
+pub struct PrintNode {
+    /// a string literal with `{}` placeholders, the items fill them in from left to right
+    pub format: Option<ConstantNode>,
+    pub items: Vec<PrintItem>,
+    /// a trailing `;` or `,` keeps the cursor on the line
+    pub newline: bool,
+    pub token: Token,
+}
+
+impl PrintNode {
+    /// the number of placeholders in the format, or [`None`] if a placeholder is invalid.
+    /// A placeholder is `{[:[<|>|^][0][width][.precision]]}`, `{{` and `}}` are escaped braces
+    pub fn placeholders(format: &str) -> Option<usize> {
+        let mut count = 0;
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '}' {
+                if chars.next() != Some('}') {
+                    return None;
+                }
+                continue;
+            }
+            if c != '{' {
+                continue;
+            }
+            if chars.peek() == Some(&'{') {
+                chars.next();
+                continue;
+            }
+            let mut spec = String::new();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => spec.push(c),
+                    None => return None,
+                }
+            }
+            if !spec.is_empty() && !Self::is_valid_spec(&spec) {
+                return None;
+            }
+            count += 1;
+        }
+        return Some(count);
+    }
+    fn is_valid_spec(spec: &str) -> bool {
+        let Some(spec) = spec.strip_prefix(':') else {
+            return false;
+        };
+        let spec = spec.strip_prefix(['<', '>', '^']).unwrap_or(spec);
+        let (width, precision) = match spec.split_once('.') {
+            Some((width, precision)) => (width, Some(precision)),
+            None => (spec, None),
+        };
+        let digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+        return digits(width) && precision.is_none_or(|p| !p.is_empty() && digits(p));
+    }
+}
+
+/// `RECORD <name>` followed by a `<field> AS <type>` line per field and a closing `FIN`
+#[derive(Debug, Clone)]
+pub struct RecordDeclarationNode {
+    pub name: IdentifierNode,
+    pub fields: Vec<RecordFieldNode>,
+    pub token: Token,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordFieldNode {
+    pub name: IdentifierNode,
+    pub data_type: DataType,
+}
+
+/// a parameter of a `FUNCTION`, `<name> AS <type>`
+#[derive(Debug, Clone)]
+pub struct ParameterNode {
+    pub name: IdentifierNode,
+    pub data_type: DataType,
+}
+
+/// `FUNCTION <name>([<parameter> AS <type>, ...]) [AS <type>]` followed by the lines of its body up to `FIN`
+#[derive(Debug, Clone)]
+pub struct FunctionDeclarationNode {
+    pub name: IdentifierNode,
+    pub parameters: Vec<ParameterNode>,
+    /// `None` for a function that doesn't return a value, which can only be called as a statement
+    pub return_type: Option<DataType>,
+    pub body: Vec<Statement>,
+    pub token: Token,
+}
+
+/// `INPUT [<prompt>;] <variable1> [, <variable2>, ...]` or `GET <variable>`, the input is parsed into the types of the variables
+#[derive(Debug, Clone)]
+pub struct InputNode {
+    pub prompt: Option<Box<Expression>>,
+    pub targets: Vec<IdentifierNode>,
+    pub token: Token,
+}
+
+/// `ONERR <statement1> [, <statement2>, ...]`, the statements run whenever an error is thrown
+#[derive(Debug, Clone)]
+pub struct ErrorHandlerNode {
+    pub actions: Vec<Statement>,
+    pub token: Token,
+}
+
+/// `THROW <code>`
+#[derive(Debug, Clone)]
+pub struct ThrowNode {
+    pub code: Box<Expression>,
+    pub token: Token,
+}
+
+/// `STOP <code>`, ends the program with the code as its exit status
+#[derive(Debug, Clone)]
+pub struct StopNode {
+    pub code: Box<Expression>,
+    pub token: Token,
+}
+
+#[derive(Debug, Clone)]
+pub enum LoopKind {
+    /// `FOR EACH <item> IN <collection>`, runs the body for every element of a list or every key of a map
+    EACH {
+        item: IdentifierNode,
+        collection: Box<Expression>,
+    },
+    /// `FOR <counter> = <start> TO <end> [STEP <step>]`, counts from start to end, both included. The bounds and the step are evaluated once before the loop
+    TO {
+        counter: IdentifierNode,
+        start: Box<Expression>,
+        end: Box<Expression>,
+        step: Option<Box<Expression>>,
+    },
+    /// `FOR <counter> = <start>, <condition>, <step>`, checks the condition before every run of the body and runs the assignment of the step after it
+    CSTYLE {
+        counter: IdentifierNode,
+        start: Box<Expression>,
+        condition: Box<Expression>,
+        step: Box<AssignmentNode>,
+    },
+    /// `WHILE <condition>`, checks the condition before every run of the body
+    WHILE { condition: Box<Expression> },
+    /// `DO` with `LOOP UNTIL <condition>` after the body, which runs at least once
+    UNTIL { condition: Box<Expression> },
+}
+
+/// a loop header followed by its body, which is either a scope or the lines up to the closing `NEXT`, `WEND` or `LOOP`
+#[derive(Debug, Clone)]
+pub struct LoopNode {
+    pub kind: LoopKind,
+    pub body: Vec<Statement>,
+    pub token: Token,
+}
+
+/// a single condition of a `CASE`, matched against the subject of its `SELECT CASE`
+#[derive(Debug, Clone)]
+pub enum CaseCondition {
+    /// `CASE <value>`
+    VALUE(Expression),
+    /// `CASE <low> TO <high>`, both ends included
+    RANGE(Expression, Expression),
+    /// `CASE IS <comparison> <value>` e.g. `CASE IS > 10`
+    IS(BinaryOpKind, Expression),
+}
+
+impl CaseCondition {
+    /// the condition as a BOOL expression comparing the subject, e.g. `CASE 3 TO 9` is `subject >= 3 AND subject <= 9`
+    pub fn test(&self, subject: &Expression) -> Expression {
+        let compare = |operation: BinaryOpKind, value: &Expression| {
+            Expression::BINARY_OP(BinaryOpNode {
+                left: Box::new(subject.clone()),
+                right: Box::new(value.clone()),
+                operation,
+                token: value.token().clone(),
+            })
+        };
+        return match self {
+            CaseCondition::VALUE(value) => compare(BinaryOpKind::EQ, value),
+            CaseCondition::RANGE(low, high) => Expression::BINARY_OP(BinaryOpNode {
+                left: Box::new(compare(BinaryOpKind::GTE, low)),
+                right: Box::new(compare(BinaryOpKind::LTE, high)),
+                operation: BinaryOpKind::AND,
+                token: low.token().clone(),
+            }),
+            CaseCondition::IS(operation, value) => compare(operation.clone(), value),
+        };
+    }
+}
+
+/// `CASE <condition1> [, <condition2>, ...]` and the lines up to the next `CASE` or `END SELECT`
+#[derive(Debug, Clone)]
+pub struct CaseNode {
+    pub conditions: Vec<CaseCondition>,
+    pub body: Vec<Statement>,
+    pub token: Token,
+}
+
+/// `SELECT CASE <subject>`, runs the body of the first case with a matching condition or the body of `CASE ELSE` if none matches
+#[derive(Debug, Clone)]
+pub struct SelectNode {
+    pub subject: Box<Expression>,
+    pub cases: Vec<CaseNode>,
+    pub else_body: Option<Vec<Statement>>,
+    pub token: Token,
+}
+
+impl SelectNode {
+    /// the hidden variable the subject is evaluated into once, its name can't be written in a program
+    pub fn subject_variable(&self) -> IdentifierNode {
+        return IdentifierNode {
+            name: "SELECT CASE".into(),
+            token: self.token.clone(),
+        };
+    }
+}
+
+/// `EXIT` or `CONTINUE`, leaves or restarts the innermost loop
+#[derive(Debug, Clone)]
+pub struct LoopJumpNode {
+    pub token: Token,
+}
+
+/// `CLEAR` or `CSCOPE`, resets every variable or the ones of the current scope to the default of its type
+#[derive(Debug, Clone)]
+pub struct ClearNode {
+    pub token: Token,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Statement {
     ASSIGN(AssignmentNode),
     IF(IfStatementNode),
     SCOPE(ScopeNode),
     VAR_DECL(VariableDeclarationNode),
+    DIM(DimNode),
+    DEF(DefNode),
     FUNC_CALL(FunctionCallNode),
     RETURN(ReturnNode),
+    LINE(LineNode),
+    ENUM(EnumDeclarationNode),
+    PENUM(PrintEnumNode),
+    GOTO(JumpNode),
+    GOSUB(JumpNode),
+    END(EndNode),
+    MENU(MenuNode),
+    IMPORT(ImportNode),
+    SCREEN(ScreenNode),
+    DATA(DataNode),
+    READ(ReadDataNode),
+    RESTORE(RestoreNode),
+    PRINT(PrintNode),
+    INPUT(InputNode),
+    GET(InputNode),
+    ONERR(ErrorHandlerNode),
+    THROW(ThrowNode),
+    STOP(StopNode),
+    RECORD(RecordDeclarationNode),
+    FUNCTION(FunctionDeclarationNode),
+    COLLECTION(CollectionNode),
+    LOOP(LoopNode),
+    EXIT(LoopJumpNode),
+    CONTINUE(LoopJumpNode),
+    SELECT(SelectNode),
+    CLEAR(ClearNode),
+    CSCOPE(ClearNode),
 }
 
 // === Root Node ===
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AstNode {
     EXPRESSION(Expression),
     STATEMENT(Statement),
@@ -0,0 +1,1970 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use crate::{
+    stdlib::{builtin_constants, resolve_function},
+    usbcompiler::{
+        ast::ast::{
+            AssignmentNode, AstNode, BinaryOpKind, CaseCondition, ClearNode, CollectionNode,
+            CollectionOpKind, DataType, DimNode, EnumDeclarationNode, Expression, FieldAccessNode,
+            FunctionCallNode, FunctionDeclarationNode, IdentifierNode, ImportNode, IndexNode,
+            JumpNode, LineNode, LoopKind, LoopNode, MenuNode, PrintEnumNode, PrintItem, PrintNode,
+            RecordDeclarationNode, ReturnNode, SelectNode, Statement, ThrowNode,
+            VariableDeclarationNode,
+        },
+        checker::{flow::FlowGraph, symbols::SymbolTable},
+        errors::error::{
+            CompilerError, CompilerWarning, DeclarationError, MiscellaneousError, SemanticError,
+        },
+        tokenizer::lexer::{Token, TokenKind},
+    },
+};
+
+#[derive(Debug, Clone)]
+pub enum Symbol {
+    Variable(DataType),
+    /// a named compile time constant, e.g. an enum key
+    Constant(DataType, i64),
+    Enum(Vec<(String, i64)>),
+    /// the alias of an imported module, holding the path it was imported with
+    Module(String),
+    /// the names and types of the fields of a record
+    Record(Vec<(String, DataType)>),
+    /// the parameter types and the return type of a `FUNCTION`
+    Function {
+        parameters: Vec<DataType>,
+        return_type: Option<DataType>,
+    },
+    /// a `DEF`, its expression is checked at every call with the parameters typed by the arguments
+    Lambda {
+        parameters: Vec<IdentifierNode>,
+        body: Expression,
+    },
+}
+
+/// Resolves symbols and validates the types of a parsed program before it is handed to code generation
+pub struct Checker {
+    symbols: SymbolTable<Symbol>,
+    lines: HashMap<usize, Token>,
+    /// jumps can target lines further down, so their targets are only resolved once every line is known
+    jumps: Vec<JumpNode>,
+    /// the types of every DATA value, READ can come before the DATA it reads so targets are checked once the whole pool is known
+    data_types: Vec<DataType>,
+    reads: Vec<(IdentifierNode, DataType)>,
+    /// the line numbers of every imported module by the path it is imported with
+    imports: HashMap<String, HashSet<usize>>,
+    /// how many loops the statement being checked is in, `EXIT` and `CONTINUE` need at least one
+    loop_depth: usize,
+    /// whether other modules import the checked module, they can jump to any of its lines
+    imported: bool,
+    /// whether the expression being checked is a condition, where a `=` can only compare
+    in_condition: bool,
+    /// whether the statement being checked is in the body of a function, and the type the function returns
+    in_function: bool,
+    return_type: Option<DataType>,
+    /// the lines of the bodies of functions, which can't be jumped to
+    function_lines: HashSet<usize>,
+    /// the `DEF`s whose expression is being checked, which can't call themselves
+    lambdas: Vec<String>,
+    /// the variables of a primitive type every `CLEAR` and `CSCOPE` resets to their defaults, by the position of its token
+    cleared: HashMap<usize, Vec<String>>,
+    errors: Vec<CompilerError>,
+    warnings: Vec<CompilerWarning>,
+    /// the warnings `ALLOW` comments suppress, by the line they are on
+    allowed: HashSet<(usize, String)>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        // builtin constants live in the outermost scope, so programs can shadow them
+        let mut symbols = SymbolTable::new();
+        for (name, data_type, value) in builtin_constants() {
+            symbols.declare(name, Symbol::Constant(data_type, value));
+        }
+        return Self {
+            symbols,
+            lines: HashMap::new(),
+            jumps: Vec::new(),
+            data_types: Vec::new(),
+            reads: Vec::new(),
+            imports: HashMap::new(),
+            loop_depth: 0,
+            imported: false,
+            in_condition: false,
+            in_function: false,
+            return_type: None,
+            function_lines: HashSet::new(),
+            lambdas: Vec::new(),
+            cleared: HashMap::new(),
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            allowed: HashSet::new(),
+        };
+    }
+    /// provide the modules the checked module imports, see [`crate::usbcompiler::loader::loader::ModuleLoader`]
+    pub fn with_imports(mut self, imports: HashMap<String, HashSet<usize>>) -> Self {
+        self.imports = imports;
+        return self;
+    }
+    /// check the module as one imported by another module
+    pub fn imported(mut self) -> Self {
+        self.imported = true;
+        return self;
+    }
+    /// provide the warnings suppressed by `ALLOW` comments, see [`crate::usbcompiler::tokenizer::lexer::Lexer::allowed`]
+    pub fn with_allowed(mut self, allowed: HashSet<(usize, String)>) -> Self {
+        self.allowed = allowed;
+        return self;
+    }
+    pub fn check(&mut self, root: &AstNode) -> Result<(), Vec<CompilerError>> {
+        match root {
+            AstNode::STATEMENT(statement) => {
+                self.check_statement(statement);
+                self.check_flow(statement);
+            }
+            AstNode::EXPRESSION(expression) => {
+                self.check_expression(expression);
+            }
+        }
+        for jump in std::mem::take(&mut self.jumps) {
+            if !self.lines.contains_key(&jump.line_number) {
+                self.semantic_error(SemanticError::UndefinedLine {
+                    token: jump.token,
+                    line_number: jump.line_number,
+                });
+            } else if self.function_lines.contains(&jump.line_number) {
+                self.semantic_error(SemanticError::FunctionJump {
+                    token: jump.token,
+                    line_number: jump.line_number,
+                });
+            }
+        }
+        // a READ target has to accept at least one value of the pool, which values it reads is only known at runtime
+        for (target, target_type) in std::mem::take(&mut self.reads) {
+            if !self.data_types.iter().any(|t| target_type.accepts(t)) {
+                self.type_mismatch(&target.token, target_type);
+            }
+        }
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
+        return Ok(());
+    }
+    /// the warnings found by [`Checker::check`] that aren't allowed on their line, they are reported whether or not checking failed
+    pub fn warnings(&mut self) -> Vec<CompilerWarning> {
+        return std::mem::take(&mut self.warnings)
+            .into_iter()
+            .filter(|warning| {
+                let line = warning.token().position_span.line;
+                !self.allowed.contains(&(line, warning.name().to_string()))
+            })
+            .collect();
+    }
+    /// warn about code that never runs, subroutines without a `RET` and reads of unassigned variables, jumping into a scope past a declaration is an error
+    fn check_flow(&mut self, root: &Statement) {
+        let flow = FlowGraph::new(root);
+        for line in flow.unreachable(self.imported) {
+            self.warnings.push(CompilerWarning::UnreachableCode {
+                token: line.token.clone(),
+            });
+        }
+        for jump in flow.missing_returns() {
+            self.warnings.push(CompilerWarning::MissingReturn {
+                token: jump.token.clone(),
+                line_number: jump.line_number,
+            });
+        }
+        let cleared = std::mem::take(&mut self.cleared);
+        for read in flow.unassigned_reads(&cleared) {
+            self.warnings.push(CompilerWarning::UnassignedRead {
+                token: read.token.clone(),
+                name: read.name.clone(),
+            });
+        }
+        for jump in flow.jumps_into_scopes() {
+            self.semantic_error(SemanticError::JumpIntoScope {
+                token: jump.token.clone(),
+                line_number: jump.line_number,
+            });
+        }
+    }
+    /// remember the variables a `CLEAR` or `CSCOPE` leaves holding the defaults of their types, for [`FlowGraph::unassigned_reads`]
+    fn check_clear(&mut self, clear: &ClearNode, current_scope: bool) {
+        let symbols: Vec<(&String, &Symbol)> = if current_scope {
+            self.symbols.current_scope().collect()
+        } else {
+            self.symbols.all().collect()
+        };
+        let names = symbols
+            .into_iter()
+            .filter(|(_, symbol)| matches!(symbol, Symbol::Variable(t) if t.is_primitive()))
+            .map(|(name, _)| name.clone())
+            .collect();
+        self.cleared.insert(clear.token.position_flat, names);
+    }
+    fn semantic_error(&mut self, error: SemanticError) {
+        self.errors.push(CompilerError::SemanticError(error));
+    }
+    fn undefined(&mut self, identifier: &IdentifierNode) {
+        self.semantic_error(SemanticError::UndefinedIdentifier {
+            token: identifier.token.clone(),
+            identifier: identifier.clone(),
+        });
+    }
+    fn type_mismatch(&mut self, token: &Token, expected_type: DataType) {
+        self.semantic_error(SemanticError::TypeMismatch {
+            token: token.clone(),
+            expected_type,
+        });
+    }
+    fn check_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.check_statement(statement);
+        }
+    }
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::LINE(line) => self.check_line(line),
+            Statement::SCOPE(scope) => {
+                self.symbols.push_scope();
+                self.check_statements(&scope.statements);
+                self.symbols.pop_scope();
+            }
+            Statement::VAR_DECL(declaration) => self.check_declaration(declaration),
+            Statement::DIM(dim) => self.check_dim(dim),
+            Statement::DEF(def) => self.declare(
+                &def.name,
+                Symbol::Lambda {
+                    parameters: def.parameters.clone(),
+                    body: *def.body.clone(),
+                },
+            ),
+            Statement::ASSIGN(assignment) => self.check_assignment(assignment),
+            Statement::ENUM(declaration) => self.check_enum(declaration),
+            Statement::PENUM(penum) => self.check_penum(penum),
+            Statement::FUNC_CALL(call) => {
+                self.check_function_call(call, false);
+            }
+            Statement::IF(if_statement) => {
+                self.check_condition(&if_statement.condition);
+                self.check_statement(&if_statement.action);
+                if let Some(else_action) = &if_statement.else_action {
+                    self.check_statement(else_action);
+                }
+            }
+            Statement::GOTO(jump) | Statement::GOSUB(jump) => {
+                if self.in_function {
+                    self.semantic_error(SemanticError::FunctionJump {
+                        token: jump.token.clone(),
+                        line_number: jump.line_number,
+                    });
+                    return;
+                }
+                self.check_jump(jump);
+            }
+            Statement::IMPORT(import) => self.check_import(import),
+            Statement::MENU(menu) => self.check_menu(menu),
+            Statement::RETURN(ret) => self.check_return(ret),
+            Statement::END(_) => (),
+            Statement::SCREEN(screen) => {
+                for target in [&screen.columns, &screen.rows] {
+                    if let Some(t) = self.assignable_type(target) {
+                        // the size is written straight into the variables, so they have to be integers
+                        if !t.is_integer() {
+                            self.type_mismatch(&target.token, DataType::INT);
+                        }
+                    }
+                }
+            }
+            Statement::DATA(data) => {
+                for value in &data.values {
+                    if let Some(t) = self.check_expression(&Expression::CONSTANT(value.clone())) {
+                        self.data_types.push(t);
+                    }
+                }
+            }
+            Statement::READ(read) => {
+                for target in &read.targets {
+                    if let Some(t) = self.assignable_type(target) {
+                        self.reads.push((target.clone(), t));
+                    }
+                }
+            }
+            Statement::RESTORE(restore) => {
+                if let Some(target) = &restore.target {
+                    self.check_jump(target);
+                }
+            }
+            Statement::PRINT(print) => self.check_print(print),
+            Statement::RECORD(declaration) => self.check_record(declaration),
+            Statement::FUNCTION(declaration) => self.check_function(declaration),
+            Statement::INPUT(input) | Statement::GET(input) => {
+                if let Some(prompt) = &input.prompt {
+                    self.check_stringified(prompt);
+                }
+                // any primitive type but an enum can be parsed from the input, an enum would allow values that aren't keys of the enum
+                for target in &input.targets {
+                    match self.assignable_type(target) {
+                        Some(DataType::ENUM(_)) => self.type_mismatch(&target.token, DataType::INT),
+                        Some(t) if !t.is_primitive() => {
+                            self.semantic_error(SemanticError::IllegalIdentifier {
+                                token: target.token.clone(),
+                                identifier: target.clone(),
+                            })
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            // the handler is called from wherever an error is thrown, it can't leave the loop it was registered in
+            Statement::ONERR(handler) => {
+                let loop_depth = std::mem::take(&mut self.loop_depth);
+                self.check_statements(&handler.actions);
+                self.loop_depth = loop_depth;
+            }
+            Statement::THROW(throw) => self.check_throw(throw),
+            Statement::STOP(stop) => {
+                if let Some(t) = self.check_expression(&stop.code)
+                    && !t.is_integer()
+                {
+                    self.type_mismatch(stop.code.token(), DataType::INT);
+                }
+            }
+            Statement::COLLECTION(collection) => {
+                self.check_collection(collection);
+            }
+            Statement::LOOP(node) => self.check_loop(node),
+            Statement::SELECT(select) => self.check_select(select),
+            Statement::CLEAR(clear) => self.check_clear(clear, false),
+            Statement::CSCOPE(clear) => self.check_clear(clear, true),
+            Statement::EXIT(jump) | Statement::CONTINUE(jump) => {
+                if self.loop_depth == 0 {
+                    self.semantic_error(SemanticError::InvalidLoopBreak {
+                        token: jump.token.clone(),
+                    });
+                }
+            }
+        }
+    }
+    fn check_condition(&mut self, condition: &Expression) {
+        let in_condition = std::mem::replace(&mut self.in_condition, true);
+        let condition_type = self.check_expression(condition);
+        self.in_condition = in_condition;
+        if let Some(t) = condition_type
+            && t != DataType::BOOL
+        {
+            self.type_mismatch(condition.token(), DataType::BOOL);
+        }
+    }
+    /// the loop variable of a `FOR EACH` only exists in the body of the loop, it holds the elements of a list or the keys of a map.
+    /// The counter of a `FOR ... TO` only exists in the body as well, its bounds and step can be any numbers.
+    /// The counter of a C-style `FOR` takes the type of its start, which has to be a number, its step is checked like any assignment.
+    /// The condition of a `WHILE` or `DO` has to be a BOOL
+    fn check_loop(&mut self, node: &LoopNode) {
+        self.symbols.push_scope();
+        match &node.kind {
+            LoopKind::EACH { item, collection } => {
+                let item_type = match self.check_expression(collection) {
+                    Some(DataType::LIST(element)) => Some(*element),
+                    Some(DataType::MAP(_)) => Some(DataType::STRING),
+                    Some(_) => {
+                        self.semantic_error(SemanticError::InvalidOperation {
+                            token: collection.token().clone(),
+                            expression: *collection.clone(),
+                        });
+                        None
+                    }
+                    None => None,
+                };
+                if let Some(item_type) = item_type {
+                    self.declare(item, Symbol::Variable(item_type));
+                }
+            }
+            // the counter is a float if any of the bounds or the step is one
+            LoopKind::TO {
+                counter,
+                start,
+                end,
+                step,
+            } => {
+                let mut counter_type = Some(DataType::INT);
+                for value in [Some(start), Some(end), step.as_ref()]
+                    .into_iter()
+                    .flatten()
+                {
+                    match self.check_expression(value) {
+                        Some(DataType::FLOAT) => {
+                            counter_type = counter_type.map(|_| DataType::FLOAT);
+                        }
+                        Some(t) if t.is_numeric() => (),
+                        Some(_) => {
+                            self.type_mismatch(value.token(), DataType::FLOAT);
+                            counter_type = None;
+                        }
+                        None => counter_type = None,
+                    }
+                }
+                if let Some(counter_type) = counter_type {
+                    self.declare(counter, Symbol::Variable(counter_type));
+                }
+            }
+            LoopKind::CSTYLE {
+                counter,
+                start,
+                condition,
+                step,
+            } => {
+                match self.check_expression(start) {
+                    Some(t) if t.is_numeric() => self.declare(counter, Symbol::Variable(t)),
+                    Some(_) => self.type_mismatch(start.token(), DataType::INT),
+                    None => (),
+                }
+                self.check_condition(condition);
+                self.check_assignment(step);
+            }
+            LoopKind::WHILE { condition } => self.check_condition(condition),
+            LoopKind::UNTIL { .. } => (),
+        }
+        self.loop_depth += 1;
+        self.check_statements(&node.body);
+        self.loop_depth -= 1;
+        self.symbols.pop_scope();
+        // the variables of the body are out of scope by the time the condition is checked
+        if let LoopKind::UNTIL { condition } = &node.kind {
+            self.check_condition(condition);
+        }
+    }
+    /// every condition is checked as the comparison it stands for, see [`CaseCondition::test`].
+    /// Without `CASE ELSE`, a select on an enum has to cover every key of the enum
+    fn check_select(&mut self, select: &SelectNode) {
+        let subject_type = match self.check_expression(&select.subject) {
+            Some(t) if !t.is_primitive() => {
+                self.semantic_error(SemanticError::InvalidOperation {
+                    token: select.subject.token().clone(),
+                    expression: *select.subject.clone(),
+                });
+                None
+            }
+            t => t,
+        };
+        self.symbols.push_scope();
+        if let Some(subject_type) = &subject_type {
+            let subject = select.subject_variable();
+            self.symbols
+                .declare(&subject.name, Symbol::Variable(subject_type.clone()));
+            for case in &select.cases {
+                for condition in &case.conditions {
+                    self.check_condition(&condition.test(&Expression::IDENTIFIER(subject.clone())));
+                }
+            }
+        }
+        let bodies = select
+            .cases
+            .iter()
+            .map(|c| &c.body)
+            .chain(&select.else_body);
+        for body in bodies {
+            self.symbols.push_scope();
+            self.check_statements(body);
+            self.symbols.pop_scope();
+        }
+        self.symbols.pop_scope();
+
+        let Some(DataType::ENUM(name)) = subject_type else {
+            return;
+        };
+        let Some(Symbol::Enum(keys)) = self.symbols.lookup(&name).cloned() else {
+            return;
+        };
+        if select.else_body.is_some() {
+            return;
+        }
+        let missing: Vec<String> = keys
+            .into_iter()
+            .filter(|(_, value)| {
+                !select
+                    .cases
+                    .iter()
+                    .flat_map(|c| &c.conditions)
+                    .any(|c| self.covers(c, *value))
+            })
+            .map(|(key, _)| key)
+            .collect();
+        if !missing.is_empty() {
+            self.semantic_error(SemanticError::NonExhaustiveSelect {
+                token: select.token.clone(),
+                missing,
+            });
+        }
+    }
+    /// returns true if the condition matches the value for sure, conditions that aren't constant never do
+    fn covers(&self, condition: &CaseCondition, value: i64) -> bool {
+        return match condition {
+            CaseCondition::VALUE(expected) => self.constant_value(expected) == Some(value),
+            CaseCondition::RANGE(low, high) => {
+                match (self.constant_value(low), self.constant_value(high)) {
+                    (Some(low), Some(high)) => low <= value && value <= high,
+                    _ => false,
+                }
+            }
+            CaseCondition::IS(operation, other) => match self.constant_value(other) {
+                Some(other) => match operation {
+                    BinaryOpKind::EQ => value == other,
+                    BinaryOpKind::NEQ => value != other,
+                    BinaryOpKind::LT => value < other,
+                    BinaryOpKind::GT => value > other,
+                    BinaryOpKind::LTE => value <= other,
+                    BinaryOpKind::GTE => value >= other,
+                    _ => false,
+                },
+                None => false,
+            },
+        };
+    }
+    /// the value of an integer literal or a named constant like an enum key
+    fn constant_value(&self, expression: &Expression) -> Option<i64> {
+        return match expression {
+            Expression::CONSTANT(constant) if constant.data_type == DataType::INT => {
+                constant.value.parse().ok()
+            }
+            Expression::IDENTIFIER(identifier) => match self.symbols.lookup(&identifier.name) {
+                Some(Symbol::Constant(t, value)) if t.is_integer() => Some(*value),
+                _ => None,
+            },
+            _ => None,
+        };
+    }
+    /// `PUSH`, `POP` and `INSERT` only work on lists and `HAS` and `KEYS` only on maps, `REMOVE` takes an index into a list or a key of a map.
+    /// Returns the type the operation evaluates to
+    fn check_collection(&mut self, collection: &CollectionNode) -> Option<DataType> {
+        let argument_types: Vec<Option<DataType>> = collection
+            .arguments
+            .iter()
+            .map(|a| self.check_expression(a))
+            .collect();
+        if collection.arguments.len() != collection.operation.arity() {
+            self.errors.push(CompilerError::DeclarationError(
+                DeclarationError::InvalidSignature {
+                    token: collection.token.clone(),
+                },
+            ));
+            return None;
+        }
+        let collection_type = argument_types[0].clone()?;
+        let (is_list, element) = match &collection_type {
+            DataType::LIST(element) => (true, *element.clone()),
+            DataType::MAP(element) => (false, *element.clone()),
+            _ => {
+                let argument = &collection.arguments[0];
+                self.semantic_error(SemanticError::InvalidOperation {
+                    token: argument.token().clone(),
+                    expression: argument.clone(),
+                });
+                return None;
+            }
+        };
+        let parameters = match (&collection.operation, is_list) {
+            (CollectionOpKind::PUSH, true) => vec![element.clone()],
+            (CollectionOpKind::INSERT, true) => vec![DataType::INT, element.clone()],
+            (CollectionOpKind::REMOVE, true) => vec![DataType::INT],
+            (CollectionOpKind::REMOVE | CollectionOpKind::HAS, false) => vec![DataType::STRING],
+            (CollectionOpKind::POP, true) | (CollectionOpKind::KEYS, false) => Vec::new(),
+            _ => {
+                self.semantic_error(SemanticError::InvalidOperation {
+                    token: collection.token.clone(),
+                    expression: Expression::COLLECTION(collection.clone()),
+                });
+                return None;
+            }
+        };
+        for ((argument, argument_type), parameter) in collection.arguments[1..]
+            .iter()
+            .zip(&argument_types[1..])
+            .zip(parameters)
+        {
+            if argument_type
+                .as_ref()
+                .is_some_and(|t| !parameter.accepts(t))
+            {
+                self.type_mismatch(argument.token(), parameter);
+            }
+        }
+        return match collection.operation {
+            CollectionOpKind::POP => Some(element),
+            CollectionOpKind::HAS => Some(DataType::BOOL),
+            CollectionOpKind::KEYS => Some(DataType::LIST(Box::new(DataType::STRING))),
+            _ => None,
+        };
+    }
+    /// lists are indexed by integers and maps by strings, the element is of the element type of the collection
+    fn index_type(&mut self, collection_type: &DataType, node: &IndexNode) -> Option<DataType> {
+        let index_type = self.check_expression(&node.index);
+        let (expected, element) = match collection_type {
+            DataType::LIST(element) => (DataType::INT, element),
+            DataType::MAP(element) => (DataType::STRING, element),
+            _ => {
+                self.semantic_error(SemanticError::InvalidOperation {
+                    token: node.token.clone(),
+                    expression: Expression::INDEX(node.clone()),
+                });
+                return None;
+            }
+        };
+        if index_type.is_some_and(|t| !expected.accepts(&t)) {
+            self.type_mismatch(node.index.token(), expected);
+        }
+        return Some(*element.clone());
+    }
+    /// error codes are positive integers, a constant code has to be one
+    fn check_throw(&mut self, throw: &ThrowNode) {
+        match self.check_expression(&throw.code) {
+            Some(t) if !t.is_integer() => self.type_mismatch(throw.code.token(), DataType::INT),
+            _ => (),
+        }
+        if let Expression::CONSTANT(code) = &*throw.code
+            && code.value.parse::<i64>().is_ok_and(|code| code <= 0)
+        {
+            self.errors.push(CompilerError::MiscError(
+                MiscellaneousError::InvalidConstant {
+                    token: code.token.clone(),
+                    expression: throw.code.clone(),
+                },
+            ));
+        }
+    }
+    /// any value can be printed, a format needs valid placeholders and exactly one expression per placeholder
+    fn check_print(&mut self, print: &PrintNode) {
+        for item in &print.items {
+            if let PrintItem::EXPRESSION(expression) = item {
+                self.check_stringified(expression);
+            }
+        }
+        let format = match &print.format {
+            Some(format) => format,
+            None => return,
+        };
+        match PrintNode::placeholders(&format.value) {
+            None => {
+                self.errors.push(CompilerError::MiscError(
+                    MiscellaneousError::InvalidConstant {
+                        token: format.token.clone(),
+                        expression: Box::new(Expression::CONSTANT(format.clone())),
+                    },
+                ));
+            }
+            Some(count) if count != print.items.len() => {
+                self.errors.push(CompilerError::DeclarationError(
+                    DeclarationError::InvalidSignature {
+                        token: print.token.clone(),
+                    },
+                ));
+            }
+            Some(_) => (),
+        }
+    }
+    fn check_line(&mut self, line: &LineNode) {
+        if self.lines.contains_key(&line.line_number) {
+            self.errors.push(CompilerError::DeclarationError(
+                DeclarationError::MultipleDefinitions {
+                    token: line.token.clone(),
+                },
+            ));
+            return;
+        }
+        self.lines.insert(line.line_number, line.token.clone());
+        if self.in_function {
+            self.function_lines.insert(line.line_number);
+        }
+    }
+    /// declare a symbol in the current scope, reporting a duplicate declaration if the name is already taken in that scope
+    fn declare(&mut self, identifier: &IdentifierNode, symbol: Symbol) {
+        if self.symbols.is_declared_in_current_scope(&identifier.name) {
+            self.errors.push(CompilerError::DeclarationError(
+                DeclarationError::MultipleDefinitions {
+                    token: identifier.token.clone(),
+                },
+            ));
+            return;
+        }
+        self.symbols.declare(&identifier.name, symbol);
+    }
+    /// make sure an annotated type exists, enums have to be declared before being used as a type
+    fn resolve_type(&mut self, data_type: &DataType, token: &Token) -> Option<DataType> {
+        if let Some(element) = data_type.element_type() {
+            self.resolve_type(element, token)?;
+            return Some(data_type.clone());
+        }
+        let defined = match data_type {
+            DataType::ENUM(name) => matches!(self.symbols.lookup(name), Some(Symbol::Enum(_))),
+            DataType::RECORD(name) => {
+                matches!(self.symbols.lookup(name), Some(Symbol::Record(_)))
+            }
+            _ => true,
+        };
+        if !defined {
+            let (DataType::ENUM(name) | DataType::RECORD(name)) = data_type else {
+                unreachable!();
+            };
+            self.undefined(&IdentifierNode {
+                name: name.clone(),
+                token: token.clone(),
+            });
+            return None;
+        }
+        return Some(data_type.clone());
+    }
+    fn check_declaration(&mut self, declaration: &VariableDeclarationNode) {
+        let value_type = match &declaration.initial_value {
+            Some(value) => match self.check_expression(value) {
+                Some(t) => Some(t),
+                None => return,
+            },
+            None => None,
+        };
+        let declared_type = match &declaration.data_type {
+            Some(t) => match self.resolve_type(t, &declaration.variable_name.token) {
+                Some(t) => Some(t),
+                None => return,
+            },
+            None => None,
+        };
+        let data_type = match (declared_type, value_type) {
+            (Some(declared), Some(value)) => {
+                if !declared.accepts(&value) {
+                    let token = declaration.initial_value.as_ref().unwrap().token().clone();
+                    self.type_mismatch(&token, declared.clone());
+                }
+                declared
+            }
+            (Some(declared), None) => declared,
+            (None, Some(value)) => value,
+            (None, None) => return,
+        };
+        self.declare(&declaration.variable_name, Symbol::Variable(data_type));
+    }
+    fn check_dim(&mut self, dim: &DimNode) {
+        for size in [Some(&dim.columns), dim.rows.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(t) = self.check_expression(size)
+                && !t.is_integer()
+            {
+                self.type_mismatch(size.token(), DataType::INT);
+            }
+        }
+        if self
+            .resolve_type(&dim.element_type, &dim.variable_name.token)
+            .is_some()
+        {
+            self.declare(&dim.variable_name, Symbol::Variable(dim.data_type()));
+        }
+    }
+    /// the type of a variable that is assigned to, constants and other symbols can't be assigned
+    fn assignable_type(&mut self, target: &IdentifierNode) -> Option<DataType> {
+        return match self.symbols.lookup(&target.name) {
+            Some(Symbol::Variable(t)) => Some(t.clone()),
+            Some(_) => {
+                self.semantic_error(SemanticError::IllegalIdentifier {
+                    token: target.token.clone(),
+                    identifier: target.clone(),
+                });
+                None
+            }
+            None => {
+                self.undefined(target);
+                None
+            }
+        };
+    }
+    fn check_assignment(&mut self, assignment: &AssignmentNode) {
+        let value_type = self.check_expression(&assignment.value);
+        let Some(mut target_type) = self.assignable_type(&assignment.target) else {
+            return;
+        };
+        // follow the path one step at a time, the type of each step decides what the next one can be
+        let mut target = Expression::IDENTIFIER(assignment.target.clone());
+        for access in &assignment.path {
+            target = AssignmentNode::access(target, std::slice::from_ref(access));
+            let next_type = match &target {
+                Expression::FIELD(access) => self.field_type(&target_type, access),
+                Expression::INDEX(node) => self.index_type(&target_type, node),
+                _ => None,
+            };
+            target_type = match next_type {
+                Some(t) => t,
+                None => return,
+            };
+        }
+        if let Some(value_type) = value_type
+            && !target_type.accepts(&value_type)
+        {
+            self.type_mismatch(assignment.value.token(), target_type);
+        }
+    }
+    /// the type of a field of a record typed value
+    fn field_type(&mut self, record_type: &DataType, access: &FieldAccessNode) -> Option<DataType> {
+        let fields = match record_type {
+            DataType::RECORD(name) => match self.symbols.lookup(name) {
+                Some(Symbol::Record(fields)) => fields,
+                _ => return None,
+            },
+            _ => {
+                self.semantic_error(SemanticError::InvalidOperation {
+                    token: access.field.token.clone(),
+                    expression: Expression::FIELD(access.clone()),
+                });
+                return None;
+            }
+        };
+        return match fields.iter().find(|(name, _)| *name == access.field.name) {
+            Some((_, t)) => Some(t.clone()),
+            None => {
+                self.undefined(&access.field);
+                None
+            }
+        };
+    }
+    /// a record can hold fields of any type including records declared before it, but not the same field twice
+    fn check_record(&mut self, declaration: &RecordDeclarationNode) {
+        let mut fields: Vec<(String, DataType)> = Vec::new();
+        for field in &declaration.fields {
+            if fields.iter().any(|(name, _)| *name == field.name.name) {
+                self.errors.push(CompilerError::DeclarationError(
+                    DeclarationError::MultipleDefinitions {
+                        token: field.name.token.clone(),
+                    },
+                ));
+                continue;
+            }
+            if let Some(t) = self.resolve_type(&field.data_type, &field.name.token) {
+                fields.push((field.name.name.clone(), t));
+            }
+        }
+        self.declare(&declaration.name, Symbol::Record(fields));
+    }
+    /// a function is declared before its body is checked so it can call itself. The body can't leave the loops around the declaration
+    fn check_function(&mut self, declaration: &FunctionDeclarationNode) {
+        let parameters: Vec<DataType> = declaration
+            .parameters
+            .iter()
+            .map(|p| {
+                self.resolve_type(&p.data_type, &p.name.token)
+                    .unwrap_or(p.data_type.clone())
+            })
+            .collect();
+        let return_type = declaration
+            .return_type
+            .as_ref()
+            .and_then(|t| self.resolve_type(t, &declaration.name.token));
+        self.declare(
+            &declaration.name,
+            Symbol::Function {
+                parameters: parameters.clone(),
+                return_type: return_type.clone(),
+            },
+        );
+        self.symbols.push_scope();
+        for (parameter, data_type) in declaration.parameters.iter().zip(parameters) {
+            self.declare(&parameter.name, Symbol::Variable(data_type));
+        }
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let in_function = std::mem::replace(&mut self.in_function, true);
+        let outer_return_type = std::mem::replace(&mut self.return_type, return_type);
+        self.check_statements(&declaration.body);
+        self.loop_depth = loop_depth;
+        self.in_function = in_function;
+        self.return_type = outer_return_type;
+        self.symbols.pop_scope();
+    }
+    /// subroutines entered with GOSUB and functions without a return type can't return a value,
+    /// a function with one returns the default of its type from a bare `RET`
+    fn check_return(&mut self, ret: &ReturnNode) {
+        let Some(expression) = &ret.return_expression else {
+            return;
+        };
+        let value_type = self.check_expression(expression);
+        let Some(return_type) = self.return_type.clone().filter(|_| self.in_function) else {
+            self.semantic_error(SemanticError::InvalidOperation {
+                token: ret.token.clone(),
+                expression: *expression.clone(),
+            });
+            return;
+        };
+        if value_type.is_some_and(|t| !return_type.accepts(&t)) {
+            self.type_mismatch(expression.token(), return_type);
+        }
+    }
+    /// check an expression that gets stringified, which any value but a record or a collection can be
+    fn check_stringified(&mut self, expression: &Expression) -> Option<DataType> {
+        let data_type = self.check_expression(expression)?;
+        if !data_type.is_primitive() {
+            self.semantic_error(SemanticError::InvalidOperation {
+                token: expression.token().clone(),
+                expression: expression.clone(),
+            });
+            return None;
+        }
+        return Some(data_type);
+    }
+    fn check_enum(&mut self, declaration: &EnumDeclarationNode) {
+        let values = match declaration.values() {
+            Ok(values) => values,
+            Err(member) => {
+                self.semantic_error(SemanticError::UnannotatedEnumValue {
+                    token: member.name.token.clone(),
+                });
+                return;
+            }
+        };
+        self.declare(&declaration.name, Symbol::Enum(values.clone()));
+        for (member, (_, value)) in declaration.members.iter().zip(values) {
+            self.declare(
+                &member.name,
+                Symbol::Constant(DataType::ENUM(declaration.name.name.clone()), value),
+            );
+        }
+    }
+    fn check_penum(&mut self, penum: &PrintEnumNode) {
+        match self.symbols.lookup(&penum.enum_name.name) {
+            Some(Symbol::Enum(_)) => (),
+            Some(_) => self.semantic_error(SemanticError::IllegalIdentifier {
+                token: penum.enum_name.token.clone(),
+                identifier: penum.enum_name.clone(),
+            }),
+            None => self.undefined(&penum.enum_name),
+        }
+        if let Some(t) = self.check_expression(&penum.value)
+            && !t.is_numeric()
+        {
+            self.type_mismatch(penum.value.token(), DataType::INT);
+        }
+    }
+    /// jumps within the module are resolved once all lines are known, the lines of imported modules are known up front
+    fn check_jump(&mut self, jump: &JumpNode) {
+        let Some(module) = &jump.module else {
+            self.jumps.push(jump.clone());
+            return;
+        };
+        let path = match self.symbols.lookup(&module.name) {
+            Some(Symbol::Module(path)) => path.clone(),
+            Some(_) => {
+                self.semantic_error(SemanticError::IllegalIdentifier {
+                    token: module.token.clone(),
+                    identifier: module.clone(),
+                });
+                return;
+            }
+            None => {
+                self.undefined(module);
+                return;
+            }
+        };
+        if !self.imports[&path].contains(&jump.line_number) {
+            self.semantic_error(SemanticError::UndefinedLine {
+                token: jump.token.clone(),
+                line_number: jump.line_number,
+            });
+        }
+    }
+    fn check_import(&mut self, import: &ImportNode) {
+        if !self.imports.contains_key(&import.path) {
+            self.errors.push(CompilerError::MiscError(
+                MiscellaneousError::InvalidImport {
+                    token: import.token.clone(),
+                    path: PathBuf::from(&import.path),
+                },
+            ));
+            return;
+        }
+        self.declare(&import.alias, Symbol::Module(import.path.clone()));
+    }
+    fn check_menu(&mut self, menu: &MenuNode) {
+        if let Some(t) = self.check_expression(&menu.selector)
+            && !t.is_integer()
+        {
+            self.type_mismatch(menu.selector.token(), DataType::INT);
+        }
+        self.check_statements(&menu.actions);
+    }
+    /// the expression of a `DEF` is checked in a scope of its own, with every parameter a variable of the type of its argument.
+    /// There is no return type to stop at, so calling a `DEF` from its own expression is an error
+    fn check_lambda_call(
+        &mut self,
+        call: &FunctionCallNode,
+        argument_types: &[Option<DataType>],
+        parameters: &[IdentifierNode],
+        body: &Expression,
+    ) -> Option<DataType> {
+        if call.arguments.len() != parameters.len() {
+            self.errors.push(CompilerError::DeclarationError(
+                DeclarationError::InvalidSignature {
+                    token: call.token.clone(),
+                },
+            ));
+            return None;
+        }
+        if self.lambdas.contains(&call.name) {
+            self.semantic_error(SemanticError::IllegalIdentifier {
+                token: call.token.clone(),
+                identifier: IdentifierNode {
+                    name: call.name.clone(),
+                    token: call.token.clone(),
+                },
+            });
+            return None;
+        }
+        // an argument that didn't check has already been reported
+        let argument_types: Vec<DataType> =
+            argument_types.iter().cloned().collect::<Option<_>>()?;
+        self.symbols.push_scope();
+        for (parameter, data_type) in parameters.iter().zip(argument_types) {
+            self.declare(parameter, Symbol::Variable(data_type));
+        }
+        self.lambdas.push(call.name.clone());
+        let data_type = self.check_expression(body);
+        self.lambdas.pop();
+        self.symbols.pop_scope();
+        return data_type;
+    }
+    /// check the arguments of a call and return the type it evaluates to, functions with overloads are resolved by the argument types.
+    /// Trailing string and date parameters of positional functions are optional (e.g. a bare `PRINT` or `TIME`), as are parameters declared as `optional_<name>` (e.g. the length of `MID$`).
+    /// Any argument passed as a string parameter gets stringified
+    fn check_function_call(
+        &mut self,
+        call: &FunctionCallNode,
+        as_expression: bool,
+    ) -> Option<DataType> {
+        let argument_types: Vec<Option<DataType>> = call
+            .arguments
+            .iter()
+            .map(|a| self.check_expression(a))
+            .collect();
+        if let Some(Symbol::Function {
+            parameters,
+            return_type,
+        }) = self.symbols.lookup(&call.name).cloned()
+        {
+            if call.arguments.len() != parameters.len() {
+                self.errors.push(CompilerError::DeclarationError(
+                    DeclarationError::InvalidSignature {
+                        token: call.token.clone(),
+                    },
+                ));
+            }
+            for ((argument, argument_type), parameter) in
+                call.arguments.iter().zip(argument_types).zip(parameters)
+            {
+                if argument_type.is_some_and(|t| !parameter.accepts(&t)) {
+                    self.type_mismatch(argument.token(), parameter);
+                }
+            }
+            if as_expression && return_type.is_none() {
+                self.semantic_error(SemanticError::InvalidOperation {
+                    token: call.token.clone(),
+                    expression: Expression::FUNC_CALL(call.clone()),
+                });
+            }
+            return return_type;
+        }
+        if let Some(Symbol::Lambda { parameters, body }) = self.symbols.lookup(&call.name).cloned()
+        {
+            return self.check_lambda_call(call, &argument_types, &parameters, &body);
+        }
+        // LEN of a list or map is its number of elements
+        if call.name == "LEN" && matches!(&argument_types[..], [Some(t)] if t.is_collection()) {
+            return Some(DataType::INT);
+        }
+        let function = match resolve_function(&call.name, &argument_types) {
+            Some(f) => f,
+            None => {
+                self.undefined(&IdentifierNode {
+                    name: call.name.clone(),
+                    token: call.token.clone(),
+                });
+                return None;
+            }
+        };
+        let parameters = function.parameters();
+        let mut required = parameters.len() - function.optional_parameters;
+        if function.is_positional() {
+            while required > 0
+                && matches!(
+                    parameters[required - 1],
+                    Some(DataType::STRING) | Some(DataType::DATE)
+                )
+            {
+                required -= 1;
+            }
+        }
+        if call.arguments.len() < required || call.arguments.len() > parameters.len() {
+            self.errors.push(CompilerError::DeclarationError(
+                DeclarationError::InvalidSignature {
+                    token: call.token.clone(),
+                },
+            ));
+        }
+        for ((argument, argument_type), parameter) in
+            call.arguments.iter().zip(argument_types).zip(parameters)
+        {
+            match (parameter, argument_type) {
+                // only a parameter declared with that exact type takes a collection, like EXEC's arguments
+                (Some(parameter), Some(argument_type)) if !parameter.is_primitive() => {
+                    if !parameter.accepts(&argument_type) {
+                        self.type_mismatch(argument.token(), parameter);
+                    }
+                }
+                // records and collections can't be stringified or passed as dynamically typed values
+                (_, Some(t)) if !t.is_primitive() => {
+                    self.semantic_error(SemanticError::InvalidOperation {
+                        token: argument.token().clone(),
+                        expression: argument.clone(),
+                    });
+                }
+                (Some(DataType::STRING), _) | (None, _) | (_, None) => (),
+                (Some(parameter), Some(argument_type)) => {
+                    if !parameter.accepts(&argument_type) {
+                        self.type_mismatch(argument.token(), parameter);
+                    }
+                }
+            }
+        }
+        let return_type = function.return_data_type();
+        if as_expression && return_type.is_none() {
+            self.semantic_error(SemanticError::InvalidOperation {
+                token: call.token.clone(),
+                expression: Expression::FUNC_CALL(call.clone()),
+            });
+        }
+        return return_type;
+    }
+    /// returns the type of the expression or [`None`] if it is invalid, in which case the error has already been reported
+    fn check_expression(&mut self, expression: &Expression) -> Option<DataType> {
+        return match expression {
+            Expression::CONSTANT(constant) => {
+                if constant.data_type == DataType::INT && constant.value.parse::<i64>().is_err() {
+                    self.errors.push(CompilerError::MiscError(
+                        MiscellaneousError::InvalidConstant {
+                            token: constant.token.clone(),
+                            expression: Box::new(expression.clone()),
+                        },
+                    ));
+                    return None;
+                }
+                Some(constant.data_type.clone())
+            }
+            Expression::IDENTIFIER(identifier) => match self.symbols.lookup(&identifier.name) {
+                Some(Symbol::Variable(t)) | Some(Symbol::Constant(t, _)) => Some(t.clone()),
+                Some(Symbol::Enum(_))
+                | Some(Symbol::Module(_))
+                | Some(Symbol::Record(_))
+                | Some(Symbol::Function { .. })
+                | Some(Symbol::Lambda { .. }) => {
+                    self.semantic_error(SemanticError::IllegalIdentifier {
+                        token: identifier.token.clone(),
+                        identifier: identifier.clone(),
+                    });
+                    None
+                }
+                None => {
+                    self.undefined(identifier);
+                    None
+                }
+            },
+            Expression::UNARY_OP(unary) => {
+                let target = self.check_expression(&unary.target)?;
+                let result = unary.operation.result_type(&target);
+                if result.is_none() {
+                    self.semantic_error(SemanticError::InvalidOperation {
+                        token: unary.token.clone(),
+                        expression: expression.clone(),
+                    });
+                }
+                result
+            }
+            Expression::BINARY_OP(binary) => {
+                // `LET A = B = C` reads like two assignments, outside of conditions comparing needs `==` to be clear
+                if !self.in_condition
+                    && matches!(&binary.token.kind, TokenKind::Operator(o) if o == "=")
+                {
+                    self.warnings.push(CompilerWarning::AmbiguousEquality {
+                        token: binary.token.clone(),
+                    });
+                }
+                let left = self.check_expression(&binary.left);
+                let right = self.check_expression(&binary.right);
+                let result = binary.operation.result_type(&left?, &right?);
+                if result.is_none() {
+                    self.semantic_error(SemanticError::InvalidOperation {
+                        token: binary.token.clone(),
+                        expression: expression.clone(),
+                    });
+                }
+                result
+            }
+            Expression::FUNC_CALL(call) => self.check_function_call(call, true),
+            // the options and prompt get stringified, so any type is fine
+            Expression::POPTIONS(poptions) => {
+                for option in &poptions.options {
+                    self.check_stringified(option);
+                }
+                self.check_stringified(&poptions.prompt);
+                Some(DataType::INT)
+            }
+            Expression::FIELD(access) => {
+                let record_type = self.check_expression(&access.record)?;
+                self.field_type(&record_type, access)
+            }
+            Expression::INDEX(node) => {
+                let collection_type = self.check_expression(&node.collection)?;
+                self.index_type(&collection_type, node)
+            }
+            Expression::COLLECTION(collection) => self.check_collection(collection),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usbcompiler::{
+        parser::parser::{EvaluationContext, Parser},
+        tokenizer::lexer::Lexer,
+    };
+
+    fn check_script(input: &str) -> Result<(), Vec<CompilerError>> {
+        let tokens = Lexer::new(input.into()).tokenize().unwrap();
+        let ast = Parser::new(tokens, EvaluationContext::FileLevel)
+            .parse()
+            .unwrap();
+        return Checker::new().check(&ast);
+    }
+    fn check_warnings(input: &str) -> Vec<CompilerWarning> {
+        let mut lexer = Lexer::new(input.into());
+        let tokens = lexer.tokenize().unwrap();
+        let ast = Parser::new(tokens, EvaluationContext::FileLevel)
+            .parse()
+            .unwrap();
+        let mut checker = Checker::new().with_allowed(lexer.allowed());
+        checker.check(&ast).unwrap();
+        return checker.warnings();
+    }
+    #[test]
+    fn test_valid_program() {
+        let result = check_script(
+            "ENUM direction = NORTH, EAST, SOUTH, WEST\nLET X AS direction\nX = SOUTH + 1\nPENUM direction, X\nPRINT \"X is \" + X",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+    }
+    #[test]
+    fn test_duplicate_line_number() {
+        let errors = check_script("10 PRINT 1\nPRINT 2\n11 PRINT 3").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::DeclarationError(
+                DeclarationError::MultipleDefinitions { .. }
+            )]
+        ));
+    }
+    #[test]
+    fn test_scoped_variable() {
+        let errors = check_script("BEGIN\nLET X = 5\nFIN\nPRINT X").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::SemanticError(
+                SemanticError::UndefinedIdentifier { .. }
+            )]
+        ));
+    }
+    #[test]
+    fn test_type_mismatch() {
+        let errors = check_script("LET X AS INTEGER\nX = \"Hello World\"").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::SemanticError(SemanticError::TypeMismatch {
+                expected_type: DataType::INT,
+                ..
+            })]
+        ));
+    }
+    #[test]
+    fn test_enum_checks() {
+        let errors = check_script("ENUM shade = CYAN 2, GREEN 0, BLUE").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::SemanticError(
+                SemanticError::UnannotatedEnumValue { .. }
+            )]
+        ));
+
+        let errors = check_script("LET X AS direction").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::SemanticError(
+                SemanticError::UndefinedIdentifier { .. }
+            )]
+        ));
+
+        let errors =
+            check_script("ENUM job = FASTFOOD 2, TEACHER\nPENUM job, \"TEACHER\"").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::SemanticError(
+                SemanticError::TypeMismatch { .. }
+            )]
+        ));
+    }
+    #[test]
+    fn test_jump_targets() {
+        let result = check_script("10 GOSUB 30\n20 END\n30 PRINT \"sub\"\nRET\nMENU 1 GOTO 10, 20");
+        assert!(result.is_ok(), "{:#?}", result);
+
+        let errors = check_script("10 GOTO 20\nMENU \"1\" GOSUB 10").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::SemanticError(SemanticError::UndefinedLine {
+                    line_number: 20,
+                    ..
+                })
+            ]
+        ));
+    }
+    #[test]
+    fn test_aliased_jumps() {
+        let tokens = Lexer::new("IMPORT \"lib.usb\" AS LIB\nGOSUB LIB, 10\nGOTO LIB, 20".into())
+            .tokenize()
+            .unwrap();
+        let ast = Parser::new(tokens, EvaluationContext::FileLevel)
+            .parse()
+            .unwrap();
+        let imports = HashMap::from([("lib.usb".to_string(), HashSet::from([10]))]);
+        let errors = Checker::new()
+            .with_imports(imports)
+            .check(&ast)
+            .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::SemanticError(SemanticError::UndefinedLine {
+                line_number: 20,
+                ..
+            })]
+        ));
+
+        let errors = check_script("IMPORT \"lib.usb\" AS LIB\nGOTO OTHER, 10").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::MiscError(MiscellaneousError::InvalidImport { .. }),
+                CompilerError::SemanticError(SemanticError::UndefinedIdentifier { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_file_descriptor_types() {
+        let result = check_script(
+            "LET F = OPEN \"a.txt\", \"r\"\nLET L AS STRING\nL = READLINE F\nAPPEND F 5",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+
+        let errors = check_script("WRITE \"a.txt\" \"Hello\"").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::SemanticError(SemanticError::TypeMismatch {
+                expected_type: DataType::INT,
+                ..
+            })]
+        ));
+    }
+    #[test]
+    fn test_named_colors() {
+        let result = check_script("FCOLOR RED\nBCOLOR #FFF\nLET RED = \"shadowed\"\nPRINT RED");
+        assert!(result.is_ok(), "{:#?}", result);
+
+        let errors = check_script("FCOLOR \"RED\"").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::SemanticError(
+                SemanticError::TypeMismatch { .. }
+            )]
+        ));
+    }
+    #[test]
+    fn test_screen_targets() {
+        let result =
+            check_script("LET C AS INT\nLET R = 0\nSCREEN C, R\nHTAB C - 1\nFLASH \"yes\"");
+        assert!(result.is_ok(), "{:#?}", result);
+
+        let errors = check_script("LET C = 0.5\nSCREEN C, RED").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::SemanticError(SemanticError::IllegalIdentifier { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_date_types() {
+        let result = check_script(
+            "LET D AS DATE = DATE + DAY 1\nD = D - HOUR 2\nLET H AS FLOAT = TIME + TIME(D)\nPRINT FORMATDATE(D, \"%H:%M\")",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+
+        let errors = check_script("LET T AS INT\nT = TIME\nPRINT DAY").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::DeclarationError(DeclarationError::InvalidSignature { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_read_targets() {
+        let result =
+            check_script("LET A AS FLOAT\nLET S = \"\"\nREAD A, S\nRESTORE 10\n10 DATA 1, \"a\"");
+        assert!(result.is_ok(), "{:#?}", result);
+
+        let errors = check_script("LET B = TRUE\nREAD B, RED\nRESTORE 5\nDATA 1.5").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::IllegalIdentifier { .. }),
+                CompilerError::SemanticError(SemanticError::UndefinedLine { .. }),
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_optional_parameters() {
+        let result = check_script(
+            "LET S$ = \"abc\"\nLET N AS INT = LEN(S$) + INSTR(S$, \"b\") + INSTR(S$, \"b\", 2)\nPRINT MID$(S$, 2) + MID$(S$, 1, N) + LEFT$(S$, 1)",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+
+        let errors = check_script("PRINT LEFT$(\"abc\")\nPRINT MID$(\"abc\", \"1\")").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::DeclarationError(DeclarationError::InvalidSignature { .. }),
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_math_overloads() {
+        let result = check_script(
+            "LET I AS INT = ABS(-1) + MAX(1, 2) + FLOOR(PI)\nLET F AS FLOAT = ABS(-1.5) + MIN(1, 0.5) + SQR(2)",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+
+        let errors = check_script("LET I AS INT = ABS(-1.5)\nLET J AS INT = SIN(1)").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_random_numbers() {
+        let result = check_script(
+            "RANDOMIZE\nRANDOMIZE 42\nLET F AS FLOAT = RND(1) + RND(-1.5)\nLET I AS INT = RNDINT(1, 6)",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+
+        let errors = check_script("LET I AS INT = RND(1)\nPRINT RNDINT(6)").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::DeclarationError(DeclarationError::InvalidSignature { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_print_format() {
+        let errors =
+            check_script("PRINT \"{:<8} {:.2} {{}}\", \"a\", 1.5\nPRINT \"{}\"").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::DeclarationError(
+                DeclarationError::InvalidSignature { .. }
+            )]
+        ));
+        let errors = check_script("PRINT \"{:x}\", 1\nPRINT \"}\"").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::MiscError(MiscellaneousError::InvalidConstant { .. }),
+                CompilerError::MiscError(MiscellaneousError::InvalidConstant { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_input_targets() {
+        let result = check_script(
+            "LET A = 0\nLET B AS DATE\nINPUT \"A, B\"; A, B\nONERR PRINT ERR\nTHROW A + 1",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script(
+            "ENUM direction = NORTH, EAST\nLET D AS direction\nINPUT D\nTHROW 0\nTHROW \"code\"",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::MiscError(MiscellaneousError::InvalidConstant { .. }),
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_records() {
+        let result = check_script(
+            "RECORD Point\nx AS INTEGER\ny AS FLOAT\nFIN\nRECORD Line\nfrom AS Point\nto AS Point\nFIN\nLET l AS Line\nLET p = l.from\np.y = p.x\nl.to = p\nPRINT l.to.y",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script(
+            "RECORD Point\nx AS INTEGER\nFIN\nLET p AS Point\np.z = 1\np = 1\nPRINT p",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::UndefinedIdentifier { .. }),
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::SemanticError(SemanticError::InvalidOperation { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_element_field_assignment() {
+        let result = check_script(
+            "RECORD Person\nage AS INT\nFIN\nLET people AS LIST OF Person\nLET p AS Person\nPUSH people, p\npeople[0].age = 99\npeople[0].age += 1",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script(
+            "RECORD Person\nage AS INT\nFIN\nLET people AS LIST OF Person\npeople[0].age = \"old\"\npeople[\"a\"].age = 1\npeople.age = 1",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::SemanticError(SemanticError::InvalidOperation { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_counting_loops() {
+        let result = check_script(
+            "FOR i = 1 TO 10 STEP 2\nLET n AS INT = i\nNEXT\nFOR x = 0 TO 1 STEP 0.5\nLET f AS FLOAT = x\nNEXT",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script(
+            "FOR x = 0 TO 1 STEP 0.5\nLET n AS INT = x\nNEXT\nFOR i = 1 TO \"10\"\nNEXT\nPRINT i",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::SemanticError(SemanticError::UndefinedIdentifier { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_c_style_loops() {
+        let result = check_script(
+            "FOR i = 0, i < 10, i += 1\nLET n AS INT = i\nNEXT\nFOR x = 0.5, x < 4, x = x * 2\nLET f AS FLOAT = x\nNEXT",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors =
+            check_script("FOR i = 0, i + 1, i += 1\nNEXT\nFOR s = \"a\", TRUE, s = \"b\"\nNEXT")
+                .unwrap_err();
+        assert!(
+            matches!(
+                errors[..],
+                [
+                    CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                    CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                    CompilerError::SemanticError(SemanticError::UndefinedIdentifier { .. })
+                ]
+            ),
+            "{:#?}",
+            errors
+        );
+    }
+    #[test]
+    fn test_functions() {
+        let result = check_script(
+            "RECORD Person\nage AS INT\nFIN\nFUNCTION older(p AS Person, years AS INT) AS FLOAT\nIF years == 0 THEN RET\nRET p.age + older(p, years - 1)\nFIN\nFUNCTION greet(p AS Person)\nPRINT p.age\nRET\nFIN\nLET p AS Person\nLET f = older(p, 2)\ngreet(p)",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script(
+            "RECORD Person\nage AS INT\nFIN\n10 FUNCTION f(p AS Person) AS Person\n20 GOTO 40\n30 RET p\n40 FIN\n50 GOSUB 30\nFUNCTION g(x AS INT)\nRET x\nFIN\nLET p AS Person\nLET x = g(1)\ng(p)\ng(1, 2)",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::FunctionJump { .. }),
+                CompilerError::SemanticError(SemanticError::InvalidOperation { .. }),
+                CompilerError::SemanticError(SemanticError::InvalidOperation { .. }),
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::DeclarationError(DeclarationError::InvalidSignature { .. }),
+                CompilerError::SemanticError(SemanticError::FunctionJump { .. }),
+            ]
+        ));
+    }
+    #[test]
+    fn test_collections() {
+        let result = check_script(
+            "RECORD Point\nx AS INTEGER\nFIN\nLET l AS LIST OF FLOAT\nPUSH l, 1\nINSERT l, 0, l[0] / 2\nLET m AS MAP OF STRING TO Point\nLET p AS Point\nm[\"origin\"] = p\nFOR EACH key IN m\nPRINT key; m[key].x; LEN(l) + POP l\nNEXT\nLET found = HAS(m, \"origin\") AND LEN(KEYS(m)) > 0\nLET words = SPLIT(\"a b\", \" \")\nPUSH words, \"c\"",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script(
+            "LET l AS LIST OF INT\nPUSH l, \"a\"\nLET k = KEYS(l)\nPRINT l[\"a\"]\nFOR EACH x IN 5\nNEXT\nPRINT l",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::SemanticError(SemanticError::InvalidOperation { .. }),
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::SemanticError(SemanticError::InvalidOperation { .. }),
+                CompilerError::SemanticError(SemanticError::InvalidOperation { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_dim() {
+        let result = check_script(
+            "RECORD Point\nx AS INTEGER\nFIN\nDIM points(4) AS Point\npoints[0].x = 1\nDIM grid(2, 3) AS FLOAT\ngrid[1][2] = points[0].x\nPUSH grid[0], 1.5",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script(
+            "DIM a(1.5) AS INT\nDIM b(2) AS Shape\nDIM c(2) AS STRING\nc[0] = 1\nDIM c(3) AS INT",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::SemanticError(SemanticError::UndefinedIdentifier { .. }),
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::DeclarationError(DeclarationError::MultipleDefinitions { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_def() {
+        let result = check_script(
+            "RECORD Point\nx AS INTEGER\nFIN\nDEF square x = x * x\nDEF right(p, n) = p.x + n\nLET p AS Point\nLET a AS INT = square(2)\nLET b AS FLOAT = square(1.5) + right(p, square(3))",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        // the parameters only exist in the expression, which is checked with the types of the arguments
+        let errors = check_script(
+            "DEF f x = f(x)\nPRINT f(1)\nDEF g x = x * 2\nLET s AS INT = g(1.5)\nPRINT g(1, 2)\nPRINT x\nLET h = g",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::IllegalIdentifier { .. }),
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::DeclarationError(DeclarationError::InvalidSignature { .. }),
+                CompilerError::SemanticError(SemanticError::UndefinedIdentifier { .. }),
+                CompilerError::SemanticError(SemanticError::IllegalIdentifier { .. })
+            ]
+        ));
+    }
+    #[test]
+    fn test_environment() {
+        let result = check_script(
+            "LET count = ARGC\nLET name = ARGV$(count)\nSETENV \"NAME\", name\nPRINT ENV$(\"NAME\") + ARGC",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script("PRINT ARGV$(\"1\")").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::SemanticError(
+                SemanticError::TypeMismatch { .. }
+            )]
+        ));
+    }
+
+    #[test]
+    fn test_processes() {
+        let result = check_script(
+            "LET out$ = SHELL \"ls -la\"\nLET status = EXEC SPLIT(out$, \" \")\nPRINT STDERR + status",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script("LET numbers AS LIST OF INT\nPRINT EXEC \"ls\", EXEC numbers")
+            .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. }),
+                CompilerError::SemanticError(SemanticError::TypeMismatch { .. })
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_filesystem() {
+        let result = check_script(
+            "MKDIR \"logs\"\nFOR EACH name IN DIR(\"logs\")\nLET size = FILESIZE(name)\nCOPY name, name + \".bak\"\nNEXT\nLET found AS BOOL = EXISTS(\"logs\")\nLET names AS LIST OF STRING = DIR()",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script("LET names AS LIST OF INT = DIR()").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::SemanticError(
+                SemanticError::TypeMismatch { .. }
+            )]
+        ));
+    }
+
+    #[test]
+    fn test_integer_operators() {
+        let result = check_script(
+            "LET n = 7 MOD 2 + 7 \\ 2\nn += 1 SHL 3 BAND 255\nLET even AS BOOL = n BAND 1 = 0",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script("LET x = 7.5 MOD 2\nLET s$ = \"a\"\ns$ -= \"b\"").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::InvalidOperation { .. }),
+                CompilerError::SemanticError(SemanticError::InvalidOperation { .. })
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_ambiguous_equality() {
+        // conditions can only compare and == always does
+        let warnings = check_warnings(
+            "LET x = 1\nIF x = 1 THEN x = 2\nWHILE x = 2 AND x <> 3\nWEND\nLET same = x == 2\nPRINT x == 1",
+        );
+        assert!(warnings.is_empty(), "{:#?}", warnings);
+        let warnings =
+            check_warnings("LET x = 1\nLET same = x = 2\nPRINT x = 1\nIF x = 1 THEN PRINT x = 2");
+        assert_eq!(warnings.len(), 3, "{:#?}", warnings);
+        assert!(matches!(
+            &warnings[0],
+            CompilerWarning::AmbiguousEquality { token } if token.position_span.line == 2
+        ));
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let warnings = check_warnings(
+            "10 GOSUB 60\nGOTO 40\nPRINT 1\nPRINT 2\n40 WHILE TRUE\nEXIT\nPRINT 3\nWEND\nEND\n60 RET",
+        );
+        assert!(matches!(
+            &warnings[..],
+            [
+                CompilerWarning::UnreachableCode { token: first },
+                CompilerWarning::UnreachableCode { token: second }
+            ] if first.position_span.line == 3 && second.position_span.line == 7
+        ));
+        // the subroutine at 30 runs into the end of the program, the one at 50 jumps to a RET
+        let warnings = check_warnings(
+            "GOSUB 30\nGOSUB 50\nEND\n30 PRINT 1\nIF TRUE THEN RET\nGOTO 80\n50 GOTO 70\n60 RET\n70 GOSUB 30\nGOTO 60\n80 PRINT 2",
+        );
+        assert!(matches!(
+            &warnings[..],
+            [CompilerWarning::MissingReturn {
+                line_number: 30,
+                ..
+            }]
+        ));
+        // STOP ends the program like END does
+        let warnings = check_warnings("IF ARGC > 1 THEN STOP 2\nSTOP 0\nPRINT 1");
+        assert!(matches!(
+            &warnings[..],
+            [CompilerWarning::UnreachableCode { token }] if token.position_span.line == 3
+        ));
+        assert!(matches!(
+            check_script("STOP \"done\"").unwrap_err()[..],
+            [CompilerError::SemanticError(
+                SemanticError::TypeMismatch { .. }
+            )]
+        ));
+        // a handler returns to where the error was thrown
+        let warnings = check_warnings("ONERR PRINT ERR\nGOSUB 30\nEND\n30 ONERR GOTO 40\n40 RET");
+        assert!(warnings.is_empty(), "{:#?}", warnings);
+        // any line of an imported module can be jumped to
+        let tokens = Lexer::new("10 END\n20 PRINT 1\nRET".into())
+            .tokenize()
+            .unwrap();
+        let ast = Parser::new(tokens, EvaluationContext::Imported)
+            .parse()
+            .unwrap();
+        let mut checker = Checker::new().imported();
+        checker.check(&ast).unwrap();
+        assert!(checker.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_warning_display() {
+        let warnings = check_warnings("STOP 1\n  PRINT 1");
+        let messages: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec!["2:3: this line never runs (ALLOW UNREACHABLE)"]
+        );
+        let warnings = check_warnings("LET n AS INTEGER\nGOSUB 50\nEND\n50 PRINT n = 1");
+        let messages: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "4:12: `=` compares here, use `==` to compare outside of a condition (ALLOW EQUALITY)",
+                "2:7: the subroutine at line 50 can run into the end of the program without a RET (ALLOW RETURN)",
+                "4:10: N may be read before it is assigned a value (ALLOW UNASSIGNED)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_display() {
+        let errors = check_script("LET s AS STRING = 5\nPRINT y").unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "1:19: expected a value of type STRING",
+                "2:7: Y isn't declared"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jump_into_scope() {
+        let result = check_script(
+            "BEGIN\n20 LET X = 1\nGOTO 20\nGOTO 40\nFIN\n40 BEGIN\nGOTO 60\nLET Y = 2\n60 PRINT Y\nFIN",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script(
+            "GOSUB 30\nGOTO 50\nBEGIN\nLET X = 1\n30 PRINT X\nFIN\nLET l AS LIST OF INT\nFOR EACH i IN l\n50 PRINT i\nNEXT",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::JumpIntoScope {
+                    line_number: 30,
+                    ..
+                }),
+                CompilerError::SemanticError(SemanticError::JumpIntoScope {
+                    line_number: 50,
+                    ..
+                })
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_unassigned_reads() {
+        // the GOTO skips the assignment to a, the subroutine assigns b on its way back
+        let warnings = check_warnings(
+            "LET a AS INTEGER\nLET b AS INTEGER\nIF ARGC > 1 THEN GOTO 20\na = 1\n20 PRINT a\nGOSUB 100\nPRINT b\nEND\n100 b = 2\nRET",
+        );
+        assert!(matches!(
+            &warnings[..],
+            [CompilerWarning::UnassignedRead { token, name }] if name == "A" && token.position_span.line == 5
+        ));
+        let warnings = check_warnings(
+            "LET x = 1\nLET s$ AS STRING\nINPUT s$\nLET l AS LIST OF INTEGER\nPUSH l, x\nPRINT s$, l[0]\nLET n AS INTEGER\nWHILE n < 3\nn = n + 1\nWEND",
+        );
+        assert!(matches!(
+            &warnings[..],
+            [CompilerWarning::UnassignedRead { token, .. }, CompilerWarning::UnassignedRead { .. }]
+                if token.position_span.line == 8
+        ));
+        let warnings = check_warnings(
+            "LET n AS INTEGER\nPRINT n // ALLOW UNASSIGNED\nPRINT n, n = 1 REM allow equality, unassigned\nPRINT n // ALLOW EQUALITY",
+        );
+        assert!(matches!(
+            &warnings[..],
+            [CompilerWarning::UnassignedRead { token, .. }] if token.position_span.line == 4
+        ));
+        // CLEAR resets every variable and CSCOPE the ones of its scope, a collection stays meaningful when it is empty
+        let warnings = check_warnings(
+            "LET a = 1\nLET l AS LIST OF INTEGER\nPUSH l, a\nCLEAR\nPRINT a\nPRINT LEN(l)\na = 2\nPRINT a\nBEGIN\nLET b = 3\nCSCOPE\nPRINT a + b\nFIN",
+        );
+        assert!(
+            matches!(
+                &warnings[..],
+                [
+                    CompilerWarning::UnassignedRead { token: a, name: first },
+                    CompilerWarning::UnassignedRead { token: b, name: second }
+                ] if first == "A" && a.position_span.line == 5 && second == "B" && b.position_span.line == 12
+            ),
+            "{:#?}",
+            warnings
+        );
+    }
+
+    #[test]
+    fn test_loop_breaks() {
+        let result = check_script(
+            "LET n = 0\nWHILE n < 10\nn = n + 1\nDO\nCONTINUE\nLOOP UNTIL n > 5\nEXIT\nWEND",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors =
+            check_script("EXIT\nWHILE TRUE\nONERR CONTINUE\nWEND\nDO\nLET x = TRUE\nLOOP UNTIL x")
+                .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::InvalidLoopBreak { .. }),
+                CompilerError::SemanticError(SemanticError::InvalidLoopBreak { .. }),
+                CompilerError::SemanticError(SemanticError::UndefinedIdentifier { .. })
+            ]
+        ));
+        let errors = check_script("WHILE 1\nWEND").unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::SemanticError(
+                SemanticError::TypeMismatch { .. }
+            )]
+        ));
+    }
+
+    #[test]
+    fn test_select() {
+        let result = check_script(
+            "ENUM hue = RED, GREEN, BLUE\nLET h AS hue\nSELECT CASE h\nCASE RED\nCASE GREEN TO BLUE\nEND SELECT\nSELECT CASE \"a\"\nCASE \"b\", IS < \"c\"\nEND SELECT",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+        let errors = check_script(
+            "ENUM hue = RED, GREEN, BLUE\nLET h AS hue\nSELECT CASE h\nCASE RED\nEND SELECT\nSELECT CASE 1\nCASE \"a\"\nEND SELECT",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [
+                CompilerError::SemanticError(SemanticError::NonExhaustiveSelect { missing, .. }),
+                CompilerError::SemanticError(SemanticError::InvalidOperation { .. })
+            ] if *missing == vec!["GREEN".to_string(), "BLUE".to_string()]
+        ));
+        // CASE ELSE covers the rest
+        let result = check_script(
+            "ENUM hue = RED, GREEN\nLET h AS hue\nSELECT CASE h\nCASE ELSE\nEND SELECT",
+        );
+        assert!(result.is_ok(), "{:#?}", result);
+    }
+}
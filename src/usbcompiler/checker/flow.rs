@@ -0,0 +1,605 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::usbcompiler::ast::ast::{
+    AssignmentNode, CaseCondition, Expression, IdentifierNode, JumpNode, LineNode, LoopKind,
+    PrintItem, Statement, TargetAccess,
+};
+
+/// a statement of a [`FlowGraph`]. A compound statement like an `IF` or a loop stands for evaluating its header, the statements of its bodies are nodes of their own
+#[derive(Debug)]
+pub struct FlowNode<'a> {
+    /// `None` for the end of the module, falling off it ends the program
+    pub statement: Option<&'a Statement>,
+    /// the line the statement is on
+    pub line: Option<&'a LineNode>,
+    /// the nodes that can run right after this one
+    pub successors: Vec<usize>,
+    /// the first node of the subroutine a `GOSUB` calls, of the handler an `ONERR` registers or of the body of a `FUNCTION`, which return to the successors
+    pub call: Option<usize>,
+}
+
+/// a block with a scope of its own and whether a variable has been declared in it so far
+#[derive(Debug, Clone)]
+struct BlockScope {
+    id: usize,
+    declared: bool,
+}
+
+/// a `GOTO` or `GOSUB` to a line of the same module, with the scopes it jumps from
+#[derive(Debug)]
+struct LocalJump<'a> {
+    node: usize,
+    jump: &'a JumpNode,
+    scopes: Vec<usize>,
+    is_call: bool,
+}
+
+/// the variables a node reads and writes, the statements in the bodies of a compound statement are nodes of their own
+#[derive(Default)]
+struct Effects<'a> {
+    reads: Vec<&'a IdentifierNode>,
+    /// the variables holding a meaningful value after the node
+    assigned: Vec<&'a str>,
+    /// the variables holding the default of their type after the node, declared without a value or reset by `CLEAR` or `CSCOPE`
+    unassigned: Vec<&'a str>,
+}
+
+impl<'a> Effects<'a> {
+    /// `cleared` holds the variables every `CLEAR` and `CSCOPE` resets by the position of its token, only the checker knows their types
+    fn of(statement: &'a Statement, cleared: &'a HashMap<usize, Vec<String>>) -> Self {
+        let mut effects = Self::default();
+        match statement {
+            Statement::VAR_DECL(declaration) => {
+                let name = declaration.variable_name.name.as_str();
+                match &declaration.initial_value {
+                    Some(value) => {
+                        effects.read(value);
+                        effects.assigned.push(name);
+                    }
+                    // records and collections get filled in after they are declared, so their defaults are meaningful
+                    None if declaration
+                        .data_type
+                        .as_ref()
+                        .is_some_and(|t| t.is_primitive()) =>
+                    {
+                        effects.unassigned.push(name);
+                    }
+                    None => effects.assigned.push(name),
+                }
+            }
+            Statement::DIM(dim) => {
+                effects.read(&dim.columns);
+                if let Some(rows) = &dim.rows {
+                    effects.read(rows);
+                }
+                effects.assigned.push(&dim.variable_name.name);
+            }
+            Statement::ASSIGN(assignment) => effects.assign(assignment),
+            Statement::INPUT(input) | Statement::GET(input) => {
+                if let Some(prompt) = &input.prompt {
+                    effects.read(prompt);
+                }
+                effects
+                    .assigned
+                    .extend(input.targets.iter().map(|t| t.name.as_str()));
+            }
+            Statement::READ(read) => effects
+                .assigned
+                .extend(read.targets.iter().map(|t| t.name.as_str())),
+            Statement::SCREEN(screen) => effects
+                .assigned
+                .extend([screen.columns.name.as_str(), screen.rows.name.as_str()]),
+            Statement::FUNC_CALL(call) => call.arguments.iter().for_each(|a| effects.read(a)),
+            Statement::COLLECTION(collection) => {
+                collection.arguments.iter().for_each(|a| effects.read(a))
+            }
+            Statement::PRINT(print) => {
+                for item in &print.items {
+                    if let PrintItem::EXPRESSION(expression) = item {
+                        effects.read(expression);
+                    }
+                }
+            }
+            Statement::CLEAR(clear) | Statement::CSCOPE(clear) => effects.unassigned.extend(
+                cleared
+                    .get(&clear.token.position_flat)
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str),
+            ),
+            Statement::PENUM(penum) => effects.read(&penum.value),
+            Statement::THROW(throw) => effects.read(&throw.code),
+            Statement::STOP(stop) => effects.read(&stop.code),
+            Statement::RETURN(node) => {
+                if let Some(expression) = &node.return_expression {
+                    effects.read(expression);
+                }
+            }
+            Statement::FUNCTION(declaration) => effects
+                .assigned
+                .extend(declaration.parameters.iter().map(|p| p.name.name.as_str())),
+            Statement::IF(if_statement) => effects.read(&if_statement.condition),
+            Statement::MENU(menu) => effects.read(&menu.selector),
+            Statement::SELECT(select) => {
+                effects.read(&select.subject);
+                for condition in select.cases.iter().flat_map(|c| &c.conditions) {
+                    match condition {
+                        CaseCondition::VALUE(value) | CaseCondition::IS(_, value) => {
+                            effects.read(value)
+                        }
+                        CaseCondition::RANGE(low, high) => {
+                            effects.read(low);
+                            effects.read(high);
+                        }
+                    }
+                }
+            }
+            // the item of a FOR EACH and the counter of a FOR are only declared by the loop, which always assigns them.
+            // The condition and the step of a C-style FOR run along with the header, before and after every run of the body
+            Statement::LOOP(node) => match &node.kind {
+                LoopKind::EACH { collection, .. } => effects.read(collection),
+                LoopKind::TO {
+                    start, end, step, ..
+                } => {
+                    effects.read(start);
+                    effects.read(end);
+                    if let Some(step) = step {
+                        effects.read(step);
+                    }
+                }
+                LoopKind::CSTYLE {
+                    start,
+                    condition,
+                    step,
+                    ..
+                } => {
+                    effects.read(start);
+                    effects.read(condition);
+                    effects.assign(step);
+                }
+                LoopKind::WHILE { condition } | LoopKind::UNTIL { condition } => {
+                    effects.read(condition)
+                }
+            },
+            _ => (),
+        }
+        return effects;
+    }
+    fn assign(&mut self, assignment: &'a AssignmentNode) {
+        self.read(&assignment.value);
+        for access in &assignment.path {
+            if let TargetAccess::INDEX(index, _) = access {
+                self.read(index);
+            }
+        }
+        // assigning to a field or an element keeps the rest of the record or collection
+        if assignment.path.is_empty() {
+            self.assigned.push(&assignment.target.name);
+        } else {
+            self.reads.push(&assignment.target);
+        }
+    }
+    fn read(&mut self, expression: &'a Expression) {
+        match expression {
+            Expression::IDENTIFIER(identifier) => self.reads.push(identifier),
+            Expression::CONSTANT(_) => (),
+            Expression::UNARY_OP(node) => self.read(&node.target),
+            Expression::BINARY_OP(node) => {
+                self.read(&node.left);
+                self.read(&node.right);
+            }
+            Expression::FUNC_CALL(call) => call.arguments.iter().for_each(|a| self.read(a)),
+            Expression::POPTIONS(options) => {
+                options.options.iter().for_each(|o| self.read(o));
+                self.read(&options.prompt);
+            }
+            Expression::FIELD(field) => self.read(&field.record),
+            Expression::INDEX(index) => {
+                self.read(&index.collection);
+                self.read(&index.index);
+            }
+            Expression::COLLECTION(collection) => {
+                collection.arguments.iter().for_each(|a| self.read(a))
+            }
+        }
+    }
+}
+
+/// The control flow graph of a module, built over its lines so jumps to them are edges like the ones between statements.
+/// Nodes are in the order of the source, except for the condition of a `DO` which comes after the body
+pub struct FlowGraph<'a> {
+    pub nodes: Vec<FlowNode<'a>>,
+    exit: usize,
+    /// the node of every line by its line number
+    lines: HashMap<usize, usize>,
+    /// the scopes every line is in by its line number, from the outermost one
+    line_scopes: HashMap<usize, Vec<BlockScope>>,
+    jumps: Vec<LocalJump<'a>>,
+    scopes: Vec<BlockScope>,
+    scope_count: usize,
+    /// the `CONTINUE` and `EXIT` nodes of the loops being built, innermost last
+    loops: Vec<(Vec<usize>, Vec<usize>)>,
+    line: Option<&'a LineNode>,
+}
+
+impl<'a> FlowGraph<'a> {
+    pub fn new(root: &'a Statement) -> Self {
+        let mut graph = Self {
+            nodes: Vec::new(),
+            exit: 0,
+            lines: HashMap::new(),
+            line_scopes: HashMap::new(),
+            jumps: Vec::new(),
+            scopes: vec![BlockScope {
+                id: 0,
+                declared: false,
+            }],
+            scope_count: 1,
+            loops: Vec::new(),
+            line: None,
+        };
+        let open = graph.build_statement(root, Vec::new());
+        graph.exit = graph.add(None, &open);
+        // jumps to lines of other modules leave the graph, jumps to undefined lines are reported by the checker
+        for jump in &graph.jumps {
+            let Some(target) = graph.lines.get(&jump.jump.line_number).copied() else {
+                continue;
+            };
+            if jump.is_call {
+                graph.nodes[jump.node].call = Some(target);
+            } else {
+                graph.nodes[jump.node].successors.push(target);
+            }
+        }
+        return graph;
+    }
+    /// add a node run after every node of `open`
+    fn add(&mut self, statement: Option<&'a Statement>, open: &[usize]) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(FlowNode {
+            statement,
+            line: self.line,
+            successors: Vec::new(),
+            call: None,
+        });
+        for from in open {
+            self.nodes[*from].successors.push(index);
+        }
+        return index;
+    }
+    fn build_statements(&mut self, statements: &'a [Statement], open: Vec<usize>) -> Vec<usize> {
+        let mut open = open;
+        for statement in statements {
+            open = self.build_statement(statement, open);
+        }
+        return open;
+    }
+    /// statements in a scope of their own, `declares` is set for blocks that declare a variable before their first statement
+    fn build_block(
+        &mut self,
+        statements: &'a [Statement],
+        open: Vec<usize>,
+        declares: bool,
+    ) -> Vec<usize> {
+        self.scopes.push(BlockScope {
+            id: self.scope_count,
+            declared: declares,
+        });
+        self.scope_count += 1;
+        let open = self.build_statements(statements, open);
+        self.scopes.pop();
+        return open;
+    }
+    /// add the nodes of a statement run after the `open` nodes, returning the nodes the statement after it runs after
+    fn build_statement(&mut self, statement: &'a Statement, open: Vec<usize>) -> Vec<usize> {
+        match statement {
+            Statement::SCOPE(scope) => return self.build_block(&scope.statements, open, false),
+            Statement::LOOP(node) if matches!(node.kind, LoopKind::UNTIL { .. }) => {
+                return self.build_do(statement, &node.body, open);
+            }
+            Statement::LINE(line) => self.line = Some(line),
+            _ => (),
+        }
+        let node = self.add(Some(statement), &open);
+        return match statement {
+            Statement::LINE(line) => {
+                self.lines.insert(line.line_number, node);
+                self.line_scopes
+                    .insert(line.line_number, self.scopes.clone());
+                vec![node]
+            }
+            Statement::VAR_DECL(_) | Statement::DIM(_) => {
+                self.scopes.last_mut().unwrap().declared = true;
+                vec![node]
+            }
+            Statement::GOTO(jump) => {
+                self.jump(node, jump, false);
+                Vec::new()
+            }
+            Statement::GOSUB(jump) => {
+                self.jump(node, jump, true);
+                vec![node]
+            }
+            Statement::RETURN(_) | Statement::END(_) | Statement::STOP(_) => Vec::new(),
+            Statement::CONTINUE(_) | Statement::EXIT(_) => {
+                let is_exit = matches!(statement, Statement::EXIT(_));
+                if let Some((continues, exits)) = self.loops.last_mut() {
+                    if is_exit {
+                        exits.push(node);
+                    } else {
+                        continues.push(node);
+                    }
+                }
+                Vec::new()
+            }
+            Statement::IF(if_statement) => {
+                let mut open = self.build_statement(&if_statement.action, vec![node]);
+                match &if_statement.else_action {
+                    Some(else_action) => open.extend(self.build_statement(else_action, vec![node])),
+                    None => open.push(node),
+                }
+                open
+            }
+            Statement::SELECT(select) => {
+                let mut open = Vec::new();
+                for case in &select.cases {
+                    open.extend(self.build_block(&case.body, vec![node], false));
+                }
+                match &select.else_body {
+                    Some(body) => open.extend(self.build_block(body, vec![node], false)),
+                    None => open.push(node),
+                }
+                open
+            }
+            // a selector out of range throws and continues after the MENU
+            Statement::MENU(menu) => {
+                let mut open = vec![node];
+                for action in &menu.actions {
+                    open.extend(self.build_statement(action, vec![node]));
+                }
+                open
+            }
+            // the handler returns to wherever the error was thrown, so its last statements lead nowhere within the graph
+            Statement::ONERR(handler) => {
+                let start = self.nodes.len();
+                let loops = std::mem::take(&mut self.loops);
+                self.build_statements(&handler.actions, Vec::new());
+                self.loops = loops;
+                if self.nodes.len() > start {
+                    self.nodes[node].call = Some(start);
+                }
+                vec![node]
+            }
+            // a function only runs when it is called, and returns to its callers which aren't followed
+            Statement::FUNCTION(declaration) => {
+                let start = self.nodes.len();
+                let loops = std::mem::take(&mut self.loops);
+                // jumps into the body are errors of their own, see SemanticError::FunctionJump
+                self.build_block(&declaration.body, Vec::new(), false);
+                self.loops = loops;
+                if self.nodes.len() > start {
+                    self.nodes[node].call = Some(start);
+                }
+                vec![node]
+            }
+            // the header of a FOR, FOR EACH or WHILE runs before every run of the body and after the last one
+            Statement::LOOP(loop_node) => {
+                self.loops.push((Vec::new(), Vec::new()));
+                let declares = matches!(
+                    loop_node.kind,
+                    LoopKind::EACH { .. } | LoopKind::TO { .. } | LoopKind::CSTYLE { .. }
+                );
+                let body = self.build_block(&loop_node.body, vec![node], declares);
+                let (continues, exits) = self.loops.pop().unwrap();
+                for from in body.into_iter().chain(continues) {
+                    self.nodes[from].successors.push(node);
+                }
+                let mut open = exits;
+                open.push(node);
+                open
+            }
+            _ => vec![node],
+        };
+    }
+    /// the body of a `DO` runs first, the node of the loop stands for its `LOOP UNTIL` condition
+    fn build_do(
+        &mut self,
+        statement: &'a Statement,
+        body: &'a [Statement],
+        open: Vec<usize>,
+    ) -> Vec<usize> {
+        let start = self.nodes.len();
+        self.loops.push((Vec::new(), Vec::new()));
+        let mut open = self.build_block(body, open, false);
+        let (continues, exits) = self.loops.pop().unwrap();
+        open.extend(continues);
+        let node = self.add(Some(statement), &open);
+        self.nodes[node].successors.push(start);
+        let mut open = exits;
+        open.push(node);
+        return open;
+    }
+    fn jump(&mut self, node: usize, jump: &'a JumpNode, is_call: bool) {
+        if jump.module.is_some() {
+            return;
+        }
+        self.jumps.push(LocalJump {
+            node,
+            jump,
+            scopes: self.scopes.iter().map(|s| s.id).collect(),
+            is_call,
+        });
+    }
+    /// whether every node can be reached from the start of the module, or from any line of an imported module as the modules importing it can jump to all of them
+    fn reachable(&self, imported: bool) -> Vec<bool> {
+        let mut reached = vec![false; self.nodes.len()];
+        let mut stack = vec![0];
+        if imported {
+            stack.extend(self.lines.values());
+        }
+        while let Some(index) = stack.pop() {
+            if reached[index] {
+                continue;
+            }
+            reached[index] = true;
+            let node = &self.nodes[index];
+            stack.extend(node.successors.iter().chain(&node.call));
+        }
+        return reached;
+    }
+    /// the line of the first statement of every run of statements that can't be reached.
+    /// Lines, `DATA`, declarations of enums and records and imports aren't run, so they can't be unreachable
+    pub fn unreachable(&self, imported: bool) -> Vec<&'a LineNode> {
+        let reached = self.reachable(imported);
+        let mut unreachable = Vec::new();
+        let mut previous_reached = true;
+        for (index, node) in self.nodes.iter().enumerate() {
+            let runs = match node.statement {
+                Some(
+                    Statement::LINE(_)
+                    | Statement::DATA(_)
+                    | Statement::ENUM(_)
+                    | Statement::RECORD(_)
+                    | Statement::IMPORT(_),
+                )
+                | None => false,
+                Some(_) => true,
+            };
+            if !runs {
+                continue;
+            }
+            if !reached[index] && previous_reached {
+                unreachable.extend(node.line);
+            }
+            previous_reached = reached[index];
+        }
+        return unreachable;
+    }
+    /// the jumps that enter a scope after a variable was declared in it, skipping the declaration
+    pub fn jumps_into_scopes(&self) -> Vec<&'a JumpNode> {
+        return self
+            .jumps
+            .iter()
+            .filter(|jump| {
+                self.line_scopes
+                    .get(&jump.jump.line_number)
+                    .is_some_and(|scopes| {
+                        scopes
+                            .iter()
+                            .any(|scope| scope.declared && !jump.scopes.contains(&scope.id))
+                    })
+            })
+            .map(|jump| jump.jump)
+            .collect();
+    }
+    /// the first `GOSUB` to every subroutine that can run into the end of the module without a `RET` or `END`
+    pub fn missing_returns(&self) -> Vec<&'a JumpNode> {
+        let mut checked = HashSet::new();
+        let mut missing = Vec::new();
+        for jump in self.jumps.iter().filter(|jump| jump.is_call) {
+            let Some(target) = self.lines.get(&jump.jump.line_number).copied() else {
+                continue;
+            };
+            if !checked.insert(target) {
+                continue;
+            }
+            if self.subroutine(target)[self.exit] {
+                missing.push(jump.jump);
+            }
+        }
+        return missing;
+    }
+    /// the nodes a subroutine starting at `target` can run, a GOSUB within the subroutine returns to it so calls aren't followed
+    fn subroutine(&self, target: usize) -> Vec<bool> {
+        let mut reached = vec![false; self.nodes.len()];
+        let mut stack = vec![target];
+        while let Some(index) = stack.pop() {
+            if reached[index] {
+                continue;
+            }
+            reached[index] = true;
+            stack.extend(&self.nodes[index].successors);
+        }
+        return reached;
+    }
+    /// the reads of variables that haven't been assigned a value on some path from their declaration.
+    /// A variable declared without a value holds the default of its type, which is rarely what a program means to read.
+    /// So does a variable after a `CLEAR` or `CSCOPE` resets it, `cleared` holds the ones they reset by the position of their token
+    pub fn unassigned_reads(
+        &self,
+        cleared: &'a HashMap<usize, Vec<String>>,
+    ) -> Vec<&'a IdentifierNode> {
+        let effects: Vec<Effects> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                node.statement
+                    .map(|statement| Effects::of(statement, cleared))
+                    .unwrap_or_default()
+            })
+            .collect();
+        // the RET nodes of the subroutine every GOSUB calls, the statement after the GOSUB runs after one of them
+        let mut returns: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            if let (Some(Statement::GOSUB(_)), Some(target)) = (node.statement, node.call) {
+                let reached = self.subroutine(target);
+                returns.insert(
+                    index,
+                    (0..self.nodes.len())
+                        .filter(|r| reached[*r])
+                        .filter(|r| matches!(self.nodes[*r].statement, Some(Statement::RETURN(_))))
+                        .collect(),
+                );
+            }
+        }
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate() {
+            for successor in node.successors.iter().chain(&node.call) {
+                predecessors[*successor].push(index);
+            }
+        }
+        // the variables that may still hold their default at the start of every node, until nothing changes
+        let mut unassigned: Vec<HashSet<&str>> = vec![HashSet::new(); self.nodes.len()];
+        let after = |index: usize, unassigned: &[HashSet<&'a str>]| {
+            let mut state = unassigned[index].clone();
+            for name in &effects[index].assigned {
+                state.remove(name);
+            }
+            state.extend(&effects[index].unassigned);
+            return state;
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in 0..self.nodes.len() {
+                let mut state = HashSet::new();
+                for predecessor in &predecessors[index] {
+                    let mut out = after(*predecessor, &unassigned);
+                    // returning from a subroutine, a variable stays unassigned only if it is on the way back
+                    if let Some(rets) = returns.get(predecessor)
+                        && self.nodes[*predecessor].call != Some(index)
+                    {
+                        let returned: HashSet<&str> =
+                            rets.iter().flat_map(|r| after(*r, &unassigned)).collect();
+                        out.retain(|name| returned.contains(name));
+                    }
+                    state.extend(out);
+                }
+                if state != unassigned[index] {
+                    unassigned[index] = state;
+                    changed = true;
+                }
+            }
+        }
+        let mut reads = Vec::new();
+        for (effects, unassigned) in effects.iter().zip(&unassigned) {
+            reads.extend(
+                effects
+                    .reads
+                    .iter()
+                    .filter(|read| unassigned.contains(read.name.as_str())),
+            );
+        }
+        return reads;
+    }
+}
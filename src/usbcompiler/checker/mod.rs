@@ -0,0 +1,3 @@
+pub mod checker;
+pub mod flow;
+pub mod symbols;
@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+
+/// A stack of scopes mapping (uppercased) names to symbols. Every `BEGIN`/`FIN` block pushes a new scope
+pub struct SymbolTable<T> {
+    scopes: Vec<HashMap<String, T>>,
+}
+
+impl<T> SymbolTable<T> {
+    pub fn new() -> Self {
+        return Self {
+            scopes: vec![HashMap::new()],
+        };
+    }
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+    pub fn pop_scope(&mut self) {
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+    }
+    /// declare a symbol in the innermost scope, returning the symbol it replaced if it was already declared in that same scope
+    pub fn declare(&mut self, name: &str, symbol: T) -> Option<T> {
+        return self.scopes.last_mut().unwrap().insert(name.into(), symbol);
+    }
+    /// find a symbol by name, starting at the innermost scope
+    pub fn lookup(&self, name: &str) -> Option<&T> {
+        return self.scopes.iter().rev().find_map(|scope| scope.get(name));
+    }
+    pub fn lookup_mut(&mut self, name: &str) -> Option<&mut T> {
+        return self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name));
+    }
+    /// every symbol of the innermost scope with its name
+    pub fn current_scope(&self) -> impl Iterator<Item = (&String, &T)> {
+        return self.scopes.last().unwrap().iter();
+    }
+    /// every symbol of every scope with its name, including the ones shadowed by an inner scope
+    pub fn all(&self) -> impl Iterator<Item = (&String, &T)> {
+        return self.scopes.iter().flat_map(|scope| scope.iter());
+    }
+    pub fn is_declared_in_current_scope(&self, name: &str) -> bool {
+        return self.scopes.last().unwrap().contains_key(name);
+    }
+    pub fn depth(&self) -> usize {
+        return self.scopes.len();
+    }
+}
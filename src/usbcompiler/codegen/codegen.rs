@@ -0,0 +1,1983 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    stdlib::{builtin_constants, find_function, resolve_function},
+    usbcompiler::{
+        ast::ast::{
+            AssignmentNode, AstNode, BinaryOpKind, BinaryOpNode, CaseCondition, CollectionNode,
+            CollectionOpKind, ConstantNode, DataType, DimNode, EnumDeclarationNode,
+            ErrorHandlerNode, Expression, FunctionCallNode, FunctionDeclarationNode,
+            IfStatementNode, IndexNode, InputNode, JumpNode, LoopKind, LoopNode, MenuNode,
+            OptionsNode, PrintEnumNode, PrintItem, PrintNode, ReadDataNode, ReturnNode, SelectNode,
+            Statement, TargetAccess, UnaryOpKind, UnaryOpNode, VariableDeclarationNode,
+        },
+        checker::symbols::SymbolTable,
+        loader::loader::Module,
+    },
+};
+
+/// where the value behind a name lives at runtime
+#[derive(Debug, Clone)]
+enum Symbol {
+    /// every variable gets its own 8 byte slot in .bss, this keeps jumping in and out of scopes (GOTO, GOSUB) free of any stack bookkeeping
+    Variable {
+        label: String,
+        data_type: DataType,
+    },
+    Constant {
+        data_type: DataType,
+        value: i64,
+    },
+    Enum {
+        label: String,
+        values: Vec<(String, i64)>,
+    },
+    /// an imported module, its labels are prefixed with `prefix`
+    Module {
+        prefix: String,
+    },
+    /// the fields of a record in the order they are laid out in, a nested record is stored inline
+    Record {
+        fields: Vec<(String, DataType)>,
+    },
+    Function {
+        label: String,
+        parameters: Vec<DataType>,
+        return_type: Option<DataType>,
+    },
+    /// a `DEF`, whose expression is generated in place of every call
+    Lambda {
+        parameters: Vec<String>,
+        body: Expression,
+    },
+}
+
+/// the function whose body is being generated
+struct FunctionFrame {
+    /// the label `RET` jumps to, which restores the variables of the caller
+    return_label: String,
+    return_type: Option<DataType>,
+    /// the label and number of slots of every variable of the body, which a call saves on the stack until it returns
+    variables: Vec<(String, usize)>,
+}
+
+const ARGUMENT_REGISTERS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+const FLOAT_ARGUMENT_REGISTERS: [&str; 8] = [
+    "xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7",
+];
+
+/// Generates x86_64 assembly (GNU as, intel syntax) for a checked AST.
+///
+/// Every value is 8 bytes wide and expressions leave their result in `rax`:
+/// integers and booleans as 64 bit integers, floats as the bits of a double and strings as a pointer to a NUL terminated UTF-8 string.
+/// Everything the language can't express in a few instructions is a call into usblib
+pub struct CodeGenerator {
+    text: String,
+    rodata: String,
+    bss: String,
+    symbols: SymbolTable<Symbol>,
+    label_count: usize,
+    strings: HashMap<String, String>,
+    enum_tables: HashSet<String>,
+    /// prefix of the labels of the module being generated, every module has its own line numbers
+    module_prefix: String,
+    /// the label prefix of every module the current module imports, by import path
+    imports: HashMap<String, String>,
+    /// the line of every value in the data pool of the current module, in program order
+    data_lines: Vec<usize>,
+    /// the labels `CONTINUE` and `EXIT` jump to for every loop around the statement being generated, innermost last
+    loops: Vec<(String, String)>,
+    /// whether the statement being generated is part of an `ONERR` handler, where a `GOTO` has to drop the frames of the throw
+    in_handler: bool,
+    function: Option<FunctionFrame>,
+}
+
+impl CodeGenerator {
+    pub fn new() -> Self {
+        return Self {
+            text: String::new(),
+            rodata: String::new(),
+            bss: String::new(),
+            symbols: Self::builtin_symbols(),
+            label_count: 0,
+            strings: HashMap::new(),
+            enum_tables: HashSet::new(),
+            module_prefix: String::new(),
+            imports: HashMap::new(),
+            data_lines: Vec::new(),
+            loops: Vec::new(),
+            in_handler: false,
+            function: None,
+        };
+    }
+    pub fn generate(&mut self, root: &AstNode) -> String {
+        self.generate_module(root);
+        return self.assemble_sections();
+    }
+    /// generate a program made out of multiple modules, the first module is the entry point.
+    /// The code of an imported module only runs when jumped to
+    pub fn generate_program(&mut self, modules: &[Module]) -> String {
+        for (index, module) in modules.iter().enumerate() {
+            self.module_prefix = Self::module_prefix(index);
+            self.imports = module
+                .imports
+                .iter()
+                .map(|(path, imported)| (path.clone(), Self::module_prefix(*imported)))
+                .collect();
+            self.symbols = Self::builtin_symbols();
+            self.generate_module(&module.ast);
+        }
+        return self.assemble_sections();
+    }
+    fn builtin_symbols() -> SymbolTable<Symbol> {
+        let mut symbols = SymbolTable::new();
+        for (name, data_type, value) in builtin_constants() {
+            symbols.declare(name, Symbol::Constant { data_type, value });
+        }
+        return symbols;
+    }
+    fn module_prefix(index: usize) -> String {
+        if index == 0 {
+            return String::new();
+        }
+        return format!("mod{}_", index);
+    }
+    fn generate_module(&mut self, root: &AstNode) {
+        if let AstNode::STATEMENT(statement) = root {
+            self.generate_data_pool(statement);
+        }
+        match root {
+            AstNode::STATEMENT(statement) => self.generate_statement(statement),
+            AstNode::EXPRESSION(expression) => {
+                self.generate_expression(expression);
+            }
+        }
+        // every script has an invisible END at the end of it
+        self.emit("xor edi, edi");
+        self.emit_call("exit");
+    }
+    /// main hands its argc and argv to usblib before running the program. Calls use rbx to realign the stack, so main saves it
+    /// for its caller like any callee saved register and pads the stack back to a 16 byte boundary.
+    /// The stack pointer of that frame is kept for the `GOTO`s leaving an error handler
+    fn assemble_sections(&self) -> String {
+        return format!(
+            "    .intel_syntax noprefix\n    .text\n    .globl main\nmain:\n    push rbp\n    mov rbp, rsp\n    push rbx\n    sub rsp, 8\n    mov qword ptr [rip + .Lmain_frame], rsp\n    call usb_start@PLT\n{}\n    .section .rodata\n{}\n    .bss\n    .align 8\n.Lmain_frame:\n    .zero 8\n{}\n    .section .note.GNU-stack,\"\",@progbits\n",
+            self.text, self.rodata, self.bss
+        );
+    }
+    fn data_label(&self) -> String {
+        return format!(".L{}data", self.module_prefix);
+    }
+    /// DATA values live in a table of (type tag, value) pairs in .rodata, READ advances a pointer into it.
+    /// Every module has its own pool, the values are in program order no matter where the DATA statements are
+    fn generate_data_pool(&mut self, root: &Statement) {
+        let mut pool = Vec::new();
+        Self::collect_data(root, &mut 0, &mut pool);
+        self.data_lines = pool.iter().map(|(line, _)| *line).collect();
+        if pool.is_empty() {
+            return;
+        }
+        let mut entries = Vec::new();
+        for (_, constant) in &pool {
+            let value = match constant.data_type {
+                DataType::FLOAT => format!(
+                    "0x{:x}",
+                    constant.value.parse::<f64>().unwrap_or(0.0).to_bits()
+                ),
+                DataType::STRING => self.string_label(&constant.value),
+                DataType::BOOL => (if constant.value == "TRUE" { "1" } else { "0" }).into(),
+                _ => constant.value.parse::<i64>().unwrap_or(0).to_string(),
+            };
+            entries.push(format!(
+                "{}, {}",
+                Self::type_tag(&constant.data_type),
+                value
+            ));
+        }
+        let label = self.data_label();
+        self.rodata.push_str(&format!(
+            "    .align 8\n{}:\n    .quad {}\n",
+            label,
+            entries.join(", ")
+        ));
+        self.bss
+            .push_str(&format!("{}_pointer:\n    .zero 8\n", label));
+    }
+    /// collect the values of every DATA statement along with the line they are on
+    fn collect_data(
+        statement: &Statement,
+        line: &mut usize,
+        pool: &mut Vec<(usize, ConstantNode)>,
+    ) {
+        match statement {
+            Statement::LINE(node) => *line = node.line_number,
+            Statement::SCOPE(scope) => {
+                for statement in &scope.statements {
+                    Self::collect_data(statement, line, pool);
+                }
+            }
+            Statement::IF(if_statement) => {
+                Self::collect_data(&if_statement.action, line, pool);
+                if let Some(else_action) = &if_statement.else_action {
+                    Self::collect_data(else_action, line, pool);
+                }
+            }
+            Statement::MENU(menu) => {
+                for action in &menu.actions {
+                    Self::collect_data(action, line, pool);
+                }
+            }
+            Statement::LOOP(node) => {
+                for statement in &node.body {
+                    Self::collect_data(statement, line, pool);
+                }
+            }
+            Statement::FUNCTION(function) => {
+                for statement in &function.body {
+                    Self::collect_data(statement, line, pool);
+                }
+            }
+            Statement::ONERR(handler) => {
+                for action in &handler.actions {
+                    Self::collect_data(action, line, pool);
+                }
+            }
+            Statement::SELECT(select) => {
+                let bodies = select
+                    .cases
+                    .iter()
+                    .map(|c| &c.body)
+                    .chain(&select.else_body);
+                for statement in bodies.flatten() {
+                    Self::collect_data(statement, line, pool);
+                }
+            }
+            Statement::DATA(data) => {
+                pool.extend(data.values.iter().map(|value| (*line, value.clone())));
+            }
+            _ => (),
+        }
+    }
+    fn emit(&mut self, instruction: &str) {
+        self.text.push_str("    ");
+        self.text.push_str(instruction);
+        self.text.push('\n');
+    }
+    fn emit_label(&mut self, label: &str) {
+        self.text.push_str(label);
+        self.text.push_str(":\n");
+    }
+    /// call a C function with the stack aligned to 16 bytes. The stack depth isn't known at compile time (GOSUB pushes return addresses),
+    /// so the stack pointer is aligned at runtime and restored from the callee saved `rbx`
+    fn emit_call(&mut self, function: &str) {
+        self.emit("mov rbx, rsp");
+        self.emit("and rsp, -16");
+        self.emit(&format!("call {}@PLT", function));
+        self.emit("mov rsp, rbx");
+    }
+    fn new_label(&mut self, prefix: &str) -> String {
+        self.label_count += 1;
+        return format!(".L{}_{}", prefix, self.label_count);
+    }
+    fn line_label(&self, line_number: usize) -> String {
+        return format!(".L{}line_{}", self.module_prefix, line_number);
+    }
+    /// the label of the line a GOTO or GOSUB jumps to, which is in another module if the jump is aliased
+    fn jump_label(&self, jump: &JumpNode) -> String {
+        let prefix = match &jump.module {
+            Some(module) => match self.symbols.lookup(&module.name) {
+                Some(Symbol::Module { prefix }) => prefix.clone(),
+                _ => self.module_prefix.clone(),
+            },
+            None => self.module_prefix.clone(),
+        };
+        return format!(".L{}line_{}", prefix, jump.line_number);
+    }
+    /// allocate the storage for a new variable
+    fn new_variable(&mut self, name: &str, data_type: &DataType) -> String {
+        let label = self.new_label("var");
+        let size = 8 * self.slots(data_type);
+        if let Some(function) = &mut self.function {
+            function.variables.push((label.clone(), size / 8));
+        }
+        self.bss
+            .push_str(&format!("{}:\n    .zero {} # {}\n", label, size, name));
+        return label;
+    }
+    /// the number of 8 byte slots a value of the type takes up, records hold a slot per (nested) field
+    fn slots(&self, data_type: &DataType) -> usize {
+        return match data_type {
+            DataType::RECORD(name) => match self.symbols.lookup(name) {
+                Some(Symbol::Record { fields }) => fields.iter().map(|(_, t)| self.slots(t)).sum(),
+                _ => 0,
+            },
+            _ => 1,
+        };
+    }
+    /// the offset of a field from the start of its record and the type of the field
+    fn field(&self, record_type: &DataType, field: &str) -> (usize, DataType) {
+        let DataType::RECORD(name) = record_type else {
+            unreachable!("the checker only allows field access on records");
+        };
+        let Some(Symbol::Record { fields }) = self.symbols.lookup(name) else {
+            unreachable!("the checker resolves every record type");
+        };
+        let mut offset = 0;
+        for (field_name, field_type) in fields {
+            if field_name == field {
+                return (offset, field_type.clone());
+            }
+            offset += 8 * self.slots(field_type);
+        }
+        unreachable!("the checker resolves every field");
+    }
+    /// get the label of a string literal, every distinct literal is only stored once
+    fn string_label(&mut self, value: &str) -> String {
+        if let Some(label) = self.strings.get(value) {
+            return label.clone();
+        }
+        let label = self.new_label("str");
+        let bytes: Vec<String> = value
+            .bytes()
+            .chain(std::iter::once(0))
+            .map(|b| b.to_string())
+            .collect();
+        self.rodata
+            .push_str(&format!("{}:\n    .byte {}\n", label, bytes.join(",")));
+        self.strings.insert(value.into(), label.clone());
+        return label;
+    }
+    /// the type tags used by usblib for dynamically typed arguments, see `USB_INT` and co in usblib.h
+    fn type_tag(data_type: &DataType) -> u32 {
+        return match data_type {
+            DataType::INT | DataType::ENUM(_) => 0,
+            DataType::FLOAT => 1,
+            DataType::STRING => 2,
+            DataType::BOOL => 3,
+            DataType::DATE => 4,
+            DataType::RECORD(_) | DataType::LIST(_) | DataType::MAP(_) => {
+                unreachable!(
+                    "the checker doesn't let records or collections be stringified or passed to usblib"
+                )
+            }
+        };
+    }
+    fn generate_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.generate_statement(statement);
+        }
+    }
+    fn generate_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::LINE(line) => self.emit_label(&self.line_label(line.line_number)),
+            Statement::SCOPE(scope) => {
+                self.symbols.push_scope();
+                self.generate_statements(&scope.statements);
+                self.symbols.pop_scope();
+            }
+            Statement::VAR_DECL(declaration) => self.generate_declaration(declaration),
+            Statement::DIM(dim) => self.generate_dim(dim),
+            Statement::DEF(def) => {
+                let parameters = def.parameters.iter().map(|p| p.name.clone()).collect();
+                self.symbols.declare(
+                    &def.name.name,
+                    Symbol::Lambda {
+                        parameters,
+                        body: *def.body.clone(),
+                    },
+                );
+            }
+            Statement::ASSIGN(assignment) => self.generate_assignment(assignment),
+            Statement::ENUM(declaration) => self.generate_enum(declaration),
+            Statement::PENUM(penum) => self.generate_penum(penum),
+            Statement::FUNC_CALL(call) => {
+                self.generate_call(call);
+            }
+            Statement::IF(if_statement) => self.generate_if(if_statement),
+            Statement::GOTO(jump) => {
+                // a handler runs on top of the runtime function that threw, leaving it for good drops everything down to the frame of main
+                if self.in_handler {
+                    self.emit("mov rsp, qword ptr [rip + .Lmain_frame]");
+                }
+                self.emit(&format!("jmp {}", self.jump_label(jump)));
+            }
+            // GOSUB pushes the return address like any call, which is also why calls align the stack at runtime
+            Statement::GOSUB(jump) => self.emit(&format!("call {}", self.jump_label(jump))),
+            Statement::RETURN(ret) => self.generate_return(ret),
+            Statement::END(_) => {
+                self.emit("xor edi, edi");
+                self.emit_call("exit");
+            }
+            Statement::MENU(menu) => self.generate_menu(menu),
+            Statement::SCREEN(screen) => {
+                let mut labels = Vec::new();
+                for target in [&screen.columns, &screen.rows] {
+                    if let Some(Symbol::Variable { label, .. }) = self.symbols.lookup(&target.name)
+                    {
+                        labels.push(label.clone());
+                    }
+                }
+                self.emit(&format!("lea rdi, [rip + {}]", labels[0]));
+                self.emit(&format!("lea rsi, [rip + {}]", labels[1]));
+                self.emit_call("screen");
+            }
+            // the values are already in the data pool
+            Statement::DATA(_) => (),
+            Statement::READ(read) => self.generate_read_data(read),
+            Statement::RESTORE(restore) => {
+                if self.data_lines.is_empty() {
+                    return;
+                }
+                let index = match &restore.target {
+                    Some(target) => self
+                        .data_lines
+                        .iter()
+                        .position(|line| *line >= target.line_number)
+                        .unwrap_or(self.data_lines.len()),
+                    None => 0,
+                };
+                self.emit(&format!(
+                    "mov qword ptr [rip + {}_pointer], {}",
+                    self.data_label(),
+                    index
+                ));
+            }
+            Statement::PRINT(print) => self.generate_print(print),
+            Statement::RECORD(declaration) => {
+                let fields = declaration
+                    .fields
+                    .iter()
+                    .map(|field| (field.name.name.clone(), field.data_type.clone()))
+                    .collect();
+                self.symbols
+                    .declare(&declaration.name.name, Symbol::Record { fields });
+            }
+            Statement::FUNCTION(declaration) => self.generate_function(declaration),
+            Statement::INPUT(input) => self.generate_input(input, false),
+            Statement::GET(get) => self.generate_input(get, true),
+            Statement::ONERR(handler) => self.generate_onerr(handler),
+            Statement::THROW(throw) => {
+                self.generate_expression(&throw.code);
+                self.emit("mov rdi, rax");
+                self.emit_call("usb_throw");
+            }
+            Statement::STOP(stop) => {
+                self.generate_expression(&stop.code);
+                self.emit("mov rdi, rax");
+                self.emit_call("usb_stop");
+            }
+            Statement::COLLECTION(collection) => {
+                self.generate_collection(collection);
+            }
+            Statement::LOOP(node) => self.generate_loop(node),
+            Statement::SELECT(select) => self.generate_select(select),
+            Statement::EXIT(_) => {
+                let (_, end_label) = self.loops.last().unwrap();
+                self.emit(&format!("jmp {}", end_label.clone()));
+            }
+            Statement::CONTINUE(_) => {
+                let (continue_label, _) = self.loops.last().unwrap();
+                self.emit(&format!("jmp {}", continue_label.clone()));
+            }
+            Statement::CLEAR(_) => self.generate_clear(false),
+            Statement::CSCOPE(_) => self.generate_clear(true),
+            Statement::IMPORT(import) => {
+                if let Some(prefix) = self.imports.get(&import.path).cloned() {
+                    self.symbols
+                        .declare(&import.alias.name, Symbol::Module { prefix });
+                }
+            }
+        }
+    }
+    fn generate_declaration(&mut self, declaration: &VariableDeclarationNode) {
+        let data_type = match &declaration.initial_value {
+            Some(value) => {
+                let value_type = self.generate_expression(value);
+                let data_type = declaration.data_type.clone().unwrap_or(value_type.clone());
+                self.emit_conversion(&value_type, &data_type);
+                data_type
+            }
+            None => declaration.data_type.clone().unwrap(),
+        };
+        let name = &declaration.variable_name.name;
+        let label = self.new_variable(name, &data_type);
+        if declaration.initial_value.is_some() {
+            self.emit_store(&label, 0, &data_type);
+        } else if data_type.is_record() {
+            self.emit_record_default(&label, 0, &data_type);
+        } else {
+            self.emit_default(&data_type);
+            self.emit_store(&label, 0, &data_type);
+        }
+        self.symbols
+            .declare(name, Symbol::Variable { label, data_type });
+    }
+    /// reset the variables of every scope, or only the ones of the current scope, to the default of their type,
+    /// in the order they were declared
+    fn generate_clear(&mut self, current_scope: bool) {
+        let symbols: Vec<&Symbol> = if current_scope {
+            self.symbols
+                .current_scope()
+                .map(|(_, symbol)| symbol)
+                .collect()
+        } else {
+            self.symbols.all().map(|(_, symbol)| symbol).collect()
+        };
+        let mut variables: Vec<(String, DataType)> = symbols
+            .into_iter()
+            .filter_map(|symbol| match symbol {
+                Symbol::Variable { label, data_type } => Some((label.clone(), data_type.clone())),
+                _ => None,
+            })
+            .collect();
+        variables
+            .sort_by_key(|(label, _)| label.rsplit('_').next().unwrap().parse::<usize>().unwrap());
+        for (label, data_type) in variables {
+            if data_type.is_record() {
+                self.emit_record_default(&label, 0, &data_type);
+            } else {
+                self.emit_default(&data_type);
+                self.emit_store(&label, 0, &data_type);
+            }
+        }
+    }
+    /// the sizes are evaluated before the variable is declared, the rows of a two dimensional array first
+    fn generate_dim(&mut self, dim: &DimNode) {
+        let mut sizes = Vec::new();
+        for size in [dim.rows.as_ref(), Some(&dim.columns)]
+            .into_iter()
+            .flatten()
+        {
+            self.generate_expression(size);
+            self.emit("mov rdi, rax");
+            self.emit_call("dim_size");
+            let label = self.new_variable("DIM size", &DataType::INT);
+            self.emit_store(&label, 0, &DataType::INT);
+            sizes.push(label);
+        }
+        self.emit_filled_list(&sizes, &dim.element_type);
+        let data_type = dim.data_type();
+        let label = self.new_variable(&dim.variable_name.name, &data_type);
+        self.emit_store(&label, 0, &data_type);
+        self.symbols.declare(
+            &dim.variable_name.name,
+            Symbol::Variable { label, data_type },
+        );
+    }
+    /// a list of as many elements as the first size, a list filled by the other sizes or the default of the element type.
+    /// Every element gets a value of its own, a record element a fresh copy of the default record
+    fn emit_filled_list(&mut self, sizes: &[String], element_type: &DataType) {
+        let list = self.new_variable("DIM list", &DataType::INT);
+        let index = self.new_variable("DIM index", &DataType::INT);
+        let start_label = self.new_label("dim");
+        let end_label = self.new_label("enddim");
+        self.emit_call("list_new");
+        self.emit(&format!("mov {}, rax", Self::variable_operand(&list, 0)));
+        self.emit(&format!("mov {}, 0", Self::variable_operand(&index, 0)));
+        self.emit_label(&start_label);
+        self.emit(&format!("mov rax, {}", Self::variable_operand(&index, 0)));
+        self.emit(&format!(
+            "cmp rax, {}",
+            Self::variable_operand(&sizes[0], 0)
+        ));
+        self.emit(&format!("jge {}", end_label));
+        if sizes.len() > 1 {
+            self.emit_filled_list(&sizes[1..], element_type);
+        } else if element_type.is_record() {
+            let record = self.new_variable("DIM element", element_type);
+            self.emit_record_default(&record, 0, element_type);
+            self.emit(&format!("lea rax, [rip + {}]", record));
+            self.emit_element(element_type, element_type);
+        } else {
+            self.emit_default(element_type);
+        }
+        self.emit("mov rsi, rax");
+        self.emit(&format!("mov rdi, {}", Self::variable_operand(&list, 0)));
+        self.emit_call("list_push");
+        self.emit(&format!("inc {}", Self::variable_operand(&index, 0)));
+        self.emit(&format!("jmp {}", start_label));
+        self.emit_label(&end_label);
+        self.emit(&format!("mov rax, {}", Self::variable_operand(&list, 0)));
+    }
+    /// the operand of the 8 bytes at an offset into a variable
+    fn variable_operand(label: &str, offset: usize) -> String {
+        if offset == 0 {
+            return format!("qword ptr [rip + {}]", label);
+        }
+        return format!("qword ptr [rip + {} + {}]", label, offset);
+    }
+    /// store the value in rax at an offset into a variable, a record is copied slot by slot from the address in rax
+    fn emit_store(&mut self, label: &str, offset: usize, data_type: &DataType) {
+        if !data_type.is_record() {
+            self.emit(&format!(
+                "mov {}, rax",
+                Self::variable_operand(label, offset)
+            ));
+            return;
+        }
+        for slot in 0..self.slots(data_type) {
+            if slot == 0 {
+                self.emit("mov rcx, qword ptr [rax]");
+            } else {
+                self.emit(&format!("mov rcx, qword ptr [rax + {}]", 8 * slot));
+            }
+            self.emit(&format!(
+                "mov {}, rcx",
+                Self::variable_operand(label, offset + 8 * slot)
+            ));
+        }
+    }
+    /// set every field of the record at an offset into a variable to its default value
+    fn emit_record_default(&mut self, label: &str, offset: usize, data_type: &DataType) {
+        let DataType::RECORD(name) = data_type else {
+            return;
+        };
+        let Some(Symbol::Record { fields }) = self.symbols.lookup(name).cloned() else {
+            return;
+        };
+        let mut field_offset = offset;
+        for (_, field_type) in &fields {
+            if field_type.is_record() {
+                self.emit_record_default(label, field_offset, field_type);
+            } else {
+                self.emit_default(field_type);
+                self.emit_store(label, field_offset, field_type);
+            }
+            field_offset += 8 * self.slots(field_type);
+        }
+    }
+    /// load the default value of a type into rax, e.g. 0 for numbers and "" for strings. Enums default to their first key and collections to a new empty one
+    fn emit_default(&mut self, data_type: &DataType) {
+        match data_type {
+            DataType::LIST(_) => self.emit_call("list_new"),
+            DataType::MAP(_) => self.emit_call("map_new"),
+            DataType::STRING => {
+                let label = self.string_label("");
+                self.emit(&format!("lea rax, [rip + {}]", label));
+            }
+            DataType::ENUM(name) => {
+                let value = match self.symbols.lookup(name) {
+                    Some(Symbol::Enum { values, .. }) => values.first().map(|v| v.1).unwrap_or(0),
+                    _ => 0,
+                };
+                self.emit(&format!("mov rax, {}", value));
+            }
+            _ => self.emit("xor eax, eax"),
+        }
+    }
+    /// the type reached by following the fields and indices of an assignment target from a variable
+    fn path_type(&self, mut data_type: DataType, path: &[TargetAccess]) -> DataType {
+        for access in path {
+            data_type = match access {
+                TargetAccess::FIELD(field) => self.field(&data_type, &field.name).1,
+                TargetAccess::INDEX(..) => {
+                    data_type.element_type().cloned().unwrap_or(DataType::INT)
+                }
+            };
+        }
+        return data_type;
+    }
+    /// the fields after the last index of the target are an offset into either the variable or the record element the index reads,
+    /// an element itself is replaced through `list_set` or `map_set`
+    fn generate_assignment(&mut self, assignment: &AssignmentNode) {
+        let value_type = self.generate_expression(&assignment.value);
+        let Some(Symbol::Variable { label, data_type }) =
+            self.symbols.lookup(&assignment.target.name).cloned()
+        else {
+            return;
+        };
+        let split = assignment
+            .path
+            .iter()
+            .rposition(|access| matches!(access, TargetAccess::INDEX(..)))
+            .map_or(0, |index| index + 1);
+        let (container, fields) = assignment.path.split_at(split);
+        let mut target_type = self.path_type(data_type, container);
+        let mut offset = 0;
+        for access in fields {
+            if let TargetAccess::FIELD(field) = access {
+                let (field_offset, field_type) = self.field(&target_type, &field.name);
+                offset += field_offset;
+                target_type = field_type;
+            }
+        }
+        let variable = Expression::IDENTIFIER(assignment.target.clone());
+        let Some((TargetAccess::INDEX(index, _), collection)) = container.split_last() else {
+            self.emit_conversion(&value_type, &target_type);
+            self.emit_store(&label, offset, &target_type);
+            return;
+        };
+        if fields.is_empty() {
+            self.emit_element(&value_type, &target_type);
+            self.emit("push rax");
+            let collection_type =
+                self.generate_expression(&AssignmentNode::access(variable, collection));
+            self.emit("push rax");
+            self.generate_expression(index);
+            self.emit("mov rsi, rax");
+            self.emit("pop rdi");
+            self.emit("pop rdx");
+            if matches!(collection_type, DataType::MAP(_)) {
+                self.emit_call("map_set");
+            } else {
+                self.emit_call("list_set");
+            }
+            return;
+        }
+        // the record element is stored as a pointer, so its field is written in place
+        self.emit_conversion(&value_type, &target_type);
+        self.emit("push rax");
+        self.generate_expression(&AssignmentNode::access(variable, container));
+        self.emit("mov rcx, rax");
+        self.emit("pop rax");
+        if !target_type.is_record() {
+            self.emit(&format!("mov qword ptr [rcx + {}], rax", offset));
+            return;
+        }
+        for slot in 0..self.slots(&target_type) {
+            self.emit(&format!("mov rdx, qword ptr [rax + {}]", 8 * slot));
+            self.emit(&format!("mov qword ptr [rcx + {}], rdx", offset + 8 * slot));
+        }
+    }
+    /// turn the value in rax into an element of a collection or the value of a function, a record is copied onto the heap so it doesn't change along with the variable it came from
+    fn emit_element(&mut self, value_type: &DataType, element_type: &DataType) {
+        self.emit_conversion(value_type, element_type);
+        if element_type.is_record() {
+            self.emit("mov rdi, rax");
+            self.emit(&format!("mov rsi, {}", 8 * self.slots(element_type)));
+            self.emit_call("copy_record");
+        }
+    }
+    /// the collection is evaluated first and kept on the stack while the other arguments are evaluated
+    fn generate_collection(&mut self, collection: &CollectionNode) -> Option<DataType> {
+        let collection_type = self.generate_expression(&collection.arguments[0]);
+        let element_type = collection_type
+            .element_type()
+            .cloned()
+            .unwrap_or(DataType::INT);
+        let is_map = matches!(collection_type, DataType::MAP(_));
+        self.emit("push rax");
+        match collection.operation {
+            CollectionOpKind::PUSH => {
+                let value_type = self.generate_expression(&collection.arguments[1]);
+                self.emit_element(&value_type, &element_type);
+                self.emit("mov rsi, rax");
+                self.emit("pop rdi");
+                self.emit_call("list_push");
+                return None;
+            }
+            CollectionOpKind::INSERT => {
+                self.generate_expression(&collection.arguments[1]);
+                self.emit("push rax");
+                let value_type = self.generate_expression(&collection.arguments[2]);
+                self.emit_element(&value_type, &element_type);
+                self.emit("mov rdx, rax");
+                self.emit("pop rsi");
+                self.emit("pop rdi");
+                self.emit_call("list_insert");
+                return None;
+            }
+            CollectionOpKind::POP => {
+                self.emit("pop rdi");
+                self.emit_call("list_pop");
+                self.emit_missing_element(&element_type);
+                return Some(element_type);
+            }
+            CollectionOpKind::KEYS => {
+                self.emit("pop rdi");
+                self.emit_call("map_keys");
+                return Some(DataType::LIST(Box::new(DataType::STRING)));
+            }
+            CollectionOpKind::REMOVE | CollectionOpKind::HAS => (),
+        }
+        self.generate_expression(&collection.arguments[1]);
+        self.emit("mov rsi, rax");
+        self.emit("pop rdi");
+        if collection.operation == CollectionOpKind::HAS {
+            self.emit_call("map_has");
+            self.emit("movzx eax, al");
+            return Some(DataType::BOOL);
+        }
+        self.emit_call(if is_map { "map_remove" } else { "list_remove" });
+        return None;
+    }
+    fn generate_index(&mut self, node: &IndexNode) -> DataType {
+        let collection_type = self.generate_expression(&node.collection);
+        self.emit("push rax");
+        self.generate_expression(&node.index);
+        self.emit("mov rsi, rax");
+        self.emit("pop rdi");
+        let element_type = collection_type
+            .element_type()
+            .cloned()
+            .unwrap_or(DataType::INT);
+        if matches!(collection_type, DataType::MAP(_)) {
+            self.emit_call("map_get");
+        } else {
+            self.emit_call("list_get");
+        }
+        self.emit_missing_element(&element_type);
+        return element_type;
+    }
+    /// a lookup that threw evaluates to 0 once the ONERR handler returns, which is replaced by the default value of the types stored as pointers
+    fn emit_missing_element(&mut self, element_type: &DataType) {
+        if element_type.is_numeric() || *element_type == DataType::BOOL {
+            return;
+        }
+        let found_label = self.new_label("found");
+        self.emit("test rax, rax");
+        self.emit(&format!("jnz {}", found_label));
+        if element_type.is_record() {
+            let label = self.new_variable("missing element", element_type);
+            self.emit_record_default(&label, 0, element_type);
+            self.emit(&format!("lea rax, [rip + {}]", label));
+        } else {
+            self.emit_default(element_type);
+        }
+        self.emit_label(&found_label);
+    }
+    /// FOR EACH walks a list by an index kept in .bss like any variable, a map is walked through a list of its keys taken before the loop starts.
+    /// The length is read again before every iteration, so elements pushed in the body are visited as well
+    fn generate_loop(&mut self, node: &LoopNode) {
+        let continue_label = self.new_label("continue");
+        let end_label = self.new_label("endloop");
+        match &node.kind {
+            LoopKind::EACH { item, collection } => {
+                self.symbols.push_scope();
+                let collection_type = self.generate_expression(collection);
+                let item_type = match collection_type {
+                    DataType::LIST(element) => *element,
+                    _ => {
+                        self.emit("mov rdi, rax");
+                        self.emit_call("map_keys");
+                        DataType::STRING
+                    }
+                };
+                let list_label = self.new_variable("FOR EACH list", &DataType::INT);
+                let index_label = self.new_variable("FOR EACH index", &DataType::INT);
+                let item_label = self.new_variable(&item.name, &item_type);
+                let start_label = self.new_label("each");
+                self.emit(&format!("mov qword ptr [rip + {}], rax", list_label));
+                self.emit(&format!("mov qword ptr [rip + {}], 0", index_label));
+                self.emit_label(&start_label);
+                self.emit(&format!("mov rax, qword ptr [rip + {}]", list_label));
+                self.emit(&format!("mov rcx, qword ptr [rip + {}]", index_label));
+                // usb_list is laid out as length, capacity, items
+                self.emit("cmp rcx, qword ptr [rax]");
+                self.emit(&format!("jge {}", end_label));
+                self.emit("mov rax, qword ptr [rax + 16]");
+                self.emit("mov rax, qword ptr [rax + rcx * 8]");
+                self.emit_store(&item_label, 0, &item_type);
+                self.symbols.declare(
+                    &item.name,
+                    Symbol::Variable {
+                        label: item_label,
+                        data_type: item_type,
+                    },
+                );
+                self.generate_loop_body(&node.body, &continue_label, &end_label);
+                self.emit_label(&continue_label);
+                self.emit(&format!("inc qword ptr [rip + {}]", index_label));
+                self.emit(&format!("jmp {}", start_label));
+                self.symbols.pop_scope();
+            }
+            LoopKind::TO {
+                counter,
+                start,
+                end,
+                step,
+            } => {
+                self.symbols.push_scope();
+                let mut types = vec![self.generate_expression(start)];
+                self.emit("push rax");
+                types.push(self.generate_expression(end));
+                self.emit("push rax");
+                match step {
+                    Some(step) => types.push(self.generate_expression(step)),
+                    None => {
+                        self.emit("mov eax, 1");
+                        types.push(DataType::INT);
+                    }
+                }
+                self.emit("push rax");
+                let counter_type = if types.contains(&DataType::FLOAT) {
+                    DataType::FLOAT
+                } else {
+                    DataType::INT
+                };
+                let counter_label = self.new_variable(&counter.name, &counter_type);
+                let end_value = self.new_variable("FOR end", &counter_type);
+                let step_value = self.new_variable("FOR step", &counter_type);
+                for (label, value_type) in [&step_value, &end_value, &counter_label]
+                    .into_iter()
+                    .zip(types.iter().rev())
+                {
+                    self.emit("pop rax");
+                    self.emit_conversion(value_type, &counter_type);
+                    self.emit(&format!("mov qword ptr [rip + {}], rax", label));
+                }
+                self.symbols.declare(
+                    &counter.name,
+                    Symbol::Variable {
+                        label: counter_label.clone(),
+                        data_type: counter_type.clone(),
+                    },
+                );
+                // a negative step counts down, the loop ends once the counter is past the end either way.
+                // The sign bit of a float is the one of an integer, so the step is compared as an integer for both
+                let start_label = self.new_label("for");
+                let down_label = self.new_label("down");
+                let body_label = self.new_label("body");
+                self.emit_label(&start_label);
+                self.emit(&format!("mov rax, qword ptr [rip + {}]", counter_label));
+                self.emit(&format!("mov rcx, qword ptr [rip + {}]", end_value));
+                self.emit(&format!("cmp qword ptr [rip + {}], 0", step_value));
+                self.emit(&format!("jl {}", down_label));
+                if counter_type == DataType::FLOAT {
+                    self.emit("movq xmm0, rax");
+                    self.emit("movq xmm1, rcx");
+                    self.emit("ucomisd xmm0, xmm1");
+                    self.emit(&format!("ja {}", end_label));
+                    self.emit(&format!("jmp {}", body_label));
+                    self.emit_label(&down_label);
+                    self.emit("movq xmm0, rax");
+                    self.emit("movq xmm1, rcx");
+                    self.emit("ucomisd xmm0, xmm1");
+                    self.emit(&format!("jb {}", end_label));
+                } else {
+                    self.emit("cmp rax, rcx");
+                    self.emit(&format!("jg {}", end_label));
+                    self.emit(&format!("jmp {}", body_label));
+                    self.emit_label(&down_label);
+                    self.emit("cmp rax, rcx");
+                    self.emit(&format!("jl {}", end_label));
+                }
+                self.emit_label(&body_label);
+                self.generate_loop_body(&node.body, &continue_label, &end_label);
+                self.emit_label(&continue_label);
+                if counter_type == DataType::FLOAT {
+                    self.emit(&format!("movsd xmm0, qword ptr [rip + {}]", counter_label));
+                    self.emit(&format!("addsd xmm0, qword ptr [rip + {}]", step_value));
+                    self.emit(&format!("movsd qword ptr [rip + {}], xmm0", counter_label));
+                } else {
+                    self.emit(&format!("mov rax, qword ptr [rip + {}]", step_value));
+                    self.emit(&format!("add qword ptr [rip + {}], rax", counter_label));
+                }
+                self.emit(&format!("jmp {}", start_label));
+                self.symbols.pop_scope();
+            }
+            // CONTINUE runs the step before the condition is checked again
+            LoopKind::CSTYLE {
+                counter,
+                start,
+                condition,
+                step,
+            } => {
+                self.symbols.push_scope();
+                let counter_type = self.generate_expression(start);
+                let counter_label = self.new_variable(&counter.name, &counter_type);
+                self.emit(&format!("mov qword ptr [rip + {}], rax", counter_label));
+                self.symbols.declare(
+                    &counter.name,
+                    Symbol::Variable {
+                        label: counter_label,
+                        data_type: counter_type,
+                    },
+                );
+                let start_label = self.new_label("for");
+                self.emit_label(&start_label);
+                self.generate_expression(condition);
+                self.emit("test rax, rax");
+                self.emit(&format!("jz {}", end_label));
+                self.generate_loop_body(&node.body, &continue_label, &end_label);
+                self.emit_label(&continue_label);
+                self.generate_assignment(step);
+                self.emit(&format!("jmp {}", start_label));
+                self.symbols.pop_scope();
+            }
+            LoopKind::WHILE { condition } => {
+                self.emit_label(&continue_label);
+                self.generate_expression(condition);
+                self.emit("test rax, rax");
+                self.emit(&format!("jz {}", end_label));
+                self.generate_loop_body(&node.body, &continue_label, &end_label);
+                self.emit(&format!("jmp {}", continue_label));
+            }
+            LoopKind::UNTIL { condition } => {
+                let start_label = self.new_label("do");
+                self.emit_label(&start_label);
+                self.generate_loop_body(&node.body, &continue_label, &end_label);
+                self.emit_label(&continue_label);
+                self.generate_expression(condition);
+                self.emit("test rax, rax");
+                self.emit(&format!("jz {}", start_label));
+            }
+        }
+        self.emit_label(&end_label);
+    }
+    /// the body gets a scope of its own, `CONTINUE` jumps to `continue_label` and `EXIT` to `end_label`
+    fn generate_loop_body(&mut self, body: &[Statement], continue_label: &str, end_label: &str) {
+        self.loops
+            .push((continue_label.to_string(), end_label.to_string()));
+        self.symbols.push_scope();
+        self.generate_statements(body);
+        self.symbols.pop_scope();
+        self.loops.pop();
+    }
+    /// usblib checks the type of every value it reads, integers are promoted to floats like they are in assignments
+    fn generate_read_data(&mut self, read: &ReadDataNode) {
+        for target in &read.targets {
+            let Some(Symbol::Variable { label, data_type }) =
+                self.symbols.lookup(&target.name).cloned()
+            else {
+                continue;
+            };
+            if self.data_lines.is_empty() {
+                // USB_ERR_OUT_OF_DATA, there is nothing to read
+                self.emit("mov edi, 6");
+                self.emit_call("usb_throw");
+                continue;
+            }
+            let pool = self.data_label();
+            self.emit(&format!("lea rdi, [rip + {}]", pool));
+            self.emit(&format!("mov rsi, {}", self.data_lines.len()));
+            self.emit(&format!("lea rdx, [rip + {}_pointer]", pool));
+            self.emit(&format!("mov ecx, {}", Self::type_tag(&data_type)));
+            self.emit_call("read_data");
+            self.emit(&format!("mov qword ptr [rip + {}], rax", label));
+        }
+    }
+    /// a format gets its values as an array of `usb_value`s, evaluated left to right into space reserved up front.
+    /// Otherwise every item is printed before the next one is evaluated, so `TAB` sees the column the previous items left the cursor in
+    fn generate_print(&mut self, print: &PrintNode) {
+        if let Some(format) = &print.format {
+            let size = 16 * print.items.len();
+            if size > 0 {
+                self.emit(&format!("sub rsp, {}", size));
+            }
+            for (index, item) in print.items.iter().enumerate() {
+                if let PrintItem::EXPRESSION(expression) = item {
+                    let data_type = self.generate_expression(expression);
+                    self.emit(&format!(
+                        "mov qword ptr [rsp + {}], {}",
+                        16 * index,
+                        Self::type_tag(&data_type)
+                    ));
+                    self.emit(&format!("mov qword ptr [rsp + {}], rax", 16 * index + 8));
+                }
+            }
+            let label = self.string_label(&format.value);
+            self.emit(&format!("lea rdi, [rip + {}]", label));
+            self.emit("mov rsi, rsp");
+            self.emit(&format!("mov rdx, {}", print.items.len()));
+            self.emit_call("print_format");
+            if size > 0 {
+                self.emit(&format!("add rsp, {}", size));
+            }
+        } else {
+            for item in &print.items {
+                match item {
+                    PrintItem::EXPRESSION(expression) => {
+                        let data_type = self.generate_expression(expression);
+                        self.emit_conversion(&data_type, &DataType::STRING);
+                        self.emit("mov rdi, rax");
+                        self.emit_call("print_text");
+                    }
+                    PrintItem::ZONE => self.emit_call("print_zone"),
+                }
+            }
+        }
+        if print.newline {
+            self.emit_call("print_newline");
+        }
+    }
+    /// the variables are passed as an array of `usb_value`s tagged with their types, which usblib fills in
+    fn generate_input(&mut self, input: &InputNode, get: bool) {
+        let targets: Vec<(String, DataType)> = input
+            .targets
+            .iter()
+            .filter_map(|target| match self.symbols.lookup(&target.name) {
+                Some(Symbol::Variable { label, data_type }) => {
+                    Some((label.clone(), data_type.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        let size = 16 * targets.len();
+        self.emit(&format!("sub rsp, {}", size));
+        for (index, (_, data_type)) in targets.iter().enumerate() {
+            self.emit(&format!(
+                "mov qword ptr [rsp + {}], {}",
+                16 * index,
+                Self::type_tag(data_type)
+            ));
+        }
+        if get {
+            self.emit("mov rdi, rsp");
+            self.emit_call("get_value");
+        } else {
+            match &input.prompt {
+                Some(prompt) => {
+                    let prompt_type = self.generate_expression(prompt);
+                    self.emit_conversion(&prompt_type, &DataType::STRING);
+                }
+                None => self.emit_default(&DataType::STRING),
+            }
+            self.emit("mov rdi, rax");
+            self.emit("mov rsi, rsp");
+            self.emit(&format!("mov rdx, {}", targets.len()));
+            self.emit_call("input_values");
+        }
+        for (index, (label, _)) in targets.iter().enumerate() {
+            self.emit(&format!("mov rax, qword ptr [rsp + {}]", 16 * index + 8));
+            self.emit(&format!("mov qword ptr [rip + {}], rax", label));
+        }
+        self.emit(&format!("add rsp, {}", size));
+    }
+    /// the statements are a subroutine usblib calls when an error is thrown, running the ONERR statement only registers it.
+    /// It is called from C, so rbx which every call clobbers is saved. A `GOTO` out of it never returns to C, so it resets
+    /// the stack to the frame of main instead
+    fn generate_onerr(&mut self, handler: &ErrorHandlerNode) {
+        let handler_label = self.new_label("onerr");
+        let end_label = self.new_label("endonerr");
+        self.emit(&format!("jmp {}", end_label));
+        self.emit_label(&handler_label);
+        self.emit("push rbx");
+        // the handler can't EXIT or CONTINUE the loops it was registered in
+        let loops = std::mem::take(&mut self.loops);
+        let in_handler = std::mem::replace(&mut self.in_handler, true);
+        let function = self.function.take();
+        self.generate_statements(&handler.actions);
+        self.loops = loops;
+        self.in_handler = in_handler;
+        self.function = function;
+        self.emit("pop rbx");
+        self.emit("ret");
+        self.emit_label(&end_label);
+        self.emit(&format!("lea rdi, [rip + {}]", handler_label));
+        self.emit_call("onerr");
+    }
+    /// a function is a subroutine like the ones GOSUB calls and its variables are as static as any other.
+    /// So that it can call itself, a call saves every variable of the body on the stack and restores them before returning.
+    /// The caller pushes the arguments, which are copied into the parameters, a record is passed by its address and copied as well.
+    /// A record is returned as the address of a copy, the variable it was in is restored before the caller gets to it
+    fn generate_function(&mut self, declaration: &FunctionDeclarationNode) {
+        let label = self.new_label("fn");
+        let end_label = self.new_label("endfn");
+        let parameters: Vec<DataType> = declaration
+            .parameters
+            .iter()
+            .map(|p| p.data_type.clone())
+            .collect();
+        self.symbols.declare(
+            &declaration.name.name,
+            Symbol::Function {
+                label: label.clone(),
+                parameters: parameters.clone(),
+                return_type: declaration.return_type.clone(),
+            },
+        );
+        // the body is generated first, the variables to save are only known once it is
+        let text = std::mem::take(&mut self.text);
+        let frame = FunctionFrame {
+            return_label: self.new_label("ret"),
+            return_type: declaration.return_type.clone(),
+            variables: Vec::new(),
+        };
+        let outer = self.function.replace(frame);
+        let loops = std::mem::take(&mut self.loops);
+        let in_handler = std::mem::replace(&mut self.in_handler, false);
+        self.symbols.push_scope();
+        let mut labels = Vec::new();
+        for (parameter, data_type) in declaration.parameters.iter().zip(&parameters) {
+            let label = self.new_variable(&parameter.name.name, data_type);
+            self.symbols.declare(
+                &parameter.name.name,
+                Symbol::Variable {
+                    label: label.clone(),
+                    data_type: data_type.clone(),
+                },
+            );
+            labels.push(label);
+        }
+        self.generate_statements(&declaration.body);
+        // running into the end of the body returns the default value
+        if let Some(return_type) = &declaration.return_type {
+            self.emit_return_default(return_type);
+        }
+        self.symbols.pop_scope();
+        self.loops = loops;
+        self.in_handler = in_handler;
+        let frame = std::mem::replace(&mut self.function, outer).unwrap();
+        let body = std::mem::replace(&mut self.text, text);
+        self.emit(&format!("jmp {}", end_label));
+        self.emit_label(&label);
+        for (variable, slots) in &frame.variables {
+            for slot in 0..*slots {
+                self.emit(&format!(
+                    "push {}",
+                    Self::variable_operand(variable, 8 * slot)
+                ));
+            }
+        }
+        // the arguments are above the saved slots and the return address, the last one on top
+        let saved: usize = frame.variables.iter().map(|(_, slots)| slots).sum();
+        for (index, (label, data_type)) in labels.iter().zip(&parameters).enumerate() {
+            let offset = 8 * (saved + parameters.len() - index);
+            self.emit(&format!("mov rax, qword ptr [rsp + {}]", offset));
+            self.emit_store(label, 0, data_type);
+        }
+        self.text.push_str(&body);
+        self.emit_label(&frame.return_label);
+        for (variable, slots) in frame.variables.iter().rev() {
+            for slot in (0..*slots).rev() {
+                self.emit(&format!(
+                    "pop {}",
+                    Self::variable_operand(variable, 8 * slot)
+                ));
+            }
+        }
+        self.emit("ret");
+        self.emit_label(&end_label);
+    }
+    /// `RET` in a function leaves its value in rax and jumps to the restoring of the caller's variables, anywhere else it returns from a GOSUB
+    fn generate_return(&mut self, ret: &ReturnNode) {
+        let Some(function) = &self.function else {
+            self.emit("ret");
+            return;
+        };
+        let return_label = function.return_label.clone();
+        if let Some(return_type) = function.return_type.clone() {
+            match &ret.return_expression {
+                Some(expression) => {
+                    let value_type = self.generate_expression(expression);
+                    self.emit_element(&value_type, &return_type);
+                }
+                None => self.emit_return_default(&return_type),
+            }
+        }
+        self.emit(&format!("jmp {}", return_label));
+    }
+    /// the default value a function returns in rax, a record is defaulted in a variable of the body and copied
+    fn emit_return_default(&mut self, return_type: &DataType) {
+        if !return_type.is_record() {
+            self.emit_default(return_type);
+            return;
+        }
+        let label = self.new_variable("default return", return_type);
+        self.emit_record_default(&label, 0, return_type);
+        self.emit(&format!("lea rax, [rip + {}]", label));
+        self.emit_element(return_type, return_type);
+    }
+    fn generate_enum(&mut self, declaration: &EnumDeclarationNode) {
+        let values = declaration.values().unwrap_or_default();
+        let enum_type = DataType::ENUM(declaration.name.name.clone());
+        for (name, value) in &values {
+            self.symbols.declare(
+                name,
+                Symbol::Constant {
+                    data_type: enum_type.clone(),
+                    value: *value,
+                },
+            );
+        }
+        let label = self.new_label("enum");
+        self.symbols
+            .declare(&declaration.name.name, Symbol::Enum { label, values });
+    }
+    /// PENUM looks the value up in a table of (value, name) pairs. The table of an enum is only emitted once a PENUM uses it, unused enums don't end up in the binary
+    fn generate_penum(&mut self, penum: &PrintEnumNode) {
+        let value_type = self.generate_expression(&penum.value);
+        if value_type == DataType::FLOAT {
+            // floats are rounded down before being looked up
+            self.emit("movq xmm0, rax");
+            self.emit("roundsd xmm0, xmm0, 1");
+            self.emit("cvttsd2si rax, xmm0");
+        }
+        let (label, values) = match self.symbols.lookup(&penum.enum_name.name).cloned() {
+            Some(Symbol::Enum { label, values }) => (label, values),
+            _ => return,
+        };
+        if self.enum_tables.insert(label.clone()) {
+            let mut table = format!("    .align 8\n{}:\n", label);
+            for (name, value) in &values {
+                let name_label = self.string_label(name);
+                table.push_str(&format!("    .quad {}, {}\n", value, name_label));
+            }
+            self.rodata.push_str(&table);
+        }
+        self.emit("mov rdx, rax");
+        self.emit(&format!("lea rdi, [rip + {}]", label));
+        self.emit(&format!("mov rsi, {}", values.len()));
+        self.emit_call("penum");
+    }
+    fn generate_if(&mut self, if_statement: &IfStatementNode) {
+        let else_label = self.new_label("else");
+        let end_label = self.new_label("endif");
+        self.generate_expression(&if_statement.condition);
+        self.emit("test rax, rax");
+        self.emit(&format!("jz {}", else_label));
+        self.generate_statement(&if_statement.action);
+        self.emit(&format!("jmp {}", end_label));
+        self.emit_label(&else_label);
+        if let Some(else_action) = &if_statement.else_action {
+            self.generate_statement(else_action);
+        }
+        self.emit_label(&end_label);
+    }
+    /// the subject is evaluated once into a hidden variable every condition compares against, see [`CaseCondition::test`].
+    /// Integer cases on constants that are close together jump through a table like MENU instead
+    fn generate_select(&mut self, select: &SelectNode) {
+        let subject_type = self.generate_expression(&select.subject);
+        let subject = select.subject_variable();
+        let subject_label = self.new_variable(&subject.name, &subject_type);
+        self.emit(&format!("mov qword ptr [rip + {}], rax", subject_label));
+        self.symbols.push_scope();
+        self.symbols.declare(
+            &subject.name,
+            Symbol::Variable {
+                label: subject_label,
+                data_type: subject_type.clone(),
+            },
+        );
+        let mut case_labels = Vec::new();
+        for _ in &select.cases {
+            case_labels.push(self.new_label("case"));
+        }
+        let else_label = self.new_label("caseelse");
+        let end_label = self.new_label("endselect");
+        match self.jump_table(select, &subject_type, &case_labels, &else_label) {
+            Some((low, targets)) => {
+                let table_label = self.new_label("casetable");
+                // rax still holds the subject, values below the lowest one wrap around to above the highest one
+                self.emit(&format!("mov rcx, {}", low));
+                self.emit("sub rax, rcx");
+                self.emit(&format!("cmp rax, {}", targets.len() - 1));
+                self.emit(&format!("ja {}", else_label));
+                self.emit(&format!("lea rcx, [rip + {}]", table_label));
+                self.emit("jmp qword ptr [rcx + rax * 8]");
+                self.rodata.push_str(&format!(
+                    "    .align 8\n{}:\n    .quad {}\n",
+                    table_label,
+                    targets.join(", ")
+                ));
+            }
+            None => {
+                let subject = Expression::IDENTIFIER(subject);
+                for (case, label) in select.cases.iter().zip(&case_labels) {
+                    for condition in &case.conditions {
+                        self.generate_expression(&condition.test(&subject));
+                        self.emit("test rax, rax");
+                        self.emit(&format!("jnz {}", label));
+                    }
+                }
+                self.emit(&format!("jmp {}", else_label));
+            }
+        }
+        for (case, label) in select.cases.iter().zip(&case_labels) {
+            self.emit_label(label);
+            self.symbols.push_scope();
+            self.generate_statements(&case.body);
+            self.symbols.pop_scope();
+            self.emit(&format!("jmp {}", end_label));
+        }
+        self.emit_label(&else_label);
+        if let Some(body) = &select.else_body {
+            self.symbols.push_scope();
+            self.generate_statements(body);
+            self.symbols.pop_scope();
+        }
+        self.emit_label(&end_label);
+        self.symbols.pop_scope();
+    }
+    /// the lowest value and the label to jump to for every value from there on, if every condition is an integer constant or a range of them.
+    /// A handful of comparisons beat a table, and a table mostly jumping to the else label isn't worth its space
+    fn jump_table(
+        &self,
+        select: &SelectNode,
+        subject_type: &DataType,
+        case_labels: &[String],
+        else_label: &str,
+    ) -> Option<(i64, Vec<String>)> {
+        const MIN_CONDITIONS: usize = 4;
+        const MAX_VALUES: i64 = 1024;
+        if !subject_type.is_integer() {
+            return None;
+        }
+        let mut ranges = Vec::new();
+        for (case, label) in select.cases.iter().zip(case_labels) {
+            for condition in &case.conditions {
+                let (low, high) = match condition {
+                    CaseCondition::VALUE(value) => {
+                        let value = self.constant_value(value)?;
+                        (value, value)
+                    }
+                    CaseCondition::RANGE(low, high) => {
+                        (self.constant_value(low)?, self.constant_value(high)?)
+                    }
+                    CaseCondition::IS(..) => return None,
+                };
+                if low <= high {
+                    ranges.push((low, high, label));
+                }
+            }
+        }
+        let low = ranges.iter().map(|r| r.0).min()?;
+        let high = ranges.iter().map(|r| r.1).max()?;
+        let count = high.checked_sub(low)?.checked_add(1)?;
+        let covered: i64 = ranges.iter().map(|r| r.1 - r.0 + 1).sum();
+        if ranges.len() < MIN_CONDITIONS || count > MAX_VALUES || count > 2 * covered {
+            return None;
+        }
+        let mut targets = vec![else_label.to_string(); count as usize];
+        // the first matching case wins, so earlier ranges overwrite later ones
+        for (range_low, range_high, label) in ranges.into_iter().rev() {
+            for value in range_low..=range_high {
+                targets[(value - low) as usize] = label.clone();
+            }
+        }
+        return Some((low, targets));
+    }
+    /// the value of an integer literal or a named constant like an enum key
+    fn constant_value(&self, expression: &Expression) -> Option<i64> {
+        return match expression {
+            Expression::CONSTANT(constant) if constant.data_type == DataType::INT => {
+                constant.value.parse().ok()
+            }
+            Expression::IDENTIFIER(identifier) => match self.symbols.lookup(&identifier.name) {
+                Some(Symbol::Constant { data_type, value }) if data_type.is_integer() => {
+                    Some(*value)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+    }
+    /// MENU jumps through a table holding a label per statement, a `GOTO` statement is stored as the line it jumps to.
+    /// usblib validates the choice and turns it into an index into that table
+    fn generate_menu(&mut self, menu: &MenuNode) {
+        let table_label = self.new_label("menu");
+        let end_label = self.new_label("endmenu");
+        self.generate_expression(&menu.selector);
+        self.emit("mov rdi, rax");
+        self.emit(&format!("mov rsi, {}", menu.actions.len()));
+        self.emit_call("menu");
+        self.emit("test rax, rax");
+        self.emit(&format!("js {}", end_label));
+        self.emit(&format!("lea rcx, [rip + {}]", table_label));
+        self.emit("jmp qword ptr [rcx + rax * 8]");
+
+        let mut targets = Vec::new();
+        for action in &menu.actions {
+            if let Statement::GOTO(jump) = action
+                && !self.in_handler
+            {
+                targets.push(self.jump_label(jump));
+                continue;
+            }
+            let label = self.new_label("menuitem");
+            self.emit_label(&label);
+            self.generate_statement(action);
+            self.emit(&format!("jmp {}", end_label));
+            targets.push(label);
+        }
+        self.emit_label(&end_label);
+        self.rodata.push_str(&format!(
+            "    .align 8\n{}:\n    .quad {}\n",
+            table_label,
+            targets.join(", ")
+        ));
+    }
+    /// the options are stringified into an array on the stack which is passed to usblib along with its length
+    fn generate_poptions(&mut self, poptions: &OptionsNode) -> DataType {
+        let size = 8 * poptions.options.len();
+        self.emit(&format!("sub rsp, {}", size));
+        for (index, option) in poptions.options.iter().enumerate() {
+            let option_type = self.generate_expression(option);
+            self.emit_conversion(&option_type, &DataType::STRING);
+            self.emit(&format!("mov qword ptr [rsp + {}], rax", 8 * index));
+        }
+        let prompt_type = self.generate_expression(&poptions.prompt);
+        self.emit_conversion(&prompt_type, &DataType::STRING);
+        self.emit("mov rdx, rax");
+        self.emit("mov rdi, rsp");
+        self.emit(&format!("mov rsi, {}", poptions.options.len()));
+        self.emit_call("poptions");
+        self.emit(&format!("add rsp, {}", size));
+        return DataType::INT;
+    }
+    /// convert the value in rax from one type to another. Only the implicit conversions the checker allows are handled
+    fn emit_conversion(&mut self, from: &DataType, to: &DataType) {
+        if from == to || (from.is_integer() && to.is_integer()) {
+            return;
+        }
+        match to {
+            DataType::FLOAT if from.is_integer() => {
+                self.emit("cvtsi2sd xmm0, rax");
+                self.emit("movq rax, xmm0");
+            }
+            DataType::STRING => {
+                self.emit("push rax");
+                self.emit("mov rsi, rsp");
+                self.emit(&format!("mov edi, {}", Self::type_tag(from)));
+                self.emit_call("str");
+                self.emit("add rsp, 8");
+            }
+            _ => (),
+        }
+    }
+    /// generate the code for an expression, leaving its value in rax and returning its type
+    fn generate_expression(&mut self, expression: &Expression) -> DataType {
+        return match expression {
+            Expression::CONSTANT(constant) => self.generate_constant(constant),
+            Expression::IDENTIFIER(identifier) => {
+                match self.symbols.lookup(&identifier.name).cloned() {
+                    // a record evaluates to its address
+                    Some(Symbol::Variable { label, data_type }) if data_type.is_record() => {
+                        self.emit(&format!("lea rax, [rip + {}]", label));
+                        data_type
+                    }
+                    Some(Symbol::Variable { label, data_type }) => {
+                        self.emit(&format!("mov rax, qword ptr [rip + {}]", label));
+                        data_type
+                    }
+                    Some(Symbol::Constant { data_type, value }) => {
+                        self.emit(&format!("mov rax, {}", value));
+                        data_type
+                    }
+                    _ => DataType::INT,
+                }
+            }
+            Expression::UNARY_OP(unary) => self.generate_unary(unary),
+            Expression::BINARY_OP(binary) => self.generate_binary(binary),
+            Expression::FUNC_CALL(call) => self.generate_call(call).unwrap_or(DataType::INT),
+            Expression::POPTIONS(poptions) => self.generate_poptions(poptions),
+            Expression::INDEX(node) => self.generate_index(node),
+            Expression::COLLECTION(collection) => self
+                .generate_collection(collection)
+                .unwrap_or(DataType::INT),
+            Expression::FIELD(access) => {
+                let record_type = self.generate_expression(&access.record);
+                let (offset, field_type) = self.field(&record_type, &access.field.name);
+                if field_type.is_record() {
+                    self.emit(&format!("lea rax, [rax + {}]", offset));
+                } else {
+                    self.emit(&format!("mov rax, qword ptr [rax + {}]", offset));
+                }
+                field_type
+            }
+        };
+    }
+    fn generate_constant(&mut self, constant: &ConstantNode) -> DataType {
+        match constant.data_type {
+            DataType::FLOAT => {
+                let bits = constant.value.parse::<f64>().unwrap_or(0.0).to_bits();
+                self.emit(&format!("mov rax, 0x{:x}", bits));
+            }
+            DataType::STRING => {
+                let label = self.string_label(&constant.value);
+                self.emit(&format!("lea rax, [rip + {}]", label));
+            }
+            DataType::BOOL => {
+                let value = if constant.value == "TRUE" { 1 } else { 0 };
+                self.emit(&format!("mov rax, {}", value));
+            }
+            _ => {
+                let value = constant.value.parse::<i64>().unwrap_or(0);
+                self.emit(&format!("mov rax, {}", value));
+            }
+        }
+        return constant.data_type.clone();
+    }
+    fn generate_unary(&mut self, unary: &UnaryOpNode) -> DataType {
+        let target_type = self.generate_expression(&unary.target);
+        let result_type = unary
+            .operation
+            .result_type(&target_type)
+            .unwrap_or(target_type.clone());
+        match (&unary.operation, &target_type) {
+            (UnaryOpKind::NEG, DataType::FLOAT) => self.emit("btc rax, 63"),
+            (UnaryOpKind::NEG, _) => self.emit("neg rax"),
+            (UnaryOpKind::NOT, _) => self.emit("xor rax, 1"),
+        }
+        return result_type;
+    }
+    /// evaluates both operands, converts them to a common type and leaves the left operand in rax and the right one in rcx before applying the operation
+    fn generate_binary(&mut self, binary: &BinaryOpNode) -> DataType {
+        let left_type = self.generate_expression(&binary.left);
+        self.emit("push rax");
+        let right_type = self.generate_expression(&binary.right);
+        let result_type = binary
+            .operation
+            .result_type(&left_type, &right_type)
+            .unwrap_or(DataType::INT);
+
+        let operand_type =
+            if binary.operation == BinaryOpKind::ADD && result_type == DataType::STRING {
+                DataType::STRING
+            } else if left_type == DataType::FLOAT
+                || right_type == DataType::FLOAT
+                || binary.operation == BinaryOpKind::DIV
+            {
+                DataType::FLOAT
+            } else if left_type.is_integer() {
+                DataType::INT
+            } else {
+                left_type.clone()
+            };
+        self.emit_conversion(&right_type, &operand_type);
+        self.emit("push rax");
+        self.emit("mov rax, qword ptr [rsp + 8]");
+        self.emit_conversion(&left_type, &operand_type);
+        self.emit("pop rcx");
+        self.emit("add rsp, 8");
+
+        match operand_type {
+            DataType::FLOAT => self.emit_float_operation(&binary.operation),
+            DataType::STRING => self.emit_string_operation(&binary.operation),
+            _ => self.emit_integer_operation(&binary.operation),
+        }
+        return result_type;
+    }
+    fn emit_integer_operation(&mut self, operation: &BinaryOpKind) {
+        let instruction = match operation {
+            BinaryOpKind::ADD => "add rax, rcx",
+            BinaryOpKind::SUB => "sub rax, rcx",
+            BinaryOpKind::MUL => "imul rax, rcx",
+            BinaryOpKind::AND | BinaryOpKind::BAND => "and rax, rcx",
+            BinaryOpKind::OR | BinaryOpKind::BOR => "or rax, rcx",
+            BinaryOpKind::BXOR => "xor rax, rcx",
+            // the shift count isn't masked to 6 bits like it is by the instruction, a count past 63 or a negative one shifts every bit out
+            BinaryOpKind::SHL => {
+                self.emit("xor edx, edx");
+                self.emit("shl rax, cl");
+                self.emit("cmp rcx, 63");
+                "cmova rax, rdx"
+            }
+            // the sign is kept, like dividing by a power of 2 rounding down. Shifting by 63 leaves only the sign
+            BinaryOpKind::SHR => {
+                self.emit("mov edx, 63");
+                self.emit("cmp rcx, rdx");
+                self.emit("cmova rcx, rdx");
+                "sar rax, cl"
+            }
+            BinaryOpKind::IDIV | BinaryOpKind::MOD => {
+                self.emit_division(operation);
+                return;
+            }
+            _ => {
+                let set = match operation {
+                    BinaryOpKind::EQ => "sete",
+                    BinaryOpKind::NEQ => "setne",
+                    BinaryOpKind::LT => "setl",
+                    BinaryOpKind::GT => "setg",
+                    BinaryOpKind::LTE => "setle",
+                    _ => "setge",
+                };
+                self.emit("cmp rax, rcx");
+                self.emit(&format!("{} al", set));
+                "movzx eax, al"
+            }
+        };
+        self.emit(instruction);
+    }
+    /// `idiv` leaves the quotient in rax and the remainder, which has the sign of the dividend, in rdx.
+    /// Dividing by zero throws USB_ERR_DIVISION_BY_ZERO and evaluates to 0 once the handler returns.
+    /// `idiv` faults on the smallest integer divided by -1, so -1 is handled on its own: `\` negates, wrapping like `-` does, and `MOD` is 0
+    fn emit_division(&mut self, operation: &BinaryOpKind) {
+        let divisor_label = self.new_label("divisor");
+        let negative_label = self.new_label("negative");
+        let end_label = self.new_label("enddivision");
+        self.emit("test rcx, rcx");
+        self.emit(&format!("jnz {}", divisor_label));
+        self.emit("mov edi, 11");
+        self.emit_call("usb_throw");
+        self.emit("xor eax, eax");
+        self.emit("mov ecx, 1");
+        self.emit_label(&divisor_label);
+        self.emit("cmp rcx, -1");
+        self.emit(&format!("je {}", negative_label));
+        self.emit("cqo");
+        self.emit("idiv rcx");
+        if *operation == BinaryOpKind::MOD {
+            self.emit("mov rax, rdx");
+        }
+        self.emit(&format!("jmp {}", end_label));
+        self.emit_label(&negative_label);
+        if *operation == BinaryOpKind::MOD {
+            self.emit("xor eax, eax");
+        } else {
+            self.emit("neg rax");
+        }
+        self.emit_label(&end_label);
+    }
+    fn emit_float_operation(&mut self, operation: &BinaryOpKind) {
+        self.emit("movq xmm0, rax");
+        self.emit("movq xmm1, rcx");
+        let instruction = match operation {
+            BinaryOpKind::ADD => "addsd",
+            BinaryOpKind::SUB => "subsd",
+            BinaryOpKind::MUL => "mulsd",
+            BinaryOpKind::DIV => "divsd",
+            _ => {
+                let set = match operation {
+                    BinaryOpKind::EQ => "sete",
+                    BinaryOpKind::NEQ => "setne",
+                    BinaryOpKind::LT => "setb",
+                    BinaryOpKind::GT => "seta",
+                    BinaryOpKind::LTE => "setbe",
+                    _ => "setae",
+                };
+                self.emit("ucomisd xmm0, xmm1");
+                self.emit(&format!("{} al", set));
+                self.emit("movzx eax, al");
+                return;
+            }
+        };
+        self.emit(&format!("{} xmm0, xmm1", instruction));
+        self.emit("movq rax, xmm0");
+    }
+    fn emit_string_operation(&mut self, operation: &BinaryOpKind) {
+        self.emit("mov rdi, rax");
+        self.emit("mov rsi, rcx");
+        if *operation == BinaryOpKind::ADD {
+            self.emit_call("str_concat");
+            return;
+        }
+        self.emit_call("str_compare");
+        self.emit("xor ecx, ecx");
+        self.emit_integer_operation(operation);
+    }
+    /// a `DEF` is expanded at the call, the arguments are stored in variables of their own types that its expression reads as the parameters
+    fn generate_lambda_call(
+        &mut self,
+        call: &FunctionCallNode,
+        parameters: &[String],
+        body: &Expression,
+    ) -> DataType {
+        let mut variables = Vec::new();
+        for (argument, parameter) in call.arguments.iter().zip(parameters) {
+            let data_type = self.generate_expression(argument);
+            let label = self.new_variable(parameter, &data_type);
+            self.emit_store(&label, 0, &data_type);
+            variables.push((parameter, label, data_type));
+        }
+        self.symbols.push_scope();
+        for (parameter, label, data_type) in variables {
+            self.symbols
+                .declare(parameter, Symbol::Variable { label, data_type });
+        }
+        let data_type = self.generate_expression(body);
+        self.symbols.pop_scope();
+        return data_type;
+    }
+    /// call a stdlib function. Arguments are evaluated left to right onto the stack and then moved into their System V registers.
+    /// The overload is picked once the argument types are known, so arguments are converted to their parameter types in place.
+    /// Dynamically typed parameters are passed as a type tag and a pointer to the value on the stack
+    fn generate_call(&mut self, call: &FunctionCallNode) -> Option<DataType> {
+        // a FUNCTION takes its arguments on the stack and drops them once it returns
+        if let Some(Symbol::Function {
+            label,
+            parameters,
+            return_type,
+        }) = self.symbols.lookup(&call.name).cloned()
+        {
+            for (argument, parameter) in call.arguments.iter().zip(&parameters) {
+                let argument_type = self.generate_expression(argument);
+                self.emit_conversion(&argument_type, parameter);
+                self.emit("push rax");
+            }
+            self.emit(&format!("call {}", label));
+            if !parameters.is_empty() {
+                self.emit(&format!("add rsp, {}", 8 * parameters.len()));
+            }
+            return return_type;
+        }
+        if let Some(Symbol::Lambda { parameters, body }) = self.symbols.lookup(&call.name).cloned()
+        {
+            return Some(self.generate_lambda_call(call, &parameters, &body));
+        }
+        find_function(&call.name)?;
+        let mut argument_types = Vec::new();
+        for argument in &call.arguments {
+            argument_types.push(self.generate_expression(argument));
+            self.emit("push rax");
+        }
+        // LEN of a list or map is the length both of them start with, see usb_list and usb_map
+        if call.name == "LEN" && matches!(&argument_types[..], [t] if t.is_collection()) {
+            self.emit("pop rax");
+            self.emit("mov rax, qword ptr [rax]");
+            return Some(DataType::INT);
+        }
+        let known_types: Vec<Option<DataType>> = argument_types.iter().cloned().map(Some).collect();
+        let function = resolve_function(&call.name, &known_types)?;
+        let parameters = function.parameters();
+        for parameter in parameters.iter().skip(call.arguments.len()) {
+            let argument_type = match parameter {
+                // a missing date is the current one e.g. a bare `TIME`
+                Some(DataType::DATE) => {
+                    self.emit_call("date");
+                    DataType::DATE
+                }
+                // USB_MISSING, a left out optional integer
+                Some(p) if p.is_integer() => {
+                    self.emit(&format!("mov rax, {}", i64::MIN));
+                    DataType::INT
+                }
+                _ => {
+                    self.emit_default(&DataType::STRING);
+                    DataType::STRING
+                }
+            };
+            argument_types.push(argument_type);
+            self.emit("push rax");
+        }
+        for (index, parameter) in parameters.iter().enumerate() {
+            let (Some(parameter), Some(argument_type)) = (parameter, argument_types.get(index))
+            else {
+                continue;
+            };
+            if argument_type == parameter || (argument_type.is_integer() && parameter.is_integer())
+            {
+                continue;
+            }
+            let offset = 8 * (argument_types.len() - 1 - index);
+            let argument_type = argument_type.clone();
+            self.emit(&format!("mov rax, qword ptr [rsp + {}]", offset));
+            self.emit_conversion(&argument_type, parameter);
+            self.emit(&format!("mov qword ptr [rsp + {}], rax", offset));
+        }
+        let mut integer_register = 0;
+        let mut float_register = 0;
+        for (index, parameter) in parameters.iter().enumerate() {
+            let offset = 8 * (argument_types.len() - 1 - index);
+            match parameter {
+                Some(DataType::FLOAT) => {
+                    let register = FLOAT_ARGUMENT_REGISTERS[float_register];
+                    self.emit(&format!("movsd {}, qword ptr [rsp + {}]", register, offset));
+                    float_register += 1;
+                }
+                Some(_) => {
+                    let register = ARGUMENT_REGISTERS[integer_register];
+                    self.emit(&format!("mov {}, qword ptr [rsp + {}]", register, offset));
+                    integer_register += 1;
+                }
+                None => {
+                    let tag_register = ARGUMENT_REGISTERS[integer_register];
+                    let value_register = ARGUMENT_REGISTERS[integer_register + 1];
+                    let tag = Self::type_tag(&argument_types[index]);
+                    self.emit(&format!("mov {}, {}", tag_register, tag));
+                    self.emit(&format!("lea {}, [rsp + {}]", value_register, offset));
+                    integer_register += 2;
+                }
+            }
+        }
+        self.emit_call(function.name);
+        if !argument_types.is_empty() {
+            self.emit(&format!("add rsp, {}", 8 * argument_types.len()));
+        }
+
+        let return_type = function.return_data_type();
+        match return_type {
+            Some(DataType::FLOAT) => self.emit("movq rax, xmm0"),
+            Some(DataType::BOOL) => self.emit("movzx eax, al"),
+            _ => (),
+        }
+        return return_type;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usbcompiler::{
+        parser::parser::{EvaluationContext, Parser},
+        tokenizer::lexer::Lexer,
+    };
+
+    fn generate_script(input: &str) -> String {
+        let tokens = Lexer::new(input.into()).tokenize().unwrap();
+        let ast = Parser::new(tokens, EvaluationContext::FileLevel)
+            .parse()
+            .unwrap();
+        return CodeGenerator::new().generate(&ast);
+    }
+    #[test]
+    fn test_line_labels() {
+        let assembly = generate_script("10 PRINT 1\nPRINT 2");
+        assert!(assembly.contains(".Lline_10:"));
+        assert!(assembly.contains(".Lline_11:"));
+    }
+    #[test]
+    fn test_enum_table_only_emitted_when_used() {
+        let assembly = generate_script("ENUM direction = NORTH, EAST\nPRINT EAST");
+        assert!(!assembly.contains(".quad"));
+        assert!(!assembly.contains("penum"));
+
+        let assembly =
+            generate_script("ENUM direction = NORTH, EAST\nENUM unused = A\nPENUM direction, EAST");
+        assert_eq!(assembly.matches(".quad").count(), 2);
+        // the table is never written to, it sits with the other constants
+        let rodata = assembly.find(".section .rodata").unwrap();
+        assert!(assembly.find(".quad").unwrap() > rodata);
+        assert!(!assembly.contains("    .data\n"));
+        assert!(assembly.contains("call penum@PLT"));
+    }
+    #[test]
+    fn test_missing_optional_argument() {
+        let assembly = generate_script("PRINT MID$(\"abc\", 2)");
+        assert!(assembly.contains("mov rax, -9223372036854775808"));
+        assert!(assembly.contains("call mid@PLT"));
+    }
+    #[test]
+    fn test_float_overload_promotes_integers() {
+        let assembly = generate_script("PRINT MIN(3, 1.5)\nPRINT MIN(3, 1)");
+        assert!(assembly.contains("mov rax, qword ptr [rsp + 8]\n    cvtsi2sd xmm0, rax"));
+        assert!(assembly.contains("call min_f@PLT"));
+        assert!(assembly.contains("call min@PLT"));
+    }
+    #[test]
+    fn test_print_format_values() {
+        let assembly = generate_script("PRINT \"{} {:.2}\", \"a\", 1.5; PRINT 1; TAB(5),");
+        assert!(assembly.contains("sub rsp, 32"));
+        assert!(
+            assembly.contains("mov qword ptr [rsp + 16], 1\n    mov qword ptr [rsp + 24], rax")
+        );
+        assert!(assembly.contains("mov rdx, 2\n"));
+        assert!(assembly.contains("call print_format@PLT"));
+        assert!(assembly.contains("call tab@PLT"));
+        assert!(assembly.contains("call print_zone@PLT"));
+        assert_eq!(assembly.matches("call print_newline@PLT").count(), 1);
+    }
+    #[test]
+    fn test_arguments_are_captured() {
+        let assembly = generate_script("PRINT ARGV$(ARGC)");
+        // main still has argc and argv in rdi and rsi
+        assert!(
+            assembly.contains(
+                "main:\n    push rbp\n    mov rbp, rsp\n    push rbx\n    sub rsp, 8\n    mov qword ptr [rip + .Lmain_frame], rsp\n    call usb_start@PLT\n"
+            )
+        );
+        assert!(assembly.contains("call argc@PLT"));
+        assert!(assembly.contains("call argv@PLT"));
+    }
+
+    #[test]
+    fn test_exec_takes_a_list() {
+        let assembly = generate_script("LET args AS LIST OF STRING\nPRINT EXEC args");
+        // the list is passed as its pointer, not as a tagged value
+        assert!(assembly.contains("mov rdi, qword ptr [rsp + 0]\n"));
+        assert!(assembly.contains("call exec@PLT"));
+    }
+
+    #[test]
+    fn test_select_jump_table() {
+        // what the cases run is covered by the compiler tests, this is about how the case is found
+        let assembly =
+            generate_script("SELECT CASE 3\nCASE 1\nCASE 2, 4\nCASE 5 TO 6\nCASE ELSE\nEND SELECT");
+        assert!(assembly.contains(".Lcasetable_"));
+        let assembly = generate_script("SELECT CASE 3\nCASE 1, 100\nEND SELECT");
+        // too sparse for a table
+        assert!(!assembly.contains("casetable"));
+    }
+}
@@ -1,57 +1,575 @@
-use std::{fs::read_to_string, path::PathBuf};
+use std::{
+    fs::{self, read_to_string},
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+};
+
+use miniserde::{Deserialize, json};
 
 use crate::usbcompiler::{
-    errors::error::CompilerError,
-    parser::parser::{EvaluationContext, Parser},
-    tokenizer::lexer::{Lexer, Token},
+    checker::checker::Checker,
+    codegen::codegen::CodeGenerator,
+    errors::error::{CompilerError, CompilerWarning, MiscellaneousError},
+    loader::loader::ModuleLoader,
 };
+
+const USBLIB_SOURCE: &str = include_str!("../../stdlib/usblib.c");
+const USBLIB_HEADER: &str = include_str!("../../stdlib/usblib.h");
+
+/// the `<project>.usbp.json` config file of a USB project
+#[derive(Deserialize)]
+struct ProjectConfig {
+    entry_point: String,
+}
+
+/// the most colors the runtime may use, set with `--color`. The runtime still downgrades to what the terminal supports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorDepth {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    pub fn bits(&self) -> u8 {
+        return match self {
+            ColorDepth::Ansi16 => 4,
+            ColorDepth::Ansi256 => 8,
+            ColorDepth::TrueColor => 24,
+        };
+    }
+}
+
+impl FromStr for ColorDepth {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "4-bit" | "16" => Ok(ColorDepth::Ansi16),
+            "8-bit" | "256" => Ok(ColorDepth::Ansi256),
+            "24-bit" => Ok(ColorDepth::TrueColor),
+            _ => Err(format!(
+                "invalid color depth '{}', expected 4-bit, 8-bit or 24-bit",
+                s
+            )),
+        };
+    }
+}
+
 pub struct Compiler {
     entry_point: PathBuf,
+    color_depth: ColorDepth,
+    warnings: Vec<CompilerWarning>,
 }
 
 impl Compiler {
     pub fn new(entry_point: PathBuf) -> Self {
-        return Self { entry_point };
+        return Self {
+            entry_point,
+            color_depth: ColorDepth::Ansi256,
+            warnings: Vec::new(),
+        };
+    }
+    pub fn with_color_depth(mut self, color_depth: ColorDepth) -> Self {
+        self.color_depth = color_depth;
+        return self;
     }
-    pub fn compile(&mut self) -> Result<(), Vec<CompilerError>> {
-        //tokenize input file
-        let entry_content = read_to_string(&self.entry_point).unwrap();
+    /// the warnings of every checked module, also filled when compiling fails
+    pub fn warnings(&self) -> &[CompilerWarning] {
+        return &self.warnings;
+    }
+    /// resolve the entry point into the .usb file to compile and the project directory holding the `obj/` and `build/` directories.
+    /// The entry point is either a project directory containing a `.usbp.json` config or a single .usb file
+    fn resolve_entry_point(&self) -> Result<(PathBuf, PathBuf), MiscellaneousError> {
+        let invalid = || MiscellaneousError::InvalidEntryPoint {
+            path: self.entry_point.clone(),
+        };
+        if self.entry_point.is_file() {
+            let project_dir = self
+                .entry_point
+                .parent()
+                .map(PathBuf::from)
+                .unwrap_or_default();
+            return Ok((self.entry_point.clone(), project_dir));
+        }
+
+        let config_path = fs::read_dir(&self.entry_point)
+            .map_err(|_| invalid())?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .find(|path| path.to_string_lossy().ends_with(".usbp.json"))
+            .ok_or_else(invalid)?;
+        let config: ProjectConfig = read_to_string(&config_path)
+            .ok()
+            .and_then(|content| json::from_str(&content).ok())
+            .ok_or_else(invalid)?;
+        return Ok((
+            self.entry_point.join(config.entry_point),
+            self.entry_point.clone(),
+        ));
+    }
+    /// compile the entry point into a native binary, returning the path of that binary
+    pub fn compile(&mut self) -> Result<PathBuf, Vec<CompilerError>> {
+        let (entry_file, project_dir) = self
+            .resolve_entry_point()
+            .map_err(|e| vec![CompilerError::MiscError(e)])?;
+        //tokenize and parse the entry file and every file it imports
+        let modules = ModuleLoader::new().load(&entry_file)?;
 
-        let mut lexer = Lexer::new(entry_content);
+        //resolve symbols and validation pass on every module, each module has its own symbols and line numbers
         let mut errors: Vec<CompilerError> = Vec::new();
-        // entrypoint gets tokenized
-        let tokens: Vec<Token>;
-        match lexer.tokenize() {
-            Ok(t) => {
-                tokens = t;
+        for (index, module) in modules.iter().enumerate() {
+            let imports = module
+                .imports
+                .iter()
+                .map(|(path, index)| (path.clone(), modules[*index].line_numbers()))
+                .collect();
+            let mut checker = Checker::new()
+                .with_imports(imports)
+                .with_allowed(module.allowed.clone());
+            // only the entry point isn't imported by another module
+            if index > 0 {
+                checker = checker.imported();
             }
-            Err(errs) => {
-                errors.extend(
-                    errs.1
-                        .into_iter()
-                        .map(CompilerError::TokenizerError)
-                        .collect::<Vec<CompilerError>>(),
-                );
-                return Err(errors);
+            if let Err(errs) = checker.check(&module.ast) {
+                errors.extend(errs);
             }
+            self.warnings.extend(checker.warnings());
+        }
+        if !errors.is_empty() {
+            return Err(errors);
         }
-        println!("{:#?}", tokens);
-
-        //parse tokens and create ast
-        let mut parser = Parser::new(tokens, EvaluationContext::FileLevel);
-        let mut root_ast = parser.parse();
 
-        //resolve symbols
+        //generate assembly
+        let assembly = CodeGenerator::new().generate_program(&modules);
 
-        //validation pass on ast
+        let name = entry_file
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "main".into());
+        let binary = self
+            .assemble(&project_dir, &name, &assembly)
+            .map_err(|e| vec![CompilerError::MiscError(e)])?;
+        return Ok(binary);
+    }
+    /// write the assembly and usblib into `obj/` and let gcc assemble and link them into `build/<name>`
+    fn assemble(
+        &self,
+        project_dir: &Path,
+        name: &str,
+        assembly: &str,
+    ) -> Result<PathBuf, MiscellaneousError> {
+        let failure = |output: String| MiscellaneousError::AssemblerFailure { output };
+        let obj_dir = project_dir.join("obj");
+        let build_dir = project_dir.join("build");
+        for (path, content) in [
+            (obj_dir.join(format!("{}.s", name)), assembly),
+            (obj_dir.join("usblib.c"), USBLIB_SOURCE),
+            (obj_dir.join("usblib.h"), USBLIB_HEADER),
+        ] {
+            fs::create_dir_all(&obj_dir)
+                .and_then(|_| fs::write(&path, content))
+                .map_err(|e| failure(e.to_string()))?;
+        }
+        fs::create_dir_all(&build_dir).map_err(|e| failure(e.to_string()))?;
 
-        //if the ast contains Import nodes, generate those and inject them into the root ast.
+        let binary = build_dir.join(name);
+        let output = process::Command::new("gcc")
+            .arg("-O2")
+            .arg("-no-pie")
+            .arg("-o")
+            .arg(&binary)
+            .arg(obj_dir.join(format!("{}.s", name)))
+            .arg(obj_dir.join("usblib.c"))
+            .arg(format!("-DUSB_COLOR_DEPTH={}", self.color_depth.bits()))
+            .arg("-lm")
+            .output()
+            .map_err(|e| failure(e.to_string()))?;
+        if !output.status.success() {
+            return Err(failure(String::from_utf8_lossy(&output.stderr).into()));
+        }
+        return Ok(binary);
+    }
+}
 
-        //generate assembly
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
 
-        if errors.len() > 0 {
-            return Err(errors);
+    /// compile a program in a directory of its own under the temp directory and run it
+    fn run_program(name: &str, source: &str) -> process::Output {
+        let dir = std::env::temp_dir().join("usbasic-tests").join(name);
+        fs::create_dir_all(&dir).unwrap();
+        let entry = dir.join(format!("{}.usb", name));
+        fs::write(&entry, source).unwrap();
+        let binary = Compiler::new(entry).compile().unwrap();
+        return process::Command::new(binary).output().unwrap();
+    }
+    /// like `run_program`, with the input written to the standard input of the program
+    fn run_program_with_input(name: &str, source: &str, input: &str) -> process::Output {
+        let dir = std::env::temp_dir().join("usbasic-tests").join(name);
+        fs::create_dir_all(&dir).unwrap();
+        let entry = dir.join(format!("{}.usb", name));
+        fs::write(&entry, source).unwrap();
+        let binary = Compiler::new(entry).compile().unwrap();
+        let mut child = process::Command::new(binary)
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        return child.wait_with_output().unwrap();
+    }
+    #[test]
+    fn test_float_formatting() {
+        let output = run_program(
+            "floats",
+            "PRINT 90.0\nPRINT 2.5 * 4\nLET f AS FLOAT = 10\nPRINT f\nPRINT 0.1\nPRINT 1 / 3\nPRINT -2.5; \" \"; 0.0",
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "90.0\n10.0\n10.0\n0.1\n0.3333333333333333\n-2.5 0.0\n"
+        );
+    }
+    #[test]
+    fn test_element_field_assignment() {
+        let output = run_program(
+            "elements",
+            "RECORD Person\nname AS STRING\nage AS INT\nFIN\nLET p AS Person\np.age = 30\nLET l AS LIST OF Person\nPUSH l, p\nPUSH l, p\nl[0].age = 99\nl[1].age += 1\nl[1].name = \"Bob\"\nPRINT l[0].age; \" \"; l[1].age; \" \"; l[1].name; \" \"; p.age",
+        );
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "99 31 Bob 30\n");
+    }
+    #[test]
+    fn test_record_passed_to_function() {
+        let output = run_program(
+            "functions",
+            "RECORD Person\nname AS STRING\nage AS INT\nFIN\nFUNCTION older(p AS Person, years AS INT) AS INT\np.age += years\nRET p.age\nFIN\nFUNCTION greet(p AS Person)\nPRINT \"Hello \"; p.name\nFIN\nFUNCTION fib(n AS INT) AS INT\nIF n < 2 THEN RET n\nRET fib(n - 1) + fib(n - 2)\nFIN\nLET p AS Person\np.name = \"Ann\"\np.age = 30\nPRINT older(p, 5); \" \"; p.age\ngreet(p)\nPRINT fib(20)",
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "35 30\nHello Ann\n6765\n"
+        );
+    }
+    #[test]
+    fn test_counting_loops() {
+        let output = run_program(
+            "counting",
+            "FOR i = 1 TO 5\nPRINT i;\nNEXT i\nPRINT\nFOR i = 10 TO 1 STEP -3\nPRINT i; \" \";\nNEXT\nPRINT\nFOR x = 0 TO 1 STEP 0.25\nPRINT x; \" \";\nNEXT\nPRINT\nFOR i = 5 TO 1\nPRINT \"never\"\nNEXT",
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "12345\n10 7 4 1 \n0.0 0.25 0.5 0.75 1.0 \n"
+        );
+    }
+    #[test]
+    fn test_stop_status() {
+        for (index, (code, status)) in [(0, 0), (3, 3), (256, 255), (-1, 1)]
+            .into_iter()
+            .enumerate()
+        {
+            let output = run_program(
+                &format!("stop{}", index),
+                &format!("LET code = {}\nSTOP code\nPRINT \"still running\"", code),
+            );
+            assert_eq!(output.status.code(), Some(status), "STOP {}", code);
+            assert!(output.stdout.is_empty());
         }
-        return Ok(());
+    }
+    #[test]
+    fn test_error_handler_retry() {
+        // every GOTO out of the handler used to leave the frames of the throw on the stack
+        let output = run_program(
+            "retry",
+            "LET n = 0\nONERR GOTO 10\n10 n = n + 1\nIF n < 5000000 THEN THROW 1\nPRINT n",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "5000000\n");
+    }
+    #[test]
+    fn test_unhandled_error_status() {
+        for (index, (code, status)) in [(7, 7), (0, 1), (256, 255), (-3, 1)]
+            .into_iter()
+            .enumerate()
+        {
+            let output = run_program(
+                &format!("status{}", index),
+                &format!("LET code = {}\nTHROW code", code),
+            );
+            assert_eq!(output.status.code(), Some(status), "THROW {}", code);
+        }
+    }
+    #[test]
+    fn test_clear() {
+        let output = run_program(
+            "clear",
+            "LET x = 10\nLET y = TRUE\nLET s = \"hi\"\nCLEAR\nPRINT x\nPRINT y\nPRINT s + \"!\"\n\
+             LET z = 10\nBEGIN\nLET w = 5\nCSCOPE\nPRINT z\nPRINT w\nFIN",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "0\nFALSE\n!\n10\n0\n"
+        );
+    }
+    #[test]
+    fn test_positional_calls_in_expressions() {
+        let file = std::env::temp_dir()
+            .join("usbasic-tests")
+            .join("readall.txt");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, "abc").unwrap();
+        let output = run_program(
+            "positional",
+            &format!(
+                "PRINT HOUR 2 + MINUTE 30\nLET F = OPEN \"{}\", \"r\"\nPRINT \"[\" + READALL F + \"]\"",
+                file.display()
+            ),
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "9000\n[abc]\n");
+    }
+    #[test]
+    fn test_nested_data() {
+        // DATA inside a FUNCTION or ONERR still belongs to the pool READ walks through
+        let output = run_program(
+            "nested_data",
+            "FUNCTION f()\nDATA 1, 2\nFIN\nONERR DATA 3\nLET a = 0\nREAD a\nPRINT a\nREAD a, a\nPRINT a",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n3\n");
+    }
+    #[test]
+    fn test_float_to_integer_range() {
+        let output = run_program(
+            "rounding",
+            "PRINT INT(-1.5); FLOOR(2.7); CEIL(2.1); ROUND(-2.5)",
+        );
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "-223-3\n");
+        // the conversions throw instead of wrapping around, EXP(1000) - EXP(1000) is NaN
+        for (index, value) in [
+            "INT(EXP(700))",
+            "FLOOR(-EXP(44))",
+            "CEIL(EXP(44))",
+            "ROUND(EXP(1000) - EXP(1000))",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let output = run_program(&format!("range{}", index), &format!("PRINT {}", value));
+            assert_eq!(output.status.code(), Some(2), "{}", value);
+            assert!(String::from_utf8_lossy(&output.stderr).contains("OutOfRange"));
+        }
+    }
+    #[test]
+    fn test_date_input_validation() {
+        // days and months that don't exist are asked for again instead of rolling over into the next month
+        let output = run_program_with_input(
+            "date_input",
+            "LET d AS DATE\nINPUT d\nPRINT FORMATDATE(d, \"%Y-%m-%d\")",
+            "2024-02-30\n2023-02-29\n2024-13-01\n2024-02-29\n",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "? ?REENTER\n? ?REENTER\n? ?REENTER\n? 2024-02-29\n"
+        );
+    }
+    #[test]
+    fn test_record_return() {
+        // the returned record is a copy, the variables of the call are restored before the caller reads it
+        let output = run_program(
+            "record_return",
+            "RECORD Point\nx AS INT\ny AS INT\nFIN\nFUNCTION make(x AS INT, y AS INT) AS Point\nLET p AS Point\np.x = x\np.y = y\nRET p\nFIN\n\
+             FUNCTION origin() AS Point\nFIN\nFUNCTION walk(n AS INT) AS Point\nIF n = 0 THEN RET make(0, 0)\nLET p = walk(n - 1)\np.x += n\nRET p\nFIN\n\
+             LET a = make(3, 4)\nLET b = make(5, 6)\nLET o = origin()\nLET w = walk(4)\nPRINT a.x; a.y; b.x; b.y; o.x; o.y; w.x",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "34560010\n");
+    }
+    #[test]
+    fn test_dim() {
+        let output = run_program(
+            "dim",
+            "RECORD Person\nname AS STRING\nage AS INT\nFIN\nDIM arr(10) AS INT\narr[0] = 100\nPRINT arr[0]; \" \"; LEN(arr); \" \"; arr[9]\n\
+             DIM grid(3, 2) AS STRING\ngrid[1][2] = \"x\"\nPRINT LEN(grid); LEN(grid[0]); \"[\"; grid[0][0]; \"]\"; grid[1][2]\n\
+             DIM people(3) AS Person\npeople[0].name = \"Ada\"\npeople[1].age = 5\nPRINT people[0].name; people[1].age; people[2].age\n\
+             LET n = -1\nDIM bad(n) AS INT",
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "100 10 0\n23[]x\nAda50\n"
+        );
+        assert_eq!(output.status.code(), Some(2));
+    }
+    #[test]
+    fn test_def() {
+        let output = run_program(
+            "def",
+            "RECORD Person\nname AS STRING\nage AS INT\nFIN\nDEF square x = x * x\nDEF greeting(p, greet) = greet + \", \" + p.name\n\
+             DEF first l = l[0]\nLET ada AS Person\nada.name = \"Ada\"\nDIM people(2) AS Person\npeople[0].age = 7\n\
+             PRINT square(5); \" \"; square(1.5); \" \"; square(square(2))\nPRINT greeting(ada, \"Hi\")\nLET f = first(people)\nPRINT f.age",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "25 2.25 16\nHi, Ada\n7\n"
+        );
+    }
+    #[test]
+    fn test_collections() {
+        let output = run_program(
+            "collections",
+            "LET l AS LIST OF FLOAT\nPUSH l, 1\nPUSH l, 2\nl[0] = 2.5\nINSERT l, 0, 0.5\n\
+             PRINT LEN(l); \" \"; l[0]; \" \"; l[1]; \" \"; POP l; \" \"; LEN(l)\n\
+             LET m AS MAP OF STRING TO INT\nm[\"b\"] = 2\nm[\"a\"] = 1\nm[\"c\"] = 3\nPRINT LEN(m) + m[\"a\"]\nREMOVE m, \"c\"\n\
+             FOR EACH k IN m\nIF k = \"a\" THEN CONTINUE\nPRINT k; m[k]\nNEXT\nPRINT HAS(m, \"a\"); \" \"; HAS(m, \"c\")\n\
+             FOR EACH x IN l\nPRINT x\nNEXT",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        // the integers pushed onto the list of floats are converted, the keys of a map come in the order they were added
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "3 0.5 2.5 2.0 2\n4\nb2\nTRUE FALSE\n0.5\n2.5\n"
+        );
+    }
+    #[test]
+    fn test_while_and_do() {
+        let output = run_program(
+            "while_do",
+            "LET n = 0\nWHILE TRUE\nn = n + 1\nIF n = 3 THEN EXIT\nWEND\nPRINT n\nLET odd = 0\n\
+             DO\nn = n - 1\nIF n MOD 2 = 0 THEN CONTINUE\nodd = odd + 1\nLOOP UNTIL n = 0\nPRINT odd\n\
+             WHILE FALSE\nPRINT \"never\"\nWEND\nDO\nPRINT \"once\"\nLOOP UNTIL TRUE",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        // CONTINUE in a DO still checks the condition, the body of a DO runs before it is checked
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n1\nonce\n");
+    }
+    #[test]
+    fn test_integer_operators() {
+        let output = run_program(
+            "integer_operators",
+            "PRINT 7 MOD 2; \" \"; -7 MOD 2; \" \"; 7 \\ 2; \" \"; -7 \\ 2\n\
+             PRINT -8 SHR 1; \" \"; 1 SHL 4; \" \"; 12 BAND 10; \" \"; 12 BOR 10; \" \"; 12 BXOR 10\n\
+             LET zero = 0\nPRINT 1 \\ zero",
+        );
+        // integer division truncates towards zero like C, a zero divisor throws DivisionByZero
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "1 -1 3 -3\n-4 16 8 14 6\n"
+        );
+        assert_eq!(output.status.code(), Some(11));
+    }
+    #[test]
+    fn test_select() {
+        let output = run_program(
+            "select",
+            "FOR i = 0 TO 7\nSELECT CASE i\nCASE 1\nPRINT \"one\";\nCASE 2, 4\nPRINT \"even\";\nCASE 5 TO 6\nPRINT \"five\";\n\
+             CASE ELSE\nPRINT \"else\";\nEND SELECT\nPRINT \" \";\n\
+             SELECT CASE i\nCASE 1, 100\nPRINT \"sparse\"\nCASE IS > 5\nPRINT \"big\"\nCASE ELSE\nPRINT \"-\"\nEND SELECT\nNEXT",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "else -\none sparse\neven -\nelse -\neven -\nfive -\nfive big\nelse big\n"
+        );
+    }
+    #[test]
+    fn test_menu() {
+        let output = run_program(
+            "menu",
+            "LET c = 0\n10 c = c + 1\nIF c > 3 THEN END\nMENU c PRINT \"one\", GOTO 20, GOSUB 30\nPRINT \"after\"; c\nGOTO 10\n\
+             20 PRINT \"jumped\"\nGOTO 10\n30 PRINT \"sub\"\nRET",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        // a GOSUB comes back to the line after the MENU, a GOTO doesn't
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "one\nafter1\njumped\nsub\nafter3\n"
+        );
+        let output = run_program("menu_range", "MENU 4 PRINT 1, PRINT 2");
+        assert_eq!(output.status.code(), Some(2));
+    }
+    #[test]
+    fn test_data_pool_in_program_order() {
+        // READ comes before the DATA it reads, RESTORE <line> moves to the first value of that line
+        let output = run_program(
+            "data_pool",
+            "LET a = 0\nLET s = \"\"\nLET b = FALSE\nREAD a, s\nPRINT a; s\nRESTORE 30\nREAD b\nPRINT b\nRESTORE\nREAD a\nPRINT a\n\
+             20 DATA 1, \"a\"\n30 DATA TRUE",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "1a\nTRUE\n1\n");
+    }
+    #[test]
+    fn test_error_handler_is_registered() {
+        // with a handler a value that doesn't fit is a Format error instead of ?REENTER, the program goes on after the INPUT
+        let output = run_program_with_input(
+            "onerr_input",
+            "ONERR PRINT \"error \"; ERR\nLET a = 5\nINPUT a\nPRINT a\nINPUT a\nPRINT a",
+            "x\n42\n",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "? error 1\n0\n? 42\n"
+        );
+    }
+    #[test]
+    fn test_record_copies() {
+        // nested records are copied by value, their string fields default to ""
+        let output = run_program(
+            "record_copies",
+            "RECORD Point\nx AS INTEGER\nlabel AS STRING\nFIN\nRECORD Line\nstart AS Point\nfinish AS Point\nFIN\nLET l AS Line\nPRINT \"[\"; l.finish.label; \"]\"\nLET p = l.finish\nl.finish.label = \"end\"\nl.finish.x = 7\nPRINT p.x; \" \"; p.label; \"|\"; l.finish.x; \" \"; l.finish.label\nLET m = l\nm.finish.x = 9\nPRINT l.finish.x; \" \"; m.finish.x",
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "[]\n0 |7 end\n7 9\n"
+        );
+    }
+    #[test]
+    fn test_function_saves_its_variables() {
+        // the parameters and locals of each call survive the recursive call
+        let output = run_program(
+            "recursion",
+            "FUNCTION f(n AS INT, s AS STRING) AS STRING\nIF n = 0 THEN RET s\nLET m = n\nLET t = f(m - 1, s + \"x\")\nRET STR$(m) + s + t\nFIN\nPRINT f(3, \"a\")",
+        );
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "3a2ax1axxaxxx\n");
+    }
+    #[test]
+    fn test_filesize_of_a_directory() {
+        let output = run_program("filesize", "PRINT FILESIZE(\"/\")");
+        assert_eq!(output.status.code(), Some(10));
+        assert!(String::from_utf8_lossy(&output.stdout).is_empty());
+    }
+    #[test]
+    fn test_c_style_for() {
+        // CONTINUE still runs the step
+        let output = run_program(
+            "c_style_for",
+            "FOR I = 0, I < 10, I += 3\nPRINT I; \" \";\nNEXT\nPRINT\nFOR X = 1, X < 100, X = X * 2 BEGIN\nIF X == 4 THEN CONTINUE\nPRINT X; \" \";\nFIN",
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "0 3 6 9 \n1 2 8 16 32 64 "
+        );
+    }
+    #[test]
+    fn test_integer_operator_limits() {
+        // the smallest integer divided by -1 wraps around instead of faulting, shift counts aren't masked
+        let output = run_program(
+            "integer_limits",
+            "LET m = -9223372036854775807 - 1\nPRINT m \\ -1; \" \"; m MOD -1; \" \"; 7 \\ -1; \" \"; 7 MOD -1\nPRINT 1 SHL 64; \" \"; 1 SHL -1; \" \"; -8 SHR 70; \" \"; 8 SHR 64; \" \"; -8 SHR -1; \" \"; -8 SHR 2",
+        );
+        assert!(output.status.success(), "{:?}", output.status);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "-9223372036854775808 0 -7 0\n0 0 -1 0 -1 -2\n"
+        );
     }
 }
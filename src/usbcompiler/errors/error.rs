@@ -1,6 +1,8 @@
+use std::{fmt, path::PathBuf};
+
 use crate::usbcompiler::{
     ast::ast::{DataType, Expression, IdentifierNode, ScopeNode},
-    tokenizer::lexer::{Span, Token},
+    tokenizer::lexer::{Span, Token, TokenKind},
 };
 
 #[derive(Debug)]
@@ -8,6 +10,7 @@ pub enum USBError {
     CompilerError(CompilerError),
 }
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum CompilerError {
     TokenizerError(LexerError),
     ParsingError(ParserError),
@@ -43,6 +46,15 @@ pub enum ParserError {
         span: Span,
         extra_tokens: Vec<Token>,
     },
+    /// a `GOTO`, `GOSUB` or `RESTORE` to a label that isn't declared in the same file
+    UndefinedLabel {
+        span: Span,
+        label: String,
+    },
+    DuplicateLabel {
+        span: Span,
+        label: String,
+    },
 }
 
 #[derive(Debug)]
@@ -76,13 +88,86 @@ pub enum SemanticError {
         expression: Expression,
         target: DataType,
     },
-    UnreachableCode {
+    InvalidLoopBreak {
         token: Token,
     },
-    InvalidLoopBreak {
+    UnannotatedEnumValue {
+        token: Token,
+    },
+    UndefinedLine {
         token: Token,
+        line_number: usize,
+    },
+    /// a jump from outside of a scope to a line of it after a declaration, which would leave the variable without its value
+    JumpIntoScope {
+        token: Token,
+        line_number: usize,
+    },
+    /// a `GOTO` or `GOSUB` into or out of the body of a `FUNCTION`, which is only entered by calling it and left with `RET`
+    FunctionJump {
+        token: Token,
+        line_number: usize,
+    },
+    /// a `SELECT CASE` on an enum without `CASE ELSE` that doesn't cover every key of the enum
+    NonExhaustiveSelect {
+        token: Token,
+        missing: Vec<String>,
     },
 }
+/// problems that don't stop a program from compiling, but are likely bugs
+#[derive(Debug)]
+pub enum CompilerWarning {
+    /// a `=` comparing outside of a condition, where it reads like an assignment. `==` always compares
+    AmbiguousEquality { token: Token },
+    /// statements no path through the program leads to, e.g. the line after a `GOTO`
+    UnreachableCode { token: Token },
+    /// a `GOSUB` to a subroutine that can run into the end of the program without a `RET` or `END`
+    MissingReturn { token: Token, line_number: usize },
+    /// a read of a variable that was declared without a value and may not have been assigned one since
+    UnassignedRead { token: Token, name: String },
+}
+impl CompilerWarning {
+    /// the name an `ALLOW` comment suppresses the warning with
+    pub fn name(&self) -> &'static str {
+        return match self {
+            CompilerWarning::AmbiguousEquality { .. } => "EQUALITY",
+            CompilerWarning::UnreachableCode { .. } => "UNREACHABLE",
+            CompilerWarning::MissingReturn { .. } => "RETURN",
+            CompilerWarning::UnassignedRead { .. } => "UNASSIGNED",
+        };
+    }
+    pub fn token(&self) -> &Token {
+        return match self {
+            CompilerWarning::AmbiguousEquality { token }
+            | CompilerWarning::UnreachableCode { token }
+            | CompilerWarning::MissingReturn { token, .. }
+            | CompilerWarning::UnassignedRead { token, .. } => token,
+        };
+    }
+}
+/// `<line>:<column>: <message>`, followed by the `ALLOW` comment that suppresses the warning
+impl fmt::Display for CompilerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let span = &self.token().position_span;
+        write!(f, "{}:{}: ", span.line, span.column)?;
+        match self {
+            CompilerWarning::AmbiguousEquality { .. } => write!(
+                f,
+                "`=` compares here, use `==` to compare outside of a condition"
+            )?,
+            CompilerWarning::UnreachableCode { .. } => write!(f, "this line never runs")?,
+            CompilerWarning::MissingReturn { line_number, .. } => write!(
+                f,
+                "the subroutine at line {} can run into the end of the program without a RET",
+                line_number
+            )?,
+            CompilerWarning::UnassignedRead { name, .. } => {
+                write!(f, "{} may be read before it is assigned a value", name)?
+            }
+        }
+        return write!(f, " (ALLOW {})", self.name());
+    }
+}
 #[derive(Debug)]
 pub enum DeclarationError {
     MultipleDefinitions { token: Token },
@@ -92,9 +177,212 @@ pub enum DeclarationError {
 pub enum MiscellaneousError {
     InvalidConstant {
         token: Token,
-        expression: Expression,
+        expression: Box<Expression>,
     },
     InvalidMacro {
         token: Token,
     },
+    InvalidEntryPoint {
+        path: PathBuf,
+    },
+    AssemblerFailure {
+        output: String,
+    },
+    InvalidImport {
+        token: Token,
+        path: PathBuf,
+    },
+    ImportCycle {
+        token: Token,
+        path: PathBuf,
+    },
+}
+/// `<line>:<column>: <message>` like a [`CompilerWarning`], errors that don't point at the code are just the message
+impl fmt::Display for CompilerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            CompilerError::TokenizerError(error) => write!(f, "{}", error),
+            CompilerError::ParsingError(error) => write!(f, "{}", error),
+            CompilerError::SemanticError(error) => write!(f, "{}", error),
+            CompilerError::DeclarationError(error) => write!(f, "{}", error),
+            CompilerError::MiscError(error) => write!(f, "{}", error),
+        };
+    }
+}
+fn write_span(f: &mut fmt::Formatter<'_>, span: &Span) -> fmt::Result {
+    return write!(f, "{}:{}: ", span.line, span.column);
+}
+impl fmt::Display for LexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            LexerError::InvalidChar(span, character) => {
+                write_span(f, span)?;
+                write!(f, "invalid character `{}`", character)
+            }
+            LexerError::InvalidNumberLiteral(span, literal) => {
+                write_span(f, span)?;
+                write!(f, "invalid number `{}`", literal)
+            }
+            LexerError::UnterminatedString(span) => {
+                write_span(f, span)?;
+                write!(f, "the string is missing its closing `\"`")
+            }
+            LexerError::UnexpectedEof => write!(f, "unexpected end of file"),
+            LexerError::InvalidEscapeChar(span, character) => {
+                write_span(f, span)?;
+                write!(f, "invalid escape sequence `\\{}`", character)
+            }
+        };
+    }
+}
+impl ParserError {
+    pub fn span(&self) -> &Span {
+        return match self {
+            ParserError::UnexpectedToken { span, .. }
+            | ParserError::MissingToken { span, .. }
+            | ParserError::MismatchedDelimiter { span }
+            | ParserError::InvalidCode { span }
+            | ParserError::ExtraCode { span, .. }
+            | ParserError::UndefinedLabel { span, .. }
+            | ParserError::DuplicateLabel { span, .. } => span,
+        };
+    }
+}
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_span(f, self.span())?;
+        return match self {
+            ParserError::UnexpectedToken { token, .. } => match token.kind {
+                TokenKind::Newline | TokenKind::EOF => write!(f, "unexpected {}", token.kind),
+                _ => write!(f, "unexpected `{}`", token.kind),
+            },
+            ParserError::MissingToken { expected, .. } => write!(f, "expected {}", expected),
+            ParserError::MismatchedDelimiter { .. } => write!(f, "mismatched delimiter"),
+            ParserError::InvalidCode { .. } => write!(f, "invalid code"),
+            ParserError::ExtraCode { .. } => write!(f, "unexpected code after the statement"),
+            ParserError::UndefinedLabel { label, .. } => {
+                write!(f, "the label @{} isn't declared in this file", label)
+            }
+            ParserError::DuplicateLabel { label, .. } => {
+                write!(f, "the label @{} is declared more than once", label)
+            }
+        };
+    }
+}
+impl SemanticError {
+    pub fn token(&self) -> &Token {
+        return match self {
+            SemanticError::UndefinedIdentifier { token, .. }
+            | SemanticError::DuplicateDeclaration { token, .. }
+            | SemanticError::ShadowedIdentifier { token, .. }
+            | SemanticError::IllegalIdentifier { token, .. }
+            | SemanticError::TypeMismatch { token, .. }
+            | SemanticError::InvalidOperation { token, .. }
+            | SemanticError::InvalidCast { token, .. }
+            | SemanticError::InvalidLoopBreak { token }
+            | SemanticError::UnannotatedEnumValue { token }
+            | SemanticError::UndefinedLine { token, .. }
+            | SemanticError::JumpIntoScope { token, .. }
+            | SemanticError::FunctionJump { token, .. }
+            | SemanticError::NonExhaustiveSelect { token, .. } => token,
+        };
+    }
+}
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_span(f, &self.token().position_span)?;
+        return match self {
+            SemanticError::UndefinedIdentifier { identifier, .. } => {
+                write!(f, "{} isn't declared", identifier.name)
+            }
+            SemanticError::DuplicateDeclaration { token, .. } => {
+                write!(f, "`{}` is declared more than once", token.kind)
+            }
+            SemanticError::ShadowedIdentifier { identifier, .. } => write!(
+                f,
+                "{} hides a declaration of an outer scope",
+                identifier.name
+            ),
+            SemanticError::IllegalIdentifier { identifier, .. } => {
+                write!(f, "{} can't be used here", identifier.name)
+            }
+            SemanticError::TypeMismatch { expected_type, .. } => {
+                write!(f, "expected a value of type {}", expected_type)
+            }
+            SemanticError::InvalidOperation { token, .. } => {
+                write!(f, "`{}` isn't valid for these types", token.kind)
+            }
+            SemanticError::InvalidCast { target, .. } => {
+                write!(f, "the value can't be converted to {}", target)
+            }
+            SemanticError::InvalidLoopBreak { token } => {
+                write!(f, "{} outside of a loop", token.kind)
+            }
+            SemanticError::UnannotatedEnumValue { token } => {
+                write!(f, "{} needs a value of its own", token.kind)
+            }
+            SemanticError::UndefinedLine { line_number, .. } => {
+                write!(f, "there is no line {}", line_number)
+            }
+            SemanticError::JumpIntoScope { line_number, .. } => write!(
+                f,
+                "the jump to line {} skips the declaration of a variable used there",
+                line_number
+            ),
+            SemanticError::FunctionJump { line_number, .. } => write!(
+                f,
+                "the jump to line {} enters or leaves the body of a FUNCTION, call it and leave it with RET instead",
+                line_number
+            ),
+            SemanticError::NonExhaustiveSelect { missing, .. } => write!(
+                f,
+                "the SELECT CASE doesn't cover {}, add them or a CASE ELSE",
+                missing.join(", ")
+            ),
+        };
+    }
+}
+impl fmt::Display for DeclarationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            DeclarationError::MultipleDefinitions { token } => {
+                write_span(f, &token.position_span)?;
+                write!(f, "{} is already declared in this scope", token.kind)
+            }
+            DeclarationError::InvalidSignature { token } => {
+                write_span(f, &token.position_span)?;
+                write!(f, "wrong number of arguments for {}", token.kind)
+            }
+        };
+    }
+}
+impl fmt::Display for MiscellaneousError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            MiscellaneousError::InvalidConstant { token, .. } => {
+                write_span(f, &token.position_span)?;
+                write!(f, "`{}` isn't a valid value here", token.kind)
+            }
+            MiscellaneousError::InvalidMacro { token } => {
+                write_span(f, &token.position_span)?;
+                write!(f, "invalid macro `{}`", token.kind)
+            }
+            MiscellaneousError::InvalidEntryPoint { path } => write!(
+                f,
+                "{} is neither a .usb file nor a project directory",
+                path.display()
+            ),
+            MiscellaneousError::AssemblerFailure { output } => {
+                write!(f, "assembling the program failed:\n{}", output)
+            }
+            MiscellaneousError::InvalidImport { token, path } => {
+                write_span(f, &token.position_span)?;
+                write!(f, "{} can't be imported", path.display())
+            }
+            MiscellaneousError::ImportCycle { token, path } => {
+                write_span(f, &token.position_span)?;
+                write!(f, "importing {} leads back to this file", path.display())
+            }
+        };
+    }
 }
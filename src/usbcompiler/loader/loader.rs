@@ -0,0 +1,264 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use crate::usbcompiler::{
+    ast::ast::{AstNode, ImportNode, Statement},
+    errors::error::{CompilerError, MiscellaneousError},
+    parser::parser::{EvaluationContext, Parser},
+    tokenizer::lexer::Lexer,
+};
+
+/// a single parsed source file of a program
+#[derive(Debug)]
+pub struct Module {
+    pub path: PathBuf,
+    pub ast: AstNode,
+    /// the module every `IMPORT` path of this file resolved to, as an index into the loaded modules
+    pub imports: HashMap<String, usize>,
+    /// the warnings `ALLOW` comments of the file suppress, see [`Lexer::allowed`]
+    pub allowed: HashSet<(usize, String)>,
+}
+
+impl Module {
+    /// the numbers of the lines other modules can jump to, lines nested in scopes included
+    pub fn line_numbers(&self) -> HashSet<usize> {
+        let mut lines = HashSet::new();
+        if let AstNode::STATEMENT(statement) = &self.ast {
+            Self::collect_lines(statement, &mut lines);
+        }
+        return lines;
+    }
+    fn collect_lines(statement: &Statement, lines: &mut HashSet<usize>) {
+        match statement {
+            Statement::LINE(line) => {
+                lines.insert(line.line_number);
+            }
+            Statement::SCOPE(scope) => {
+                for statement in &scope.statements {
+                    Self::collect_lines(statement, lines);
+                }
+            }
+            Statement::LOOP(node) => {
+                for statement in &node.body {
+                    Self::collect_lines(statement, lines);
+                }
+            }
+            Statement::IF(if_statement) => {
+                Self::collect_lines(&if_statement.action, lines);
+                if let Some(else_action) = &if_statement.else_action {
+                    Self::collect_lines(else_action, lines);
+                }
+            }
+            Statement::SELECT(select) => {
+                let bodies = select
+                    .cases
+                    .iter()
+                    .map(|c| &c.body)
+                    .chain(&select.else_body);
+                for statement in bodies.flatten() {
+                    Self::collect_lines(statement, lines);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Loads the entry file of a program and every file it (transitively) imports.
+/// Every file is only loaded once, no matter how many files import it
+pub struct ModuleLoader {
+    modules: Vec<Option<Module>>,
+    loaded: HashMap<PathBuf, usize>,
+    /// the files currently being loaded, a file importing one of these is an import cycle
+    loading: Vec<PathBuf>,
+    errors: Vec<CompilerError>,
+}
+
+impl ModuleLoader {
+    pub fn new() -> Self {
+        return Self {
+            modules: Vec::new(),
+            loaded: HashMap::new(),
+            loading: Vec::new(),
+            errors: Vec::new(),
+        };
+    }
+    /// load the program starting at the entry file, the entry file is always the first module
+    pub fn load(mut self, entry_file: &Path) -> Result<Vec<Module>, Vec<CompilerError>> {
+        let path = match entry_file.canonicalize() {
+            Ok(path) => path,
+            Err(_) => {
+                return Err(vec![CompilerError::MiscError(
+                    MiscellaneousError::InvalidEntryPoint {
+                        path: entry_file.into(),
+                    },
+                )]);
+            }
+        };
+        self.load_module(path, EvaluationContext::FileLevel);
+
+        if !self.errors.is_empty() {
+            return Err(self.errors);
+        }
+        return Ok(self.modules.into_iter().flatten().collect());
+    }
+    /// load and parse a file and the files it imports, returning the index of its module
+    fn load_module(&mut self, path: PathBuf, context: EvaluationContext) -> Option<usize> {
+        let content = match read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => {
+                self.errors.push(CompilerError::MiscError(
+                    MiscellaneousError::InvalidEntryPoint { path },
+                ));
+                return None;
+            }
+        };
+        let mut lexer = Lexer::new(content);
+        let tokens = match lexer.tokenize() {
+            Ok(tokens) => tokens,
+            Err((_, errors)) => {
+                self.errors
+                    .extend(errors.into_iter().map(CompilerError::TokenizerError));
+                return None;
+            }
+        };
+        let ast = match Parser::new(tokens, context).parse() {
+            Ok(ast) => ast,
+            Err(errors) => {
+                self.errors
+                    .extend(errors.into_iter().map(CompilerError::ParsingError));
+                return None;
+            }
+        };
+
+        // reserve the slot first so the importing module comes before the modules it imports
+        let index = self.modules.len();
+        self.modules.push(None);
+        self.loaded.insert(path.clone(), index);
+        self.loading.push(path.clone());
+
+        let mut import_nodes = Vec::new();
+        if let AstNode::STATEMENT(statement) = &ast {
+            Self::collect_imports(statement, &mut import_nodes);
+        }
+        let directory = path.parent().map(PathBuf::from).unwrap_or_default();
+        let mut imports = HashMap::new();
+        for import in import_nodes {
+            if let Some(module) = self.resolve_import(&directory, import) {
+                imports.insert(import.path.clone(), module);
+            }
+        }
+
+        self.loading.pop();
+        self.modules[index] = Some(Module {
+            path,
+            ast,
+            imports,
+            allowed: lexer.allowed(),
+        });
+        return Some(index);
+    }
+    fn collect_imports<'a>(statement: &'a Statement, imports: &mut Vec<&'a ImportNode>) {
+        match statement {
+            Statement::IMPORT(import) => imports.push(import),
+            Statement::SCOPE(scope) => {
+                for statement in &scope.statements {
+                    Self::collect_imports(statement, imports);
+                }
+            }
+            _ => (),
+        }
+    }
+    /// resolve an import relative to the directory of the importing file and load it if it wasn't already
+    fn resolve_import(&mut self, directory: &Path, import: &ImportNode) -> Option<usize> {
+        let path = match directory.join(&import.path).canonicalize() {
+            Ok(path) => path,
+            Err(_) => {
+                self.errors.push(CompilerError::MiscError(
+                    MiscellaneousError::InvalidImport {
+                        token: import.token.clone(),
+                        path: directory.join(&import.path),
+                    },
+                ));
+                return None;
+            }
+        };
+        if self.loading.contains(&path) {
+            self.errors
+                .push(CompilerError::MiscError(MiscellaneousError::ImportCycle {
+                    token: import.token.clone(),
+                    path,
+                }));
+            return None;
+        }
+        if let Some(index) = self.loaded.get(&path) {
+            return Some(*index);
+        }
+        return self.load_module(path, EvaluationContext::Imported);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// write the given files into a fresh directory in the system temp directory
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("usb_loader_{}", name));
+        let _ = fs::remove_dir_all(&directory);
+        for (path, content) in files {
+            let path = directory.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        return directory;
+    }
+    #[test]
+    fn test_imports_resolve_relative_to_importer() {
+        let directory = write_files(
+            "relative",
+            &[
+                (
+                    "main.usb",
+                    "IMPORT \"lib/a.usb\" AS A\nIMPORT \"lib/b.usb\" AS B",
+                ),
+                ("lib/a.usb", "IMPORT \"b.usb\" AS B\n10 PRINT 1"),
+                ("lib/b.usb", "20 PRINT 2"),
+            ],
+        );
+        let modules = ModuleLoader::new()
+            .load(&directory.join("main.usb"))
+            .unwrap();
+        // b.usb is imported twice but only loaded once
+        assert_eq!(modules.len(), 3);
+        assert!(modules[0].path.ends_with("main.usb"));
+        assert_eq!(modules[0].imports["lib/b.usb"], modules[1].imports["b.usb"]);
+        assert_eq!(
+            modules[modules[0].imports["lib/b.usb"]].line_numbers(),
+            HashSet::from([20])
+        );
+    }
+    #[test]
+    fn test_import_cycle() {
+        let directory = write_files(
+            "cycle",
+            &[
+                ("main.usb", "IMPORT \"a.usb\" AS A"),
+                ("a.usb", "IMPORT \"main.usb\" AS MAIN"),
+            ],
+        );
+        let errors = ModuleLoader::new()
+            .load(&directory.join("main.usb"))
+            .unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [CompilerError::MiscError(
+                MiscellaneousError::ImportCycle { .. }
+            )]
+        ));
+    }
+}
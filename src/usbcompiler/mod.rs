@@ -1,7 +1,10 @@
 #![allow(dead_code)]
 
 pub mod ast;
+pub mod checker;
+pub mod codegen;
 pub mod compiler;
 pub mod errors;
+pub mod loader;
 pub mod parser;
 pub mod tokenizer;
@@ -1,14 +1,41 @@
-use crate::usbcompiler::{
-    ast::ast::{AstNode, Expression, ScopeNode, Statement},
-    errors::error::ParserError,
-    tokenizer::lexer::{Span, Token, TokenKind},
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    stdlib::{StdLibFunction, find_function},
+    usbcompiler::{
+        ast::ast::{
+            AssignmentNode, AstNode, BinaryOpKind, BinaryOpNode, CaseCondition, CaseNode,
+            ClearNode, CollectionNode, CollectionOpKind, ConstantNode, DataNode, DataType, DefNode,
+            DimNode, EndNode, EnumDeclarationNode, EnumMemberNode, ErrorHandlerNode, Expression,
+            FunctionCallNode, FunctionDeclarationNode, IdentifierNode, IfStatementNode, ImportNode,
+            InputNode, JumpNode, LineNode, LoopJumpNode, LoopKind, LoopNode, MenuNode, OptionsNode,
+            ParameterNode, PrintEnumNode, PrintItem, PrintNode, ReadDataNode,
+            RecordDeclarationNode, RecordFieldNode, RestoreNode, ReturnNode, ScopeNode, ScreenNode,
+            SelectNode, Statement, StopNode, TargetAccess, ThrowNode, UnaryOpKind, UnaryOpNode,
+            VariableDeclarationNode,
+        },
+        errors::error::ParserError,
+        tokenizer::lexer::{Span, Token, TokenKind},
+    },
 };
 
+/// keywords whose first argument is a file descriptor
+const FILE_KEYWORDS: [&str; 3] = ["READ", "WRITE", "APPEND"];
+
 pub struct Parser {
     input: Vec<Token>,
     position: usize,
     evaluation_context: EvaluationContext,
     current_token: Option<Token>,
+    line_number: usize,
+    /// set while parsing the statements of a `MENU`, where commas separate statements as well as arguments
+    in_menu: bool,
+    /// the names of the records declared so far, any other name in a type annotation is an enum
+    records: HashSet<String>,
+    /// the line numbers of the `@<label>:` declarations so far, jumps to labels are resolved once the whole file is parsed
+    labels: HashMap<String, usize>,
+    /// the label tokens of every jump to a label, checked against the declarations even if the file has other errors
+    label_jumps: Vec<Token>,
 }
 pub enum EvaluationContext {
     Imported,
@@ -16,7 +43,8 @@ pub enum EvaluationContext {
     Nested,
 }
 
-type ExpressionHandler = fn(&mut Parser, Token) -> Result<Option<Expression>, ParserError>;
+type ExpressionHandler = fn(&mut Parser, Token) -> Result<Option<Expression>, Vec<ParserError>>;
+type StatementHandler = fn(&mut Parser, Token) -> Result<Option<Statement>, Vec<ParserError>>;
 impl Parser {
     pub fn new(input: Vec<Token>, evaluation_context: EvaluationContext) -> Self {
         let mut parser = Self {
@@ -24,6 +52,11 @@ impl Parser {
             evaluation_context,
             position: 0,
             current_token: None,
+            line_number: 0,
+            in_menu: false,
+            records: HashSet::new(),
+            labels: HashMap::new(),
+            label_jumps: Vec::new(),
         };
 
         parser.advance();
@@ -55,63 +88,2607 @@ impl Parser {
             self.current_token = None;
         }
     }
+    /// the span of the current token, or the span of the last token when the stream is exhausted
+    fn current_span(&self) -> Span {
+        if let Some(token) = &self.current_token {
+            return token.position_span.clone();
+        }
+        return match self.input.last() {
+            Some(token) => token.position_span.clone(),
+            None => Span::new(1, 1, 0),
+        };
+    }
+    fn current_is_keyword(&self, keyword: &str) -> bool {
+        return match &self.current_token {
+            Some(token) => Self::is_keyword(token, keyword),
+            None => false,
+        };
+    }
+    fn is_keyword(token: &Token, keyword: &str) -> bool {
+        if let TokenKind::Keyword(k) = &token.kind {
+            return k.to_uppercase() == keyword;
+        }
+        return false;
+    }
+    fn current_is(&self, kind: TokenKind) -> bool {
+        return self.current_token.as_ref().is_some_and(|t| t.kind == kind);
+    }
+    fn current_is_operator(&self, operator: &str) -> bool {
+        if let Some(Token {
+            kind: TokenKind::Operator(o),
+            ..
+        }) = &self.current_token
+        {
+            return o.to_uppercase() == operator;
+        }
+        return false;
+    }
+    /// returns true if the current token ends the current statement
+    /// `ELSE` and `ELSEIF` end the statement of the `THEN` in front of them
+    fn at_statement_end(&self) -> bool {
+        return match &self.current_token {
+            Some(token) => {
+                matches!(
+                    token.kind,
+                    TokenKind::Newline | TokenKind::Semicolon | TokenKind::EOF
+                ) || Self::is_keyword(token, "ELSE")
+                    || Self::is_keyword(token, "ELSEIF")
+            }
+            None => true,
+        };
+    }
+    /// returns true if the current token ends an argument list. Inside a `MENU` a comma followed by a statement starts the next menu item
+    fn at_argument_end(&self) -> bool {
+        if self.at_statement_end() {
+            return true;
+        }
+        let current = self.current_token.as_ref().unwrap();
+        if matches!(current.kind, TokenKind::ParenClose | TokenKind::BrackClose) {
+            return true;
+        }
+        return self.in_menu
+            && current.kind == TokenKind::Comma
+            && self.peek(0).is_some_and(|t| Self::starts_statement(&t));
+    }
+    /// returns true if the token is a keyword that starts a statement rather than an expression
+    fn starts_statement(token: &Token) -> bool {
+        let TokenKind::Keyword(keyword) = &token.kind else {
+            return false;
+        };
+        if Self::positional_expression(keyword).is_some() {
+            return false;
+        }
+        return !matches!(
+            keyword.to_uppercase().as_str(),
+            "TRUE" | "FALSE" | "POPTIONS" | "POP" | "HAS" | "KEYS"
+        );
+    }
+    /// the positional stdlib function a keyword calls if it evaluates to a value, e.g. `INPUT`
+    fn positional_expression(keyword: &str) -> Option<&'static StdLibFunction> {
+        return find_function(keyword)
+            .filter(|f| f.is_positional() && f.return_data_type().is_some());
+    }
+    /// consume the current token if it has the given kind, erroring with `expected` otherwise
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> Result<Token, Vec<ParserError>> {
+        if let Some(token) = self.current_token.clone()
+            && token.kind == kind
+        {
+            self.advance();
+            return Ok(token);
+        }
+        return Err(vec![ParserError::MissingToken {
+            span: self.current_span(),
+            expected: expected.into(),
+        }]);
+    }
+    fn expect_keyword(&mut self, keyword: &str) -> Result<Token, Vec<ParserError>> {
+        if self.current_is_keyword(keyword) {
+            let token = self.current_token.clone().unwrap();
+            self.advance();
+            return Ok(token);
+        }
+        return Err(vec![ParserError::MissingToken {
+            span: self.current_span(),
+            expected: keyword.into(),
+        }]);
+    }
+    fn expect_operator(&mut self, operator: &str) -> Result<Token, Vec<ParserError>> {
+        if self.current_is_operator(operator) {
+            let token = self.current_token.clone().unwrap();
+            self.advance();
+            return Ok(token);
+        }
+        return Err(vec![ParserError::MissingToken {
+            span: self.current_span(),
+            expected: operator.into(),
+        }]);
+    }
+    fn expect_identifier(&mut self) -> Result<IdentifierNode, Vec<ParserError>> {
+        if let Some(token) = self.current_token.clone()
+            && let TokenKind::Identifier(name, _) = &token.kind
+        {
+            self.advance();
+            return Ok(IdentifierNode {
+                name: name.to_uppercase(),
+                token,
+            });
+        }
+        return Err(vec![ParserError::MissingToken {
+            span: self.current_span(),
+            expected: "identifier".into(),
+        }]);
+    }
+    /// the name of a record field, which can be a keyword as it always follows a `.` e.g. `p.home`
+    fn expect_field(&mut self) -> Result<IdentifierNode, Vec<ParserError>> {
+        if let Some(token) = self.current_token.clone()
+            && let TokenKind::Keyword(keyword) = &token.kind
+        {
+            self.advance();
+            return Ok(IdentifierNode {
+                name: keyword.to_uppercase(),
+                token,
+            });
+        }
+        return self.expect_identifier();
+    }
+    /// skip the rest of the current line, used to recover after a syntax error
+    fn skip_line(&mut self) {
+        while let Some(token) = &self.current_token {
+            match token.kind {
+                TokenKind::EOF => break,
+                TokenKind::Newline => {
+                    self.advance();
+                    break;
+                }
+                _ => self.advance(),
+            }
+        }
+    }
     pub fn parse(&mut self) -> Result<AstNode, Vec<ParserError>> {
         let root = match self.evaluation_context {
             EvaluationContext::Imported | EvaluationContext::FileLevel => {
-                AstNode::STATEMENT(Statement::SCOPE(ScopeNode {
-                    statements: Vec::new(),
-                }))
+                let result = self.parse_lines(&[]);
+                let label_errors = self.undefined_labels();
+                let mut statements = match result {
+                    Ok(statements) if label_errors.is_empty() => statements,
+                    Ok(_) => return Err(label_errors),
+                    Err(mut errors) => {
+                        errors.extend(label_errors);
+                        return Err(errors);
+                    }
+                };
+                self.resolve_labels(&mut statements);
+                AstNode::STATEMENT(Statement::SCOPE(ScopeNode { statements }))
             }
             EvaluationContext::Nested => todo!(),
         };
 
         return Ok(root);
     }
-    fn get_expression_handlers(&self) -> Vec<ExpressionHandler> {
-        return vec![Self::handle_single_constant];
+    /// the jumps to labels that aren't declared anywhere in the file, including the jumps of lines with other errors
+    fn undefined_labels(&self) -> Vec<ParserError> {
+        return self
+            .label_jumps
+            .iter()
+            .filter_map(|token| match &token.kind {
+                TokenKind::Label(label) if !self.labels.contains_key(&label.to_uppercase()) => {
+                    Some(ParserError::UndefinedLabel {
+                        span: token.position_span.clone(),
+                        label: label.clone(),
+                    })
+                }
+                _ => None,
+            })
+            .collect();
     }
-    fn handle_single_constant(&mut self, token: Token) -> Result<Option<Expression>, ParserError> {
-        if let TokenKind::Number(num) = &token.kind {
-            if let Some(op) = self.peek(1) {
-                if let TokenKind::Operator(_) = op.kind {
-                    return Ok(None);
+    /// point every jump to a label at the line number of its declaration, labels can be declared after the jumps using them.
+    /// Every label has been checked to be declared by [`Self::undefined_labels`]
+    fn resolve_labels(&self, statements: &mut [Statement]) {
+        for statement in statements {
+            match statement {
+                Statement::GOTO(jump)
+                | Statement::GOSUB(jump)
+                | Statement::RESTORE(RestoreNode {
+                    target: Some(jump), ..
+                }) => {
+                    if let TokenKind::Label(label) = &jump.token.kind
+                        && let Some(line_number) = self.labels.get(&label.to_uppercase())
+                    {
+                        jump.line_number = *line_number;
+                    }
+                }
+                Statement::SCOPE(scope) => self.resolve_labels(&mut scope.statements),
+                Statement::LOOP(node) => self.resolve_labels(&mut node.body),
+                Statement::MENU(menu) => self.resolve_labels(&mut menu.actions),
+                Statement::ONERR(handler) => self.resolve_labels(&mut handler.actions),
+                Statement::FUNCTION(declaration) => self.resolve_labels(&mut declaration.body),
+                Statement::IF(if_statement) => {
+                    self.resolve_labels(std::slice::from_mut(&mut if_statement.action));
+                    if let Some(else_action) = &mut if_statement.else_action {
+                        self.resolve_labels(std::slice::from_mut(else_action));
+                    }
+                }
+                Statement::SELECT(select) => {
+                    let bodies = select
+                        .cases
+                        .iter_mut()
+                        .map(|c| &mut c.body)
+                        .chain(&mut select.else_body);
+                    for body in bodies {
+                        self.resolve_labels(body);
+                    }
                 }
+                _ => (),
             }
-            let val: i64 = num.parse().unwrap();
         }
-        return Ok(None);
     }
-    fn parse_expression(&mut self) -> Result<Expression, Vec<ParserError>> {
+    /// returns true if the current token starts the closing keyword, which can be made of multiple keywords e.g. `END SELECT`
+    fn current_is_closing(&self, closing_keyword: &str) -> bool {
+        return closing_keyword
+            .split(' ')
+            .enumerate()
+            .all(|(index, keyword)| {
+                if index == 0 {
+                    return self.current_is_keyword(keyword);
+                }
+                return self
+                    .peek(index - 1)
+                    .is_some_and(|t| Self::is_keyword(&t, keyword));
+            });
+    }
+    /// parse lines until the end of the input or until a line starting with one of the closing keywords is found, which is left to the caller.
+    /// Every line is prefixed with a [`Statement::LINE`] holding either its explicit or inferred line number, including the closing line
+    fn parse_lines(
+        &mut self,
+        closing_keywords: &[&str],
+    ) -> Result<Vec<Statement>, Vec<ParserError>> {
+        let mut statements = Vec::new();
         let mut errors: Vec<ParserError> = Vec::new();
-        let mut expression: Option<Expression> = None;
-        let span: Span;
-        let handlers = self.get_expression_handlers();
-        if let Some(token) = self.current_token.clone() {
-            span = token.position_span.clone();
-            for handler in handlers {
-                let handled = handler(self, token.clone());
-                match handled {
-                    Ok(e) => {
-                        if let Some(_expression) = e {
-                            expression = Some(_expression);
-                            break;
-                        }
-                    }
-                    Err(e) => errors.push(e),
+        while let Some(token) = self.current_token.clone() {
+            match &token.kind {
+                TokenKind::EOF => break,
+                TokenKind::Newline | TokenKind::Semicolon => {
+                    self.advance();
+                    continue;
+                }
+                _ => (),
+            }
+
+            let mut line_number = self.line_number + 1;
+            if let TokenKind::Number(num) = &token.kind {
+                match num.parse::<usize>() {
+                    Ok(n) => line_number = n,
+                    Err(_) => errors.push(ParserError::InvalidCode {
+                        span: token.position_span.clone(),
+                    }),
+                }
+                self.advance();
+            }
+            self.line_number = line_number;
+            statements.push(Statement::LINE(LineNode { line_number, token }));
+            match self.parse_label_declaration(line_number) {
+                Ok(true) if self.at_statement_end() => continue,
+                Ok(_) => (),
+                Err(e) => errors.extend(e),
+            }
+
+            if closing_keywords.iter().any(|k| self.current_is_closing(k)) {
+                if !errors.is_empty() {
+                    return Err(errors);
                 }
+                return Ok(statements);
             }
-            if let None = expression {
-                errors.push(ParserError::UnexpectedToken { span, token });
+
+            match self.parse_line() {
+                Ok(line) => statements.extend(line),
+                Err(e) => {
+                    errors.extend(e);
+                    self.skip_line();
+                }
             }
         }
-        if errors.len() > 0 {
+
+        if !closing_keywords.is_empty() {
+            errors.push(ParserError::MissingToken {
+                span: self.current_span(),
+                expected: closing_keywords.join(" or "),
+            });
+        }
+        if !errors.is_empty() {
             return Err(errors);
         }
+        return Ok(statements);
+    }
+    /// `@<label>:` at the start of a line names the line, returns whether there was a label
+    fn parse_label_declaration(&mut self, line_number: usize) -> Result<bool, Vec<ParserError>> {
+        let Some(token) = self.current_token.clone() else {
+            return Ok(false);
+        };
+        let TokenKind::Label(label) = &token.kind else {
+            return Ok(false);
+        };
+        if !self.peek(0).is_some_and(|t| t.kind == TokenKind::Colon) {
+            return Ok(false);
+        }
+        self.consume(2);
+        if self
+            .labels
+            .insert(label.to_uppercase(), line_number)
+            .is_some()
+        {
+            return Err(vec![ParserError::DuplicateLabel {
+                span: token.position_span,
+                label: label.clone(),
+            }]);
+        }
+        return Ok(true);
+    }
+    /// parse a `;` separated list of statements up to the end of the line
+    fn parse_line(&mut self) -> Result<Vec<Statement>, Vec<ParserError>> {
+        let mut statements = Vec::new();
+        loop {
+            statements.push(self.parse_statement()?);
+            match self.current_token.as_ref().map(|t| &t.kind) {
+                Some(TokenKind::Semicolon) => self.advance(),
+                Some(TokenKind::Newline) => {
+                    self.advance();
+                    break;
+                }
+                Some(TokenKind::EOF) | None => break,
+                Some(_) => {
+                    let span = self.current_span();
+                    let mut extra_tokens = Vec::new();
+                    while !self.at_statement_end() {
+                        extra_tokens.extend(self.consume(1));
+                    }
+                    return Err(vec![ParserError::ExtraCode { span, extra_tokens }]);
+                }
+            }
+        }
+        return Ok(statements);
+    }
+    /// get all the statement handlers, ordered by precedence like the lexers handlers
+    fn get_statement_handlers(&self) -> Vec<StatementHandler> {
+        return vec![
+            Self::handle_scope,
+            Self::handle_let,
+            Self::handle_dim,
+            Self::handle_def,
+            Self::handle_enum,
+            Self::handle_record,
+            Self::handle_function,
+            Self::handle_for,
+            Self::handle_while,
+            Self::handle_do,
+            Self::handle_loop_jump,
+            Self::handle_clear,
+            Self::handle_if,
+            Self::handle_select,
+            Self::handle_collection_statement,
+            Self::handle_penum,
+            Self::handle_menu,
+            Self::handle_jump,
+            Self::handle_return,
+            Self::handle_end,
+            Self::handle_stop,
+            Self::handle_import,
+            Self::handle_screen,
+            Self::handle_data,
+            Self::handle_read_data,
+            Self::handle_restore,
+            Self::handle_print,
+            Self::handle_input,
+            Self::handle_onerr,
+            Self::handle_throw,
+            Self::handle_positional_call,
+            Self::handle_assignment,
+        ];
+    }
+    fn parse_statement(&mut self) -> Result<Statement, Vec<ParserError>> {
+        let token = match self.current_token.clone() {
+            Some(t) => t,
+            None => {
+                return Err(vec![ParserError::InvalidCode {
+                    span: self.current_span(),
+                }]);
+            }
+        };
+        for handler in self.get_statement_handlers() {
+            if let Some(statement) = handler(self, token.clone())? {
+                return Ok(statement);
+            }
+        }
+        return Err(vec![ParserError::UnexpectedToken {
+            span: token.position_span.clone(),
+            token,
+        }]);
+    }
+    fn handle_scope(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "BEGIN") {
+            return Ok(None);
+        }
+        self.advance();
+        return Ok(Some(Statement::SCOPE(self.parse_scope()?)));
+    }
+    /// parse the lines of a scope whose `BEGIN` has already been consumed, up to and including its `FIN`
+    fn parse_scope(&mut self) -> Result<ScopeNode, Vec<ParserError>> {
+        if !self.at_statement_end() {
+            return Err(vec![ParserError::UnexpectedToken {
+                span: self.current_span(),
+                token: self.current_token.clone().unwrap(),
+            }]);
+        }
+        return Ok(ScopeNode {
+            statements: self.parse_block("FIN")?,
+        });
+    }
+    /// `LET <name> [AS <type>] [= <expression> [AS <type>]]`
+    fn handle_let(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "LET") {
+            return Ok(None);
+        }
+        self.advance();
+        let variable_name = self.expect_identifier()?;
+        let mut data_type = None;
+        if self.current_is_keyword("AS") {
+            self.advance();
+            data_type = Some(self.parse_type_annotation()?);
+        }
+        let mut initial_value = None;
+        if self.current_is_operator("=") {
+            self.advance();
+            initial_value = Some(Box::new(self.parse_expression()?));
+            if self.current_is_keyword("AS") {
+                let as_token = self.current_token.clone().unwrap();
+                self.advance();
+                if data_type.is_some() {
+                    return Err(vec![ParserError::InvalidCode {
+                        span: as_token.position_span,
+                    }]);
+                }
+                data_type = Some(self.parse_type_annotation()?);
+            }
+        }
+        // a declaration has to be annotated or assigned for its type to be known
+        if data_type.is_none() && initial_value.is_none() {
+            return Err(vec![ParserError::MissingToken {
+                span: self.current_span(),
+                expected: "AS or =".into(),
+            }]);
+        }
 
+        return Ok(Some(Statement::VAR_DECL(VariableDeclarationNode {
+            variable_name,
+            data_type,
+            is_constant: false,
+            initial_value,
+        })));
+    }
+    /// `DIM <name> (<columns> [, <rows>]) AS <type>`, unlike `LET` there is no value to infer the type from
+    fn handle_dim(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "DIM") {
+            return Ok(None);
+        }
+        self.advance();
+        let variable_name = self.expect_identifier()?;
+        self.expect(TokenKind::ParenOpen, "(")?;
+        let columns = Box::new(self.parse_expression()?);
+        let mut rows = None;
+        if self.current_is(TokenKind::Comma) {
+            self.advance();
+            rows = Some(Box::new(self.parse_expression()?));
+        }
+        self.expect(TokenKind::ParenClose, ")")?;
+        self.expect_keyword("AS")?;
+        let element_type = self.parse_type_annotation()?;
+        return Ok(Some(Statement::DIM(DimNode {
+            variable_name,
+            columns,
+            rows,
+            element_type,
+            token,
+        })));
+    }
+    /// `DEF <name> [<param1>, <param2>, ...] = <expression>`, the parameters can be put in parentheses like the arguments of a call
+    fn handle_def(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "DEF") {
+            return Ok(None);
+        }
+        self.advance();
+        let name = self.expect_identifier()?;
+        let enclosed = self.current_is(TokenKind::ParenOpen);
+        if enclosed {
+            self.advance();
+        }
+        let mut parameters = Vec::new();
+        while !self.current_is_operator("=") && !self.current_is(TokenKind::ParenClose) {
+            if !parameters.is_empty() {
+                self.expect(TokenKind::Comma, ",")?;
+            }
+            parameters.push(self.expect_identifier()?);
+        }
+        if enclosed {
+            self.expect(TokenKind::ParenClose, ")")?;
+        }
+        if !self.current_is_operator("=") {
+            return Err(vec![ParserError::MissingToken {
+                span: self.current_span(),
+                expected: "=".into(),
+            }]);
+        }
+        self.advance();
+        let body = Box::new(self.parse_expression()?);
+        return Ok(Some(Statement::DEF(DefNode {
+            name,
+            parameters,
+            body,
+            token,
+        })));
+    }
+    /// parse the type following an `AS`. Any name that is not a builtin type or a record declared before is assumed to be an enum.
+    /// Collections are `LIST OF <type>` and `MAP OF STRING TO <type>`
+    fn parse_type_annotation(&mut self) -> Result<DataType, Vec<ParserError>> {
+        if self.current_is_keyword("DATE") {
+            self.advance();
+            return Ok(DataType::DATE);
+        }
+        if self.current_is_keyword("LIST") {
+            self.advance();
+            self.expect_keyword("OF")?;
+            return Ok(DataType::LIST(Box::new(self.parse_type_annotation()?)));
+        }
+        if self.current_is_keyword("MAP") {
+            self.advance();
+            self.expect_keyword("OF")?;
+            // keys are always strings
+            let key = self.expect_identifier()?;
+            if key.name != "STRING" {
+                return Err(vec![ParserError::MissingToken {
+                    span: key.token.position_span,
+                    expected: "STRING".into(),
+                }]);
+            }
+            self.expect_keyword("TO")?;
+            return Ok(DataType::MAP(Box::new(self.parse_type_annotation()?)));
+        }
+        let identifier = self.expect_identifier()?;
+        return Ok(match identifier.name.as_str() {
+            "INT" | "INTEGER" => DataType::INT,
+            "FLOAT" => DataType::FLOAT,
+            "STRING" => DataType::STRING,
+            "BOOL" | "BOOLEAN" => DataType::BOOL,
+            _ if self.records.contains(&identifier.name) => DataType::RECORD(identifier.name),
+            _ => DataType::ENUM(identifier.name),
+        });
+    }
+    /// `RECORD <name>`, a `<field> AS <type>` line per field and `FIN`. A field can be a record declared before
+    fn handle_record(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "RECORD") {
+            return Ok(None);
+        }
+        self.advance();
+        let name = self.expect_identifier()?;
+        let mut fields = Vec::new();
+        loop {
+            if !self.at_statement_end() {
+                return Err(vec![ParserError::UnexpectedToken {
+                    span: self.current_span(),
+                    token: self.current_token.clone().unwrap(),
+                }]);
+            }
+            while self.current_is(TokenKind::Newline) || self.current_is(TokenKind::Semicolon) {
+                self.advance();
+            }
+            if self.current_is_keyword("FIN") {
+                self.advance();
+                break;
+            }
+            if self.current_is(TokenKind::EOF) || self.current_token.is_none() {
+                return Err(vec![ParserError::MissingToken {
+                    span: self.current_span(),
+                    expected: "FIN".into(),
+                }]);
+            }
+            let field = self.expect_field()?;
+            self.expect_keyword("AS")?;
+            fields.push(RecordFieldNode {
+                name: field,
+                data_type: self.parse_type_annotation()?,
+            });
+        }
+        self.records.insert(name.name.clone());
+        return Ok(Some(Statement::RECORD(RecordDeclarationNode {
+            name,
+            fields,
+            token,
+        })));
+    }
+    /// `FUNCTION <name>([<parameter> AS <type>, ...]) [AS <type>]` followed by the lines of its body up to `FIN`
+    fn handle_function(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "FUNCTION") {
+            return Ok(None);
+        }
+        self.advance();
+        let mut name = self.expect_identifier()?;
+        // calls drop the `$` of the name like they do for STR$ and the like
+        name.name = name.name.trim_end_matches('$').into();
+        self.expect(TokenKind::ParenOpen, "(")?;
+        let mut parameters = Vec::new();
+        while !self.current_is(TokenKind::ParenClose) {
+            if !parameters.is_empty() {
+                self.expect(TokenKind::Comma, ",")?;
+            }
+            let name = self.expect_identifier()?;
+            self.expect_keyword("AS")?;
+            parameters.push(ParameterNode {
+                name,
+                data_type: self.parse_type_annotation()?,
+            });
+        }
+        self.advance();
+        let mut return_type = None;
+        if self.current_is_keyword("AS") {
+            self.advance();
+            return_type = Some(self.parse_type_annotation()?);
+        }
+        if !self.at_statement_end() {
+            return Err(vec![ParserError::UnexpectedToken {
+                span: self.current_span(),
+                token: self.current_token.clone().unwrap(),
+            }]);
+        }
+        return Ok(Some(Statement::FUNCTION(FunctionDeclarationNode {
+            name,
+            parameters,
+            return_type,
+            body: self.parse_block("FIN")?,
+            token,
+        })));
+    }
+    /// `FOR EACH <item> IN <collection>`, `FOR <counter> = <start> TO <end> [STEP <step>]` or `FOR <counter> = <start>, <condition>, <step>`,
+    /// followed by a scope or the lines up to the closing `NEXT`. The step of a C-style loop is an assignment, e.g. `I += 1`.
+    /// The `NEXT` of a counting loop can name its counter like in AppleSoft, e.g. `NEXT I`
+    fn handle_for(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "FOR") {
+            return Ok(None);
+        }
+        self.advance();
+        if self.current_is_keyword("EACH") {
+            self.advance();
+            let item = self.expect_identifier()?;
+            self.expect_keyword("IN")?;
+            let collection = Box::new(self.parse_expression()?);
+            let body = self.parse_loop_body("NEXT")?;
+            return Ok(Some(Statement::LOOP(LoopNode {
+                kind: LoopKind::EACH { item, collection },
+                body,
+                token,
+            })));
+        }
+        let counter = self.expect_identifier()?;
+        self.expect_operator("=")?;
+        let start = Box::new(self.parse_expression()?);
+        if self.current_is(TokenKind::Comma) {
+            self.advance();
+            let condition = Box::new(self.parse_expression()?);
+            self.expect(TokenKind::Comma, ",")?;
+            let step_token = self.current_token.clone().unwrap();
+            let Some(Statement::ASSIGN(step)) = self.handle_assignment(step_token.clone())? else {
+                return Err(vec![ParserError::UnexpectedToken {
+                    span: step_token.position_span.clone(),
+                    token: step_token,
+                }]);
+            };
+            let body = self.parse_loop_body("NEXT")?;
+            return Ok(Some(Statement::LOOP(LoopNode {
+                kind: LoopKind::CSTYLE {
+                    counter,
+                    start,
+                    condition,
+                    step: Box::new(step),
+                },
+                body,
+                token,
+            })));
+        }
+        self.expect_keyword("TO")?;
+        let end = Box::new(self.parse_expression()?);
+        let mut step = None;
+        if self.current_is_keyword("STEP") {
+            self.advance();
+            step = Some(Box::new(self.parse_expression()?));
+        }
+        let body = self.parse_loop_body("NEXT")?;
+        if matches!(
+            self.current_token.as_ref().map(|t| &t.kind),
+            Some(TokenKind::Identifier(..))
+        ) {
+            let next = self.expect_identifier()?;
+            if next.name != counter.name {
+                return Err(vec![ParserError::UnexpectedToken {
+                    span: next.token.position_span.clone(),
+                    token: next.token,
+                }]);
+            }
+        }
+        return Ok(Some(Statement::LOOP(LoopNode {
+            kind: LoopKind::TO {
+                counter,
+                start,
+                end,
+                step,
+            },
+            body,
+            token,
+        })));
+    }
+    /// `WHILE <condition>` followed by a scope or the lines up to the closing `WEND`
+    fn handle_while(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "WHILE") {
+            return Ok(None);
+        }
+        self.advance();
+        let condition = Box::new(self.parse_expression()?);
+        let body = self.parse_loop_body("WEND")?;
+        return Ok(Some(Statement::LOOP(LoopNode {
+            kind: LoopKind::WHILE { condition },
+            body,
+            token,
+        })));
+    }
+    /// `DO`, the lines of the body and `LOOP UNTIL <condition>`
+    fn handle_do(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "DO") {
+            return Ok(None);
+        }
+        self.advance();
+        if !self.at_statement_end() {
+            return Err(vec![ParserError::UnexpectedToken {
+                span: self.current_span(),
+                token: self.current_token.clone().unwrap(),
+            }]);
+        }
+        let body = self.parse_block("LOOP")?;
+        self.expect_keyword("UNTIL")?;
+        let condition = Box::new(self.parse_expression()?);
+        return Ok(Some(Statement::LOOP(LoopNode {
+            kind: LoopKind::UNTIL { condition },
+            body,
+            token,
+        })));
+    }
+    /// `EXIT` or `CONTINUE`, whether they are inside of a loop is up to the checker
+    fn handle_loop_jump(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        let is_exit = Self::is_keyword(&token, "EXIT");
+        if !is_exit && !Self::is_keyword(&token, "CONTINUE") {
+            return Ok(None);
+        }
+        self.advance();
+        let jump = LoopJumpNode { token };
+        if is_exit {
+            return Ok(Some(Statement::EXIT(jump)));
+        }
+        return Ok(Some(Statement::CONTINUE(jump)));
+    }
+    /// `CLEAR` or `CSCOPE`
+    fn handle_clear(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        let is_clear = Self::is_keyword(&token, "CLEAR");
+        if !is_clear && !Self::is_keyword(&token, "CSCOPE") {
+            return Ok(None);
+        }
+        self.advance();
+        let clear = ClearNode { token };
+        if is_clear {
+            return Ok(Some(Statement::CLEAR(clear)));
+        }
+        return Ok(Some(Statement::CSCOPE(clear)));
+    }
+    /// `IF <condition> {THEN <statement> | GOTO <line> | GOSUB <line> | <scope>}`, optionally followed by
+    /// `ELSEIF` and another `IF` without the `IF` or `ELSE {<statement> | <scope>}` on the same line
+    fn handle_if(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "IF") {
+            return Ok(None);
+        }
+        self.advance();
+        return Ok(Some(Statement::IF(self.parse_if()?)));
+    }
+    /// the rest of an `IF` or `ELSEIF` after its keyword
+    fn parse_if(&mut self) -> Result<IfStatementNode, Vec<ParserError>> {
+        let condition = Box::new(self.parse_expression()?);
+        if self.current_is_keyword("THEN") {
+            self.advance();
+        } else if !["GOTO", "GOSUB", "BEGIN"]
+            .iter()
+            .any(|k| self.current_is_keyword(k))
+        {
+            return Err(vec![ParserError::MissingToken {
+                span: self.current_span(),
+                expected: "THEN".into(),
+            }]);
+        }
+        let action = Box::new(self.parse_statement()?);
+        let else_action = if self.current_is_keyword("ELSEIF") {
+            self.advance();
+            Some(Box::new(Statement::IF(self.parse_if()?)))
+        } else if self.current_is_keyword("ELSE") {
+            self.advance();
+            Some(Box::new(self.parse_statement()?))
+        } else {
+            None
+        };
+        return Ok(IfStatementNode {
+            condition,
+            action,
+            else_action,
+        });
+    }
+    /// `SELECT CASE <subject>`, every `CASE <condition1> [, <condition2>, ...]` line followed by the lines it runs,
+    /// an optional `CASE ELSE` and `END SELECT`
+    fn handle_select(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        const CLOSING_KEYWORDS: [&str; 2] = ["CASE", "END SELECT"];
+        if !Self::is_keyword(&token, "SELECT") {
+            return Ok(None);
+        }
+        self.advance();
+        self.expect_keyword("CASE")?;
+        let subject = Box::new(self.parse_expression()?);
+        if !self.at_statement_end() {
+            return Err(vec![ParserError::UnexpectedToken {
+                span: self.current_span(),
+                token: self.current_token.clone().unwrap(),
+            }]);
+        }
+        // every body ends with the line of the CASE after it, which starts that case instead
+        let mut case_line = self.parse_lines(&CLOSING_KEYWORDS)?;
+        if case_line.iter().any(|s| !matches!(s, Statement::LINE(_))) {
+            return Err(vec![ParserError::MissingToken {
+                span: token.position_span,
+                expected: "CASE".into(),
+            }]);
+        }
+        let mut cases = Vec::new();
+        let mut else_body: Option<Vec<Statement>> = None;
+        while !self.current_is_closing("END SELECT") {
+            let case_token = self.expect_keyword("CASE")?;
+            if else_body.is_some() {
+                // CASE ELSE has to be the last case
+                return Err(vec![ParserError::UnexpectedToken {
+                    span: case_token.position_span.clone(),
+                    token: case_token,
+                }]);
+            }
+            let is_else = self.current_is_keyword("ELSE");
+            let mut conditions = Vec::new();
+            if is_else {
+                self.advance();
+            } else {
+                conditions.push(self.parse_case_condition()?);
+                while self.current_is(TokenKind::Comma) {
+                    self.advance();
+                    conditions.push(self.parse_case_condition()?);
+                }
+            }
+            let mut body = std::mem::take(&mut case_line);
+            body.extend(self.parse_lines(&CLOSING_KEYWORDS)?);
+            case_line = body.pop().into_iter().collect();
+            if is_else {
+                else_body = Some(body);
+                continue;
+            }
+            cases.push(CaseNode {
+                conditions,
+                body,
+                token: case_token,
+            });
+        }
+        self.consume(2);
+        // the line of END SELECT is the end of the last body
+        match (&mut else_body, cases.last_mut()) {
+            (Some(body), _) | (None, Some(CaseNode { body, .. })) => body.extend(case_line),
+            (None, None) => (),
+        }
+        return Ok(Some(Statement::SELECT(SelectNode {
+            subject,
+            cases,
+            else_body,
+            token,
+        })));
+    }
+    /// `<value>`, `<low> TO <high>` or `IS <comparison> <value>`
+    fn parse_case_condition(&mut self) -> Result<CaseCondition, Vec<ParserError>> {
+        if self.current_is_keyword("IS") {
+            self.advance();
+            let operation = self
+                .current_token
+                .as_ref()
+                .and_then(Self::binary_operator)
+                .filter(|o| o.is_comparison());
+            let Some(operation) = operation else {
+                return Err(vec![ParserError::MissingToken {
+                    span: self.current_span(),
+                    expected: "comparison".into(),
+                }]);
+            };
+            self.advance();
+            return Ok(CaseCondition::IS(operation, self.parse_expression()?));
+        }
+        let value = self.parse_expression()?;
+        if self.current_is_keyword("TO") {
+            self.advance();
+            return Ok(CaseCondition::RANGE(value, self.parse_expression()?));
+        }
+        return Ok(CaseCondition::VALUE(value));
+    }
+    /// the body of a loop whose header has already been parsed, either `BEGIN` up to `FIN` or the following lines up to the closing keyword
+    fn parse_loop_body(
+        &mut self,
+        closing_keyword: &str,
+    ) -> Result<Vec<Statement>, Vec<ParserError>> {
+        if self.current_is_keyword("BEGIN") {
+            self.advance();
+            return Ok(self.parse_scope()?.statements);
+        }
+        if !self.at_statement_end() {
+            return Err(vec![ParserError::UnexpectedToken {
+                span: self.current_span(),
+                token: self.current_token.clone().unwrap(),
+            }]);
+        }
+        return self.parse_block(closing_keyword);
+    }
+    /// the lines up to and including the closing keyword
+    fn parse_block(&mut self, closing_keyword: &str) -> Result<Vec<Statement>, Vec<ParserError>> {
+        let statements = self.parse_lines(&[closing_keyword])?;
+        self.advance();
+        return Ok(statements);
+    }
+    /// the operation a keyword performs on a collection
+    fn collection_operation(token: &Token) -> Option<CollectionOpKind> {
+        let TokenKind::Keyword(keyword) = &token.kind else {
+            return None;
+        };
+        return match keyword.to_uppercase().as_str() {
+            "PUSH" => Some(CollectionOpKind::PUSH),
+            "POP" => Some(CollectionOpKind::POP),
+            "INSERT" => Some(CollectionOpKind::INSERT),
+            "REMOVE" => Some(CollectionOpKind::REMOVE),
+            "HAS" => Some(CollectionOpKind::HAS),
+            "KEYS" => Some(CollectionOpKind::KEYS),
+            _ => None,
+        };
+    }
+    /// the arguments of a collection operation, either enclosed e.g. `HAS(m, "a")` or positional e.g. `PUSH l, 5`
+    fn parse_collection(
+        &mut self,
+        operation: CollectionOpKind,
+        token: Token,
+        operand: bool,
+    ) -> Result<CollectionNode, Vec<ParserError>> {
+        self.advance();
+        let arguments = if self.current_is(TokenKind::ParenOpen) {
+            self.parse_enclosed_arguments()?
+        } else {
+            let keyword = format!("{:?}", operation);
+            self.parse_positional_arguments(&keyword, Some(operation.arity()), operand)?
+        };
+        return Ok(CollectionNode {
+            operation,
+            arguments,
+            token,
+        });
+    }
+    /// `PUSH`, `POP`, `INSERT` and `REMOVE`, which change the collection they are given
+    fn handle_collection_statement(
+        &mut self,
+        token: Token,
+    ) -> Result<Option<Statement>, Vec<ParserError>> {
+        let operation = match Self::collection_operation(&token) {
+            // HAS and KEYS only evaluate to a value
+            Some(CollectionOpKind::HAS | CollectionOpKind::KEYS) | None => return Ok(None),
+            Some(op) => op,
+        };
+        return Ok(Some(Statement::COLLECTION(
+            self.parse_collection(operation, token, false)?,
+        )));
+    }
+    /// `ENUM <name> = <key1 [value1]> [, ...]`
+    fn handle_enum(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "ENUM") {
+            return Ok(None);
+        }
+        self.advance();
+        let name = self.expect_identifier()?;
+        self.expect_operator("=")?;
+        let mut members = Vec::new();
+        loop {
+            let member_name = self.expect_identifier()?;
+            let mut value = None;
+            if !self.at_statement_end()
+                && self.current_token.as_ref().unwrap().kind != TokenKind::Comma
+            {
+                value = Some(self.parse_integer_literal()?);
+            }
+            members.push(EnumMemberNode {
+                name: member_name,
+                value,
+            });
+            if self.at_statement_end() {
+                break;
+            }
+            self.expect(TokenKind::Comma, ",")?;
+        }
+
+        return Ok(Some(Statement::ENUM(EnumDeclarationNode { name, members })));
+    }
+    /// parse an optionally negated integer literal
+    fn parse_integer_literal(&mut self) -> Result<i64, Vec<ParserError>> {
+        let negative = self.current_is_operator("-");
+        if negative {
+            self.advance();
+        }
+        if let Some(token) = self.current_token.clone()
+            && let TokenKind::Number(num) = &token.kind
+        {
+            if let Ok(value) = num.parse::<i64>() {
+                self.advance();
+                return Ok(if negative { -value } else { value });
+            }
+            return Err(vec![ParserError::InvalidCode {
+                span: token.position_span,
+            }]);
+        }
+        return Err(vec![ParserError::MissingToken {
+            span: self.current_span(),
+            expected: "integer".into(),
+        }]);
+    }
+    /// `PENUM <ENUM>, <expression>`
+    fn handle_penum(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "PENUM") {
+            return Ok(None);
+        }
+        self.advance();
+        let enum_name = self.expect_identifier()?;
+        self.expect(TokenKind::Comma, ",")?;
+        let value = Box::new(self.parse_expression()?);
+        return Ok(Some(Statement::PENUM(PrintEnumNode { enum_name, value })));
+    }
+    /// keywords that map onto a positional stdlib function (e.g. `PRINT x`) become a call to that function with the comma separated arguments following it
+    fn handle_positional_call(
+        &mut self,
+        token: Token,
+    ) -> Result<Option<Statement>, Vec<ParserError>> {
+        let function = match &token.kind {
+            TokenKind::Keyword(k) => match find_function(k) {
+                Some(f) if f.is_positional() => f,
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        self.advance();
+        return Ok(Some(Statement::FUNC_CALL(FunctionCallNode {
+            name: function.usb_name(),
+            arguments: self.parse_positional_arguments(&function.usb_name(), None, false)?,
+            token,
+        })));
+    }
+    /// parse the comma separated arguments following a keyword, e.g. the `"a", B` of `PRINT "a", B`.
+    /// Parsing stops after `limit` arguments so the comma after a nested call belongs to the enclosing call e.g. `FORMATDATE(DATE + DAY 1, "%d")`.
+    /// The arguments of an `operand` call, one inside an expression, stop at the next binary operator so `HOUR 2 + MINUTE 30` adds two calls
+    fn parse_positional_arguments(
+        &mut self,
+        keyword: &str,
+        limit: Option<usize>,
+        operand: bool,
+    ) -> Result<Vec<Expression>, Vec<ParserError>> {
+        let mut arguments = Vec::new();
+        while !self.at_argument_end() && limit.is_none_or(|limit| arguments.len() < limit) {
+            let after_descriptor = arguments.len() == 1 && FILE_KEYWORDS.contains(&keyword);
+            if after_descriptor && !self.current_is(TokenKind::Comma) {
+                // the comma after a file descriptor is optional e.g. `WRITE F "Hello"`
+            } else if !arguments.is_empty() {
+                self.expect(TokenKind::Comma, ",")?;
+            }
+            arguments.push(if operand {
+                self.parse_unary()?
+            } else {
+                self.parse_expression()?
+            });
+        }
+        return Ok(arguments);
+    }
+    /// `MENU <expression> {<statement1> [, <statement2>, ...] | {GOTO | GOSUB} linenumber1 [, linenumber2, ...]}`
+    fn handle_menu(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "MENU") {
+            return Ok(None);
+        }
+        self.advance();
+        let selector = Box::new(self.parse_expression()?);
+        let in_menu = self.in_menu;
+        self.in_menu = true;
+        let actions = self.parse_menu_actions();
+        self.in_menu = in_menu;
+        return Ok(Some(Statement::MENU(MenuNode {
+            selector,
+            actions: actions?,
+            token,
+        })));
+    }
+    fn parse_menu_actions(&mut self) -> Result<Vec<Statement>, Vec<ParserError>> {
+        let mut actions = vec![self.parse_statement()?];
+        while !self.at_statement_end() {
+            self.expect(TokenKind::Comma, ",")?;
+            // a line number or label continues the GOTO or GOSUB list before it
+            let repeated = match (actions.last(), &self.current_token) {
+                (
+                    Some(Statement::GOTO(_) | Statement::GOSUB(_)),
+                    Some(Token {
+                        kind: TokenKind::Number(_) | TokenKind::Label(_),
+                        ..
+                    }),
+                ) => Some(actions.last().unwrap().clone()),
+                _ => None,
+            };
+            let action = match repeated {
+                Some(Statement::GOTO(jump)) => {
+                    Statement::GOTO(self.parse_jump_target(jump.module)?)
+                }
+                Some(Statement::GOSUB(jump)) => {
+                    Statement::GOSUB(self.parse_jump_target(jump.module)?)
+                }
+                _ => self.parse_statement()?,
+            };
+            actions.push(action);
+        }
+        return Ok(actions);
+    }
+    /// `GOTO { linenumber | @label | alias, linenumber }` or the same for `GOSUB`
+    fn handle_jump(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        let is_goto = Self::is_keyword(&token, "GOTO");
+        if !is_goto && !Self::is_keyword(&token, "GOSUB") {
+            return Ok(None);
+        }
+        self.advance();
+        let mut module = None;
+        if matches!(
+            self.current_token.as_ref().map(|t| &t.kind),
+            Some(TokenKind::Identifier(..))
+        ) {
+            module = Some(self.expect_identifier()?);
+            self.expect(TokenKind::Comma, ",")?;
+        }
+        let target = self.parse_jump_target(module)?;
+        return Ok(Some(if is_goto {
+            Statement::GOTO(target)
+        } else {
+            Statement::GOSUB(target)
+        }));
+    }
+    fn parse_jump_target(
+        &mut self,
+        module: Option<IdentifierNode>,
+    ) -> Result<JumpNode, Vec<ParserError>> {
+        if let Some(token) = self.current_token.clone() {
+            // labels only name lines of their own file, the line number is filled in by resolve_labels
+            if matches!(token.kind, TokenKind::Label(_)) && module.is_none() {
+                self.advance();
+                self.label_jumps.push(token.clone());
+                return Ok(JumpNode {
+                    module,
+                    line_number: 0,
+                    token,
+                });
+            }
+            if let TokenKind::Number(num) = &token.kind {
+                self.advance();
+                return match num.parse::<usize>() {
+                    Ok(line_number) => Ok(JumpNode {
+                        module,
+                        line_number,
+                        token,
+                    }),
+                    Err(_) => Err(vec![ParserError::InvalidCode {
+                        span: token.position_span,
+                    }]),
+                };
+            }
+        }
+        let expected = match module {
+            Some(_) => "line number",
+            None => "line number or label",
+        };
+        return Err(vec![ParserError::MissingToken {
+            span: self.current_span(),
+            expected: expected.into(),
+        }]);
+    }
+    /// `RET [expression]`, `RETURN` is accepted for AppleSoft compatibility
+    fn handle_return(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "RET") && !Self::is_keyword(&token, "RETURN") {
+            return Ok(None);
+        }
+        self.advance();
+        let mut return_expression = None;
+        if !self.at_argument_end() {
+            return_expression = Some(Box::new(self.parse_expression()?));
+        }
+        return Ok(Some(Statement::RETURN(ReturnNode {
+            return_expression,
+            token,
+        })));
+    }
+    /// `IMPORT "<path>" AS <alias>`
+    fn handle_import(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "IMPORT") {
+            return Ok(None);
+        }
+        self.advance();
+        let path = match self.current_token.clone() {
+            Some(Token {
+                kind: TokenKind::StringLiteral(literal),
+                ..
+            }) => Self::unescape_string_literal(&literal),
+            _ => {
+                return Err(vec![ParserError::MissingToken {
+                    span: self.current_span(),
+                    expected: "path".into(),
+                }]);
+            }
+        };
+        self.advance();
+        self.expect_keyword("AS")?;
+        let alias = self.expect_identifier()?;
+        return Ok(Some(Statement::IMPORT(ImportNode { path, alias, token })));
+    }
+    /// `SCREEN <columns>, <rows>`
+    fn handle_screen(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "SCREEN") {
+            return Ok(None);
+        }
+        self.advance();
+        let columns = self.expect_identifier()?;
+        self.expect(TokenKind::Comma, ",")?;
+        let rows = self.expect_identifier()?;
+        return Ok(Some(Statement::SCREEN(ScreenNode {
+            columns,
+            rows,
+            token,
+        })));
+    }
+    /// `DATA <constant1> [, <constant2>, ...]`
+    fn handle_data(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "DATA") {
+            return Ok(None);
+        }
+        self.advance();
+        let mut values = vec![self.parse_data_constant()?];
+        while !self.at_argument_end() {
+            self.expect(TokenKind::Comma, ",")?;
+            values.push(self.parse_data_constant()?);
+        }
+        return Ok(Some(Statement::DATA(DataNode { values, token })));
+    }
+    /// a literal of a DATA statement, numbers may be negative
+    fn parse_data_constant(&mut self) -> Result<ConstantNode, Vec<ParserError>> {
+        let negative = self.current_is_operator("-");
+        if negative {
+            self.advance();
+        }
+        let Some(token) = self.current_token.clone() else {
+            return Err(vec![ParserError::MissingToken {
+                span: self.current_span(),
+                expected: "constant".into(),
+            }]);
+        };
+        let mut constant = match self.handle_single_constant(token.clone())? {
+            Some(Expression::CONSTANT(constant)) => constant,
+            _ => {
+                return Err(vec![ParserError::MissingToken {
+                    span: token.position_span,
+                    expected: "constant".into(),
+                }]);
+            }
+        };
+        if negative {
+            if !constant.data_type.is_numeric() {
+                return Err(vec![ParserError::InvalidCode {
+                    span: token.position_span,
+                }]);
+            }
+            constant.value.insert(0, '-');
+        }
+        return Ok(constant);
+    }
+    /// `READ <variable1> [, <variable2>, ...]`. As an expression `READ` reads from a file instead, see `FILE_KEYWORDS`
+    fn handle_read_data(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "READ") {
+            return Ok(None);
+        }
+        self.advance();
+        let mut targets = vec![self.expect_identifier()?];
+        while !self.at_argument_end() {
+            self.expect(TokenKind::Comma, ",")?;
+            targets.push(self.expect_identifier()?);
+        }
+        return Ok(Some(Statement::READ(ReadDataNode { targets, token })));
+    }
+    /// `RESTORE [linenumber | @label]`
+    fn handle_restore(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "RESTORE") {
+            return Ok(None);
+        }
+        self.advance();
+        let mut target = None;
+        if !self.at_argument_end() {
+            target = Some(self.parse_jump_target(None)?);
+        }
+        return Ok(Some(Statement::RESTORE(RestoreNode { target, token })));
+    }
+    /// `PRINT <format> [, <expression> ...]` or the AppleSoft form `PRINT [<expression>] [{; | ,} <expression> ...] [; | ,]`.
+    /// The first form is used when the first expression is a string literal with placeholders
+    fn handle_print(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "PRINT") {
+            return Ok(None);
+        }
+        self.advance();
+        let mut print = PrintNode {
+            format: None,
+            items: Vec::new(),
+            newline: true,
+            token,
+        };
+        if !self.at_argument_end() {
+            let first = self.parse_expression()?;
+            match first {
+                Expression::CONSTANT(constant)
+                    if constant.data_type == DataType::STRING
+                        && constant.value.contains(['{', '}']) =>
+                {
+                    print.format = Some(constant)
+                }
+                _ => print.items.push(PrintItem::EXPRESSION(first)),
+            }
+        }
+        loop {
+            if self.current_is(TokenKind::Comma) && !self.at_argument_end() {
+                self.advance();
+                // in a format the commas only separate the arguments
+                if print.format.is_none() {
+                    print.items.push(PrintItem::ZONE);
+                }
+                if self.at_argument_end() {
+                    print.newline = false;
+                    break;
+                }
+                print
+                    .items
+                    .push(PrintItem::EXPRESSION(self.parse_expression()?));
+                continue;
+            }
+            if !self.current_is(TokenKind::Semicolon) {
+                break;
+            }
+            let next = self.peek(0);
+            if next
+                .as_ref()
+                .is_none_or(|t| matches!(t.kind, TokenKind::Newline | TokenKind::EOF))
+            {
+                self.advance();
+                print.newline = false;
+                break;
+            }
+            if !next.is_some_and(|t| self.continues_print(&t)) {
+                // the `;` separates statements e.g. `PRINT A; PRINT B`
+                break;
+            }
+            if print.format.is_some() {
+                return Err(vec![ParserError::InvalidCode {
+                    span: self.current_span(),
+                }]);
+            }
+            self.advance();
+            print
+                .items
+                .push(PrintItem::EXPRESSION(self.parse_expression()?));
+        }
+        return Ok(Some(Statement::PRINT(print)));
+    }
+    /// `INPUT [<prompt>;] <variable1> [, <variable2>, ...]` or `GET <variable>`, the prompt has to be a string literal like in AppleSoft.
+    /// Anything else is left to the positional `INPUT` and `GET` functions e.g. a bare `GET` waiting for a key
+    fn handle_input(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        let get = Self::is_keyword(&token, "GET");
+        if !get && !Self::is_keyword(&token, "INPUT") {
+            return Ok(None);
+        }
+        let prompted =
+            !get && matches!(
+                self.peek(0),
+                Some(Token {
+                    kind: TokenKind::StringLiteral(_),
+                    ..
+                })
+            ) && matches!(
+                self.peek(1),
+                Some(Token {
+                    kind: TokenKind::Semicolon,
+                    ..
+                })
+            );
+        if !prompted
+            && !matches!(
+                self.peek(0),
+                Some(Token {
+                    kind: TokenKind::Identifier(..),
+                    ..
+                })
+            )
+        {
+            return Ok(None);
+        }
+        self.advance();
+        let mut prompt = None;
+        if prompted {
+            prompt = Some(Box::new(self.parse_expression()?));
+            self.expect(TokenKind::Semicolon, ";")?;
+        }
+        let mut targets = vec![self.expect_identifier()?];
+        while !get && !self.at_argument_end() {
+            self.expect(TokenKind::Comma, ",")?;
+            targets.push(self.expect_identifier()?);
+        }
+        let input = InputNode {
+            prompt,
+            targets,
+            token,
+        };
+        return Ok(Some(if get {
+            Statement::GET(input)
+        } else {
+            Statement::INPUT(input)
+        }));
+    }
+    /// `ONERR <statement1> [, <statement2>, ...]`, the statements are separated like the ones of a `MENU`
+    fn handle_onerr(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "ONERR") {
+            return Ok(None);
+        }
+        self.advance();
+        let in_menu = self.in_menu;
+        self.in_menu = true;
+        let actions = self.parse_menu_actions();
+        self.in_menu = in_menu;
+        return Ok(Some(Statement::ONERR(ErrorHandlerNode {
+            actions: actions?,
+            token,
+        })));
+    }
+    /// `THROW <code>`
+    fn handle_throw(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "THROW") {
+            return Ok(None);
+        }
+        self.advance();
+        return Ok(Some(Statement::THROW(ThrowNode {
+            code: Box::new(self.parse_expression()?),
+            token,
+        })));
+    }
+    /// returns true if the token after a `;` continues a PRINT rather than starting the next statement, assignments like `PRINT A; B = 5` are statements
+    fn continues_print(&self, token: &Token) -> bool {
+        return match &token.kind {
+            TokenKind::Keyword(_) => !Self::starts_statement(token),
+            TokenKind::Identifier(..) => !self.peek(1).is_some_and(
+                |t| matches!(&t.kind, TokenKind::Operator(o) if ["=", "+=", "-=", "*="].contains(&o.as_str())),
+            ),
+            TokenKind::Newline | TokenKind::EOF | TokenKind::Semicolon => false,
+            _ => true,
+        };
+    }
+    fn handle_end(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "END") {
+            return Ok(None);
+        }
         self.advance();
-        return Ok(expression.unwrap());
+        return Ok(Some(Statement::END(EndNode { token })));
     }
-    fn parse_statement(&mut self) -> Result<Expression, Vec<ParserError>> {
-        todo!();
+    /// `STOP <code>`
+    fn handle_stop(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "STOP") {
+            return Ok(None);
+        }
+        self.advance();
+        return Ok(Some(Statement::STOP(StopNode {
+            code: Box::new(self.parse_expression()?),
+            token,
+        })));
+    }
+    /// `<name>[{.<field> | [<index>]} ...] = <expression>` e.g. `people[0].age = 99`,
+    /// `+=`, `-=` and `*=` assign the result of the operation on the target and the expression.
+    /// A name followed by parentheses is a call whose result is discarded, e.g. `greet(p)`
+    fn handle_assignment(&mut self, token: Token) -> Result<Option<Statement>, Vec<ParserError>> {
+        if !matches!(token.kind, TokenKind::Identifier(..)) {
+            return Ok(None);
+        }
+        let target = self.expect_identifier()?;
+        if self.current_is(TokenKind::ParenOpen) {
+            return Ok(Some(Statement::FUNC_CALL(FunctionCallNode {
+                name: target.name.trim_end_matches('$').into(),
+                arguments: self.parse_enclosed_arguments()?,
+                token,
+            })));
+        }
+        let path = self.parse_path()?;
+        let operator = self.current_token.clone();
+        let operation = match operator.as_ref().map(|t| &t.kind) {
+            Some(TokenKind::Operator(o)) if o == "+=" => Some(BinaryOpKind::ADD),
+            Some(TokenKind::Operator(o)) if o == "-=" => Some(BinaryOpKind::SUB),
+            Some(TokenKind::Operator(o)) if o == "*=" => Some(BinaryOpKind::MUL),
+            _ => None,
+        };
+        if operation.is_some() {
+            self.advance();
+        } else {
+            self.expect_operator("=")?;
+        }
+        let mut assignment = AssignmentNode {
+            target,
+            path,
+            value: self.parse_expression()?,
+        };
+        if let Some(operation) = operation {
+            assignment.value = Expression::BINARY_OP(BinaryOpNode {
+                left: Box::new(assignment.target_expression()),
+                right: Box::new(assignment.value),
+                operation,
+                token: operator.unwrap(),
+            });
+        }
+        return Ok(Some(Statement::ASSIGN(assignment)));
+    }
+    fn get_expression_handlers(&self) -> Vec<ExpressionHandler> {
+        return vec![
+            Self::handle_single_constant,
+            Self::handle_parenthesized,
+            Self::handle_poptions,
+            Self::handle_collection_expression,
+            Self::handle_positional_expression,
+            Self::handle_identifier,
+        ];
+    }
+    fn handle_single_constant(
+        &mut self,
+        token: Token,
+    ) -> Result<Option<Expression>, Vec<ParserError>> {
+        let (data_type, value) = match &token.kind {
+            TokenKind::Number(num) if num.contains('.') => (DataType::FLOAT, num.clone()),
+            TokenKind::Number(num) => (DataType::INT, num.clone()),
+            TokenKind::StringLiteral(literal) => {
+                (DataType::STRING, Self::unescape_string_literal(literal))
+            }
+            // colors are plain 0xRRGGBB integers, the short #RGB form repeats every digit
+            TokenKind::ColorLiteral(literal) => {
+                let digits: String = match literal.len() {
+                    4 => literal[1..].chars().flat_map(|c| [c, c]).collect(),
+                    _ => literal[1..].into(),
+                };
+                let value = i64::from_str_radix(&digits, 16).unwrap_or(0);
+                (DataType::INT, value.to_string())
+            }
+            TokenKind::Keyword(k) if k.to_uppercase() == "TRUE" || k.to_uppercase() == "FALSE" => {
+                (DataType::BOOL, k.to_uppercase())
+            }
+            _ => return Ok(None),
+        };
+        self.advance();
+        return Ok(Some(Expression::CONSTANT(ConstantNode {
+            data_type,
+            value,
+            token,
+        })));
+    }
+    /// strip the quotes of a string literal token and resolve its escape characters
+    fn unescape_string_literal(literal: &str) -> String {
+        let inner = &literal[1..literal.len() - 1];
+        let mut value = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('r') => value.push('\r'),
+                Some('0') => value.push('\0'),
+                Some(escaped) => value.push(escaped),
+                None => (),
+            }
+        }
+        return value;
+    }
+    fn handle_parenthesized(
+        &mut self,
+        token: Token,
+    ) -> Result<Option<Expression>, Vec<ParserError>> {
+        if token.kind != TokenKind::ParenOpen {
+            return Ok(None);
+        }
+        self.advance();
+        let expression = self.parse_expression()?;
+        self.expect(TokenKind::ParenClose, ")")?;
+        return Ok(Some(expression));
+    }
+    /// `POPTIONS <option>, [option, ...] <prompt>`, the last argument is the prompt
+    fn handle_poptions(&mut self, token: Token) -> Result<Option<Expression>, Vec<ParserError>> {
+        if !Self::is_keyword(&token, "POPTIONS") {
+            return Ok(None);
+        }
+        self.advance();
+        let mut options = self.parse_positional_arguments("POPTIONS", None, false)?;
+        if options.len() < 2 {
+            return Err(vec![ParserError::MissingToken {
+                span: self.current_span(),
+                expected: "option and prompt".into(),
+            }]);
+        }
+        let prompt = Box::new(options.pop().unwrap());
+        return Ok(Some(Expression::POPTIONS(OptionsNode {
+            options,
+            prompt,
+            token,
+        })));
+    }
+    /// `POP`, `HAS` and `KEYS` used for their value e.g. `LET last = POP l`
+    fn handle_collection_expression(
+        &mut self,
+        token: Token,
+    ) -> Result<Option<Expression>, Vec<ParserError>> {
+        let operation = match Self::collection_operation(&token) {
+            Some(op @ (CollectionOpKind::POP | CollectionOpKind::HAS | CollectionOpKind::KEYS)) => {
+                op
+            }
+            _ => return Ok(None),
+        };
+        return Ok(Some(Expression::COLLECTION(
+            self.parse_collection(operation, token, true)?,
+        )));
+    }
+    /// a positional function used for its value e.g. `LET F = OPEN "file.txt", "r"`
+    fn handle_positional_expression(
+        &mut self,
+        token: Token,
+    ) -> Result<Option<Expression>, Vec<ParserError>> {
+        let function = match &token.kind {
+            TokenKind::Keyword(k) => match Self::positional_expression(k) {
+                Some(f) => f,
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        self.advance();
+        // an operator ends a call without arguments e.g. `DATE + DAY 1` and parentheses enclose the arguments e.g. `TIME(D) - 1`
+        let arguments = match &self.current_token {
+            Some(t) if function.parameters().is_empty() || Self::binary_operator(t).is_some() => {
+                Vec::new()
+            }
+            Some(t) if t.kind == TokenKind::ParenOpen => self.parse_enclosed_arguments()?,
+            _ => self.parse_positional_arguments(
+                &function.usb_name(),
+                Some(function.parameters().len()),
+                true,
+            )?,
+        };
+        return Ok(Some(Expression::FUNC_CALL(FunctionCallNode {
+            name: function.usb_name(),
+            arguments,
+            token,
+        })));
+    }
+    /// a variable, possibly followed by the fields of a record and the indices of a collection e.g. `p.home.city` or `l[0].name`,
+    /// or a call using parentheses e.g. `STR(x)`
+    fn handle_identifier(&mut self, token: Token) -> Result<Option<Expression>, Vec<ParserError>> {
+        if !matches!(token.kind, TokenKind::Identifier(..)) {
+            return Ok(None);
+        }
+        let identifier = self.expect_identifier()?;
+        if !matches!(
+            self.current_token.as_ref().map(|t| &t.kind),
+            Some(TokenKind::ParenOpen)
+        ) {
+            let path = self.parse_path()?;
+            return Ok(Some(AssignmentNode::access(
+                Expression::IDENTIFIER(identifier),
+                &path,
+            )));
+        }
+        return Ok(Some(Expression::FUNC_CALL(FunctionCallNode {
+            name: identifier.name.trim_end_matches('$').into(),
+            arguments: self.parse_enclosed_arguments()?,
+            token,
+        })));
+    }
+    /// parse the fields and indices following a variable, e.g. the `[0].name` of `l[0].name`
+    fn parse_path(&mut self) -> Result<Vec<TargetAccess>, Vec<ParserError>> {
+        let mut path = Vec::new();
+        loop {
+            if self.current_is(TokenKind::Dot) {
+                self.advance();
+                path.push(TargetAccess::FIELD(self.expect_field()?));
+            } else if self.current_is(TokenKind::BrackOpen) {
+                let (index, token) = self.parse_index()?;
+                path.push(TargetAccess::INDEX(index, token));
+            } else {
+                return Ok(path);
+            }
+        }
+    }
+    /// parse an index between brackets e.g. the `[0]` of `l[0]`, returning it along with the token of the opening bracket
+    fn parse_index(&mut self) -> Result<(Box<Expression>, Token), Vec<ParserError>> {
+        let token = self.expect(TokenKind::BrackOpen, "[")?;
+        let index = self.parse_expression()?;
+        self.expect(TokenKind::BrackClose, "]")?;
+        return Ok((Box::new(index), token));
+    }
+    /// parse the comma separated arguments between the parentheses of a call, e.g. the `(x, 1)` of `LEFT(x, 1)`
+    fn parse_enclosed_arguments(&mut self) -> Result<Vec<Expression>, Vec<ParserError>> {
+        self.expect(TokenKind::ParenOpen, "(")?;
+        let mut arguments = Vec::new();
+        while !self.current_is(TokenKind::ParenClose) {
+            if !arguments.is_empty() {
+                self.expect(TokenKind::Comma, ",")?;
+            }
+            arguments.push(self.parse_expression()?);
+        }
+        self.advance();
+        return Ok(arguments);
+    }
+    fn binary_operator(token: &Token) -> Option<BinaryOpKind> {
+        let operator = match &token.kind {
+            TokenKind::Operator(o) => o.to_uppercase(),
+            _ => return None,
+        };
+        return match operator.as_str() {
+            "+" => Some(BinaryOpKind::ADD),
+            "-" => Some(BinaryOpKind::SUB),
+            "*" => Some(BinaryOpKind::MUL),
+            "/" => Some(BinaryOpKind::DIV),
+            "=" | "==" => Some(BinaryOpKind::EQ),
+            "<>" | "!=" => Some(BinaryOpKind::NEQ),
+            "<" => Some(BinaryOpKind::LT),
+            ">" => Some(BinaryOpKind::GT),
+            "<=" => Some(BinaryOpKind::LTE),
+            ">=" => Some(BinaryOpKind::GTE),
+            "AND" => Some(BinaryOpKind::AND),
+            "OR" => Some(BinaryOpKind::OR),
+            "MOD" => Some(BinaryOpKind::MOD),
+            "\\" => Some(BinaryOpKind::IDIV),
+            "BAND" => Some(BinaryOpKind::BAND),
+            "BOR" => Some(BinaryOpKind::BOR),
+            "BXOR" => Some(BinaryOpKind::BXOR),
+            "SHL" => Some(BinaryOpKind::SHL),
+            "SHR" => Some(BinaryOpKind::SHR),
+            _ => None,
+        };
+    }
+    fn parse_expression(&mut self) -> Result<Expression, Vec<ParserError>> {
+        return self.parse_binary(0);
+    }
+    /// precedence climbing over the binary operators, all of them are left associative
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expression, Vec<ParserError>> {
+        let mut left = self.parse_unary()?;
+        while let Some(token) = self.current_token.clone() {
+            let operation = match Self::binary_operator(&token) {
+                Some(op) if op.precedence() >= min_precedence => op,
+                _ => break,
+            };
+            self.advance();
+            let right = self.parse_binary(operation.precedence() + 1)?;
+            left = Expression::BINARY_OP(BinaryOpNode {
+                left: Box::new(left),
+                right: Box::new(right),
+                operation,
+                token,
+            });
+        }
+        return Ok(left);
+    }
+    fn parse_unary(&mut self) -> Result<Expression, Vec<ParserError>> {
+        let token = match self.current_token.clone() {
+            Some(t) => t,
+            None => {
+                return Err(vec![ParserError::InvalidCode {
+                    span: self.current_span(),
+                }]);
+            }
+        };
+        let (operation, precedence) = if self.current_is_operator("-") {
+            (UnaryOpKind::NEG, u8::MAX)
+        } else if self.current_is_operator("NOT") {
+            // NOT binds looser than comparisons so NOT X > 5 negates the comparison
+            (UnaryOpKind::NOT, BinaryOpKind::EQ.precedence())
+        } else {
+            return self.parse_primary();
+        };
+        self.advance();
+        let target = if precedence == u8::MAX {
+            self.parse_unary()?
+        } else {
+            self.parse_binary(precedence)?
+        };
+        return Ok(Expression::UNARY_OP(UnaryOpNode {
+            target: Box::new(target),
+            operation,
+            token,
+        }));
+    }
+    fn parse_primary(&mut self) -> Result<Expression, Vec<ParserError>> {
+        let token = match self.current_token.clone() {
+            Some(t) => t,
+            None => {
+                return Err(vec![ParserError::InvalidCode {
+                    span: self.current_span(),
+                }]);
+            }
+        };
+        let span = token.position_span.clone();
+        for handler in self.get_expression_handlers() {
+            if let Some(expression) = handler(self, token.clone())? {
+                return Ok(expression);
+            }
+        }
+        return Err(vec![ParserError::UnexpectedToken { span, token }]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usbcompiler::{ast::ast::FieldAccessNode, tokenizer::lexer::Lexer};
+
+    fn parse_script(input: &str) -> Vec<Statement> {
+        let tokens = Lexer::new(input.into()).tokenize().unwrap();
+        let mut parser = Parser::new(tokens, EvaluationContext::FileLevel);
+        let result = parser.parse();
+        assert!(
+            result.is_ok(),
+            "Parser returned errors: {:#?}",
+            result.err()
+        );
+        match result.unwrap() {
+            AstNode::STATEMENT(Statement::SCOPE(scope)) => return scope.statements,
+            other => panic!("expected root scope, got {:#?}", other),
+        }
+    }
+    fn line_numbers(statements: &[Statement]) -> Vec<usize> {
+        return statements
+            .iter()
+            .filter_map(|s| match s {
+                Statement::LINE(line) => Some(line.line_number),
+                _ => None,
+            })
+            .collect();
+    }
+    #[test]
+    fn test_inferred_line_numbers() {
+        let statements = parse_script("10 PRINT 5\nPRINT 6\n\n30 PRINT 7\nPRINT 8");
+        assert_eq!(line_numbers(&statements), vec![10, 11, 30, 31]);
+
+        // lines holding nothing but a comment don't count as a line
+        let statements = parse_script("PRINT 5\nREM a comment\nPRINT 6 // comment");
+        assert_eq!(line_numbers(&statements), vec![1, 2]);
+    }
+    #[test]
+    fn test_scope_lines() {
+        let statements = parse_script("10 BEGIN\n20 LET X = 5\n30 FIN\n40 PRINT 1");
+        assert_eq!(line_numbers(&statements), vec![10, 40]);
+        match &statements[1] {
+            Statement::SCOPE(scope) => assert_eq!(line_numbers(&scope.statements), vec![20, 30]),
+            other => panic!("expected scope, got {:#?}", other),
+        }
+    }
+    #[test]
+    fn test_operator_precedence() {
+        let statements = parse_script("LET X = 1 + 2 * 3 > 4 AND TRUE");
+        let Statement::VAR_DECL(declaration) = &statements[1] else {
+            panic!("expected declaration");
+        };
+        let Some(Expression::BINARY_OP(and)) = declaration.initial_value.as_deref() else {
+            panic!("expected binary operation");
+        };
+        assert_eq!(and.operation, BinaryOpKind::AND);
+        let Expression::BINARY_OP(greater) = and.left.as_ref() else {
+            panic!("expected comparison");
+        };
+        assert_eq!(greater.operation, BinaryOpKind::GT);
+        let Expression::BINARY_OP(add) = greater.left.as_ref() else {
+            panic!("expected addition");
+        };
+        assert_eq!(add.operation, BinaryOpKind::ADD);
+    }
+    #[test]
+    fn test_integer_operators() {
+        let statements = parse_script("LET X = 1 BOR 2 + 7 MOD 4 SHL 1 = 0\nX += 5 \\ 2");
+        let Statement::VAR_DECL(declaration) = &statements[1] else {
+            panic!("expected declaration");
+        };
+        let Some(Expression::BINARY_OP(equal)) = declaration.initial_value.as_deref() else {
+            panic!("expected comparison");
+        };
+        assert_eq!(equal.operation, BinaryOpKind::EQ);
+        let Expression::BINARY_OP(or) = equal.left.as_ref() else {
+            panic!("expected BOR");
+        };
+        assert_eq!(or.operation, BinaryOpKind::BOR);
+        let Expression::BINARY_OP(shift) = or.right.as_ref() else {
+            panic!("expected SHL");
+        };
+        assert_eq!(shift.operation, BinaryOpKind::SHL);
+        let Expression::BINARY_OP(add) = shift.left.as_ref() else {
+            panic!("expected addition");
+        };
+        assert!(matches!(
+            add.right.as_ref(),
+            Expression::BINARY_OP(BinaryOpNode {
+                operation: BinaryOpKind::MOD,
+                ..
+            })
+        ));
+        // X += 5 \ 2 is X = X + 5 \ 2
+        let Statement::ASSIGN(assignment) = &statements[3] else {
+            panic!("expected assignment");
+        };
+        let Expression::BINARY_OP(sum) = &assignment.value else {
+            panic!("expected X + 5 \\ 2");
+        };
+        assert_eq!(sum.operation, BinaryOpKind::ADD);
+        assert!(matches!(sum.left.as_ref(), Expression::IDENTIFIER(x) if x.name == "X"));
+        assert!(matches!(
+            sum.right.as_ref(),
+            Expression::BINARY_OP(BinaryOpNode {
+                operation: BinaryOpKind::IDIV,
+                ..
+            })
+        ));
+    }
+    #[test]
+    fn test_enum_declaration() {
+        let statements = parse_script("ENUM class = ROGUE 2, WARRIOR 5, MAGE\nLET C AS class");
+        let Statement::ENUM(declaration) = &statements[1] else {
+            panic!("expected enum declaration");
+        };
+        assert_eq!(declaration.name.name, "CLASS");
+        assert_eq!(
+            declaration.values().unwrap(),
+            vec![
+                ("ROGUE".into(), 2),
+                ("WARRIOR".into(), 5),
+                ("MAGE".into(), 6)
+            ]
+        );
+        let Statement::VAR_DECL(variable) = &statements[3] else {
+            panic!("expected declaration");
+        };
+        assert_eq!(variable.data_type, Some(DataType::ENUM("CLASS".into())));
+
+        let statements = parse_script("ENUM shade = CYAN 2, GREEN 0, BLUE");
+        let Statement::ENUM(declaration) = &statements[1] else {
+            panic!("expected enum declaration");
+        };
+        assert_eq!(declaration.values().unwrap_err().name.name, "BLUE");
+    }
+    #[test]
+    fn test_menu_actions() {
+        let statements =
+            parse_script("MENU X PRINT \"a\", 1, GOSUB 20, 30, END\nMENU X GOTO 10, 20");
+        let Statement::MENU(menu) = &statements[1] else {
+            panic!("expected menu");
+        };
+        assert!(matches!(
+            menu.actions[..],
+            [
+                Statement::PRINT(_),
+                Statement::GOSUB(JumpNode {
+                    line_number: 20,
+                    ..
+                }),
+                Statement::GOSUB(JumpNode {
+                    line_number: 30,
+                    ..
+                }),
+                Statement::END(_)
+            ]
+        ));
+        let Statement::PRINT(print) = &menu.actions[0] else {
+            panic!("expected print");
+        };
+        assert!(matches!(
+            print.items[..],
+            [
+                PrintItem::EXPRESSION(_),
+                PrintItem::ZONE,
+                PrintItem::EXPRESSION(_)
+            ]
+        ));
+
+        let Statement::MENU(menu) = &statements[3] else {
+            panic!("expected menu");
+        };
+        assert!(matches!(
+            menu.actions[..],
+            [
+                Statement::GOTO(JumpNode {
+                    line_number: 10,
+                    ..
+                }),
+                Statement::GOTO(JumpNode {
+                    line_number: 20,
+                    ..
+                })
+            ]
+        ));
+    }
+    #[test]
+    fn test_file_statements() {
+        let statements = parse_script(
+            "LET F = OPEN \"./file.txt\", \"rw\"\nLET C = READ F 1\nWRITE F, \"Hello\"\nCLOSE F",
+        );
+        let Statement::VAR_DECL(open) = &statements[1] else {
+            panic!("expected declaration");
+        };
+        let Some(Expression::FUNC_CALL(call)) = open.initial_value.as_deref() else {
+            panic!("expected OPEN call");
+        };
+        assert_eq!((call.name.as_str(), call.arguments.len()), ("OPEN", 2));
+        let Statement::VAR_DECL(read) = &statements[3] else {
+            panic!("expected declaration");
+        };
+        let Some(Expression::FUNC_CALL(call)) = read.initial_value.as_deref() else {
+            panic!("expected READ call");
+        };
+        assert_eq!((call.name.as_str(), call.arguments.len()), ("READ", 2));
+        let Statement::FUNC_CALL(call) = &statements[5] else {
+            panic!("expected WRITE call");
+        };
+        assert_eq!((call.name.as_str(), call.arguments.len()), ("WRITE", 2));
+    }
+    #[test]
+    fn test_date_expressions() {
+        let statements =
+            parse_script("LET D AS DATE = DATE + DAY 1\nPRINT FORMATDATE(TIME(D) - 1, \"%d\")");
+        let Statement::VAR_DECL(declaration) = &statements[1] else {
+            panic!("expected declaration");
+        };
+        assert_eq!(declaration.data_type, Some(DataType::DATE));
+        let Some(Expression::BINARY_OP(sum)) = declaration.initial_value.as_deref() else {
+            panic!("expected DATE + DAY 1");
+        };
+        assert!(matches!(&*sum.left, Expression::FUNC_CALL(call) if call.arguments.is_empty()));
+        let Statement::PRINT(print) = &statements[3] else {
+            panic!("expected PRINT");
+        };
+        let PrintItem::EXPRESSION(Expression::FUNC_CALL(format)) = &print.items[0] else {
+            panic!("expected FORMATDATE call");
+        };
+        assert_eq!(format.arguments.len(), 2);
+        assert!(matches!(&format.arguments[0], Expression::BINARY_OP(_)));
+        // the arguments of a call inside an expression end at the next operator
+        let statements =
+            parse_script("LET S = HOUR 2 + MINUTE 30\nLET T = \"[\" + READALL F + \"]\"");
+        let Statement::VAR_DECL(declaration) = &statements[1] else {
+            panic!("expected declaration");
+        };
+        let Some(Expression::BINARY_OP(sum)) = declaration.initial_value.as_deref() else {
+            panic!("expected HOUR 2 + MINUTE 30");
+        };
+        for call in [&sum.left, &sum.right] {
+            assert!(
+                matches!(&**call, Expression::FUNC_CALL(call) if matches!(call.arguments[..], [Expression::CONSTANT(_)]))
+            );
+        }
+        let Statement::VAR_DECL(declaration) = &statements[3] else {
+            panic!("expected declaration");
+        };
+        let Some(Expression::BINARY_OP(concatenation)) = declaration.initial_value.as_deref()
+        else {
+            panic!("expected string concatenation");
+        };
+        assert!(matches!(&*concatenation.right, Expression::CONSTANT(_)));
+        assert!(
+            matches!(&*concatenation.left, Expression::BINARY_OP(left) if matches!(&*left.right, Expression::FUNC_CALL(call) if call.arguments.len() == 1))
+        );
+    }
+    #[test]
+    fn test_data_statements() {
+        let statements =
+            parse_script("DATA -1, 2.5, \"a\"\nREAD A, B\nRESTORE 10\nLET C = READ F 1");
+        let Statement::DATA(data) = &statements[1] else {
+            panic!("expected DATA");
+        };
+        let values: Vec<&str> = data.values.iter().map(|v| v.value.as_str()).collect();
+        assert_eq!(values, vec!["-1", "2.5", "a"]);
+        let Statement::READ(read) = &statements[3] else {
+            panic!("expected READ");
+        };
+        assert_eq!(read.targets.len(), 2);
+        let Statement::RESTORE(restore) = &statements[5] else {
+            panic!("expected RESTORE");
+        };
+        assert_eq!(restore.target.as_ref().map(|t| t.line_number), Some(10));
+        // READ in an expression reads from a file
+        let Statement::VAR_DECL(declaration) = &statements[7] else {
+            panic!("expected declaration");
+        };
+        assert!(matches!(
+            declaration.initial_value.as_deref(),
+            Some(Expression::FUNC_CALL(call)) if call.name == "READ"
+        ));
+    }
+    #[test]
+    fn test_print_separators() {
+        let statements = parse_script(
+            "PRINT \"a\"; 1, B;\nPRINT \"{:>5} {}\", A, 2\nPRINT A; PRINT B\nPRINT A; B = 1",
+        );
+        let Statement::PRINT(print) = &statements[1] else {
+            panic!("expected PRINT");
+        };
+        assert!(matches!(
+            print.items[..],
+            [
+                PrintItem::EXPRESSION(_),
+                PrintItem::EXPRESSION(_),
+                PrintItem::ZONE,
+                PrintItem::EXPRESSION(_)
+            ]
+        ));
+        assert!(!print.newline);
+        let Statement::PRINT(print) = &statements[3] else {
+            panic!("expected PRINT");
+        };
+        assert_eq!(
+            print.format.as_ref().map(|f| f.value.as_str()),
+            Some("{:>5} {}")
+        );
+        assert_eq!(print.items.len(), 2);
+        assert!(print.newline);
+        // a `;` before a statement separates statements
+        assert!(matches!(
+            statements[5..7],
+            [Statement::PRINT(_), Statement::PRINT(_)]
+        ));
+        assert!(matches!(
+            statements[8..10],
+            [Statement::PRINT(_), Statement::ASSIGN(_)]
+        ));
+    }
+    #[test]
+    fn test_input_statements() {
+        let statements = parse_script(
+            "INPUT \"Age\"; A, B$\nGET K$\nGET\nLET S$ = INPUT \"> \"\nONERR PRINT ERR, GOTO 10\nTHROW 5",
+        );
+        let Statement::INPUT(input) = &statements[1] else {
+            panic!("expected INPUT");
+        };
+        assert!(input.prompt.is_some());
+        assert_eq!(input.targets.len(), 2);
+        assert!(matches!(&statements[3], Statement::GET(get) if get.targets.len() == 1));
+        // without a variable GET and INPUT are calls
+        assert!(matches!(&statements[5], Statement::FUNC_CALL(call) if call.name == "GET"));
+        assert!(matches!(&statements[7], Statement::VAR_DECL(_)));
+        let Statement::ONERR(handler) = &statements[9] else {
+            panic!("expected ONERR");
+        };
+        assert!(matches!(
+            handler.actions[..],
+            [Statement::PRINT(_), Statement::GOTO(_)]
+        ));
+        assert!(matches!(&statements[11], Statement::THROW(_)));
+    }
+    #[test]
+    fn test_records() {
+        let statements = parse_script(
+            "RECORD Person\nname AS STRING\n\nhome AS INTEGER\nFIN\nLET p AS Person\np.home = p.home + 1",
+        );
+        let Statement::RECORD(record) = &statements[1] else {
+            panic!("expected RECORD");
+        };
+        let fields: Vec<&str> = record.fields.iter().map(|f| f.name.name.as_str()).collect();
+        assert_eq!(fields, vec!["NAME", "HOME"]);
+        let Statement::VAR_DECL(declaration) = &statements[3] else {
+            panic!("expected declaration");
+        };
+        assert_eq!(
+            declaration.data_type,
+            Some(DataType::RECORD("PERSON".into()))
+        );
+        let Statement::ASSIGN(assignment) = &statements[5] else {
+            panic!("expected assignment");
+        };
+        assert!(
+            matches!(&assignment.path[..], [TargetAccess::FIELD(field)] if field.name == "HOME")
+        );
+        let Expression::BINARY_OP(sum) = &assignment.value else {
+            panic!("expected p.home + 1");
+        };
+        assert!(matches!(
+            &*sum.left,
+            Expression::FIELD(FieldAccessNode { field, .. }) if field.name == "HOME"
+        ));
+    }
+    #[test]
+    fn test_element_field_assignment() {
+        let statements = parse_script("people[0].age = 99\npeople[i + 1].home.city += \"!\"");
+        let Statement::ASSIGN(assignment) = &statements[1] else {
+            panic!("expected assignment");
+        };
+        assert_eq!(assignment.target.name, "PEOPLE");
+        assert!(matches!(
+            &assignment.path[..],
+            [TargetAccess::INDEX(..), TargetAccess::FIELD(field)] if field.name == "AGE"
+        ));
+        let Statement::ASSIGN(assignment) = &statements[3] else {
+            panic!("expected assignment");
+        };
+        assert_eq!(assignment.path.len(), 3);
+        let Expression::BINARY_OP(sum) = &assignment.value else {
+            panic!("expected people[i + 1].home.city + \"!\"");
+        };
+        assert!(matches!(
+            &*sum.left,
+            Expression::FIELD(FieldAccessNode { record, field }) if field.name == "CITY" && matches!(**record, Expression::FIELD(_))
+        ));
+    }
+    #[test]
+    fn test_functions() {
+        let statements = parse_script(
+            "FUNCTION older(p AS Person, years AS INT) AS INT\nRET p.age + years\nFIN\nFUNCTION greet$()\nPRINT \"hi\"\nFIN\ngreet$()",
+        );
+        let Statement::FUNCTION(declaration) = &statements[1] else {
+            panic!("expected FUNCTION");
+        };
+        assert_eq!(declaration.name.name, "OLDER");
+        let parameters: Vec<(&str, &DataType)> = declaration
+            .parameters
+            .iter()
+            .map(|p| (p.name.name.as_str(), &p.data_type))
+            .collect();
+        assert_eq!(
+            parameters,
+            vec![
+                ("P", &DataType::ENUM("PERSON".into())),
+                ("YEARS", &DataType::INT)
+            ]
+        );
+        assert_eq!(declaration.return_type, Some(DataType::INT));
+        assert!(matches!(
+            declaration.body[..],
+            [Statement::LINE(_), Statement::RETURN(_), Statement::LINE(_)]
+        ));
+        let Statement::FUNCTION(declaration) = &statements[3] else {
+            panic!("expected FUNCTION");
+        };
+        assert_eq!(declaration.name.name, "GREET");
+        assert!(declaration.parameters.is_empty() && declaration.return_type.is_none());
+        assert!(matches!(
+            &statements[5],
+            Statement::FUNC_CALL(FunctionCallNode { name, arguments, .. }) if name == "GREET" && arguments.is_empty()
+        ));
+    }
+    #[test]
+    fn test_collections() {
+        let statements = parse_script(
+            "LET m AS MAP OF STRING TO LIST OF INT\nm[\"a\"] = KEYS m\nPUSH m[\"a\"], 1\nFOR EACH x IN m[\"a\"]\nPRINT x + POP l\nNEXT",
+        );
+        let Statement::VAR_DECL(declaration) = &statements[1] else {
+            panic!("expected declaration");
+        };
+        assert_eq!(
+            declaration.data_type,
+            Some(DataType::MAP(Box::new(DataType::LIST(Box::new(
+                DataType::INT
+            )))))
+        );
+        let Statement::ASSIGN(assignment) = &statements[3] else {
+            panic!("expected assignment");
+        };
+        assert!(matches!(assignment.path[..], [TargetAccess::INDEX(..)]));
+        assert!(matches!(
+            &assignment.value,
+            Expression::COLLECTION(CollectionNode { operation: CollectionOpKind::KEYS, arguments, .. }) if arguments.len() == 1
+        ));
+        let Statement::COLLECTION(push) = &statements[5] else {
+            panic!("expected PUSH");
+        };
+        assert_eq!(push.operation, CollectionOpKind::PUSH);
+        assert!(matches!(push.arguments[..], [Expression::INDEX(_), _]));
+        let Statement::LOOP(node) = &statements[7] else {
+            panic!("expected FOR EACH");
+        };
+        assert!(matches!(&node.kind, LoopKind::EACH { item, .. } if item.name == "X"));
+        // the body holds the PRINT line, the NEXT line closes the loop
+        assert_eq!(line_numbers(&node.body), vec![5, 6]);
+        assert_eq!(node.body.len(), 3);
+        assert_eq!(line_numbers(&statements), vec![1, 2, 3, 4]);
+        let statements = parse_script("DIM grid(3, n + 1) AS STRING\nDIM row(3) AS INT");
+        let Statement::DIM(dim) = &statements[1] else {
+            panic!("expected DIM");
+        };
+        assert!(matches!(
+            dim.rows.as_deref(),
+            Some(Expression::BINARY_OP(_))
+        ));
+        assert_eq!(
+            dim.data_type(),
+            DataType::LIST(Box::new(DataType::LIST(Box::new(DataType::STRING))))
+        );
+        let Statement::DIM(dim) = &statements[3] else {
+            panic!("expected DIM");
+        };
+        assert_eq!(dim.data_type(), DataType::LIST(Box::new(DataType::INT)));
+        let statements = parse_script("DEF square x = x * x\nDEF area(w, h) = w * h\nDEF nine = 9");
+        let names: Vec<Vec<&str>> = [1, 3, 5]
+            .iter()
+            .map(|index| {
+                let Statement::DEF(def) = &statements[*index] else {
+                    panic!("expected DEF");
+                };
+                def.parameters.iter().map(|p| p.name.as_str()).collect()
+            })
+            .collect();
+        assert_eq!(names, vec![vec!["X"], vec!["W", "H"], vec![]]);
+        let tokens = Lexer::new("DIM a(3)".into()).tokenize().unwrap();
+        assert!(
+            Parser::new(tokens, EvaluationContext::FileLevel)
+                .parse()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_for_loops() {
+        let statements = parse_script(
+            "FOR EACH x IN l\nPRINT x\nNEXT\nFOR i = 10 TO 0 STEP -2\nPRINT i\nNEXT i\nFOR j = 1 TO n BEGIN\nPRINT j\nFIN",
+        );
+        let Statement::LOOP(node) = &statements[1] else {
+            panic!("expected FOR EACH");
+        };
+        assert!(matches!(&node.kind, LoopKind::EACH { item, .. } if item.name == "X"));
+        let Statement::LOOP(node) = &statements[3] else {
+            panic!("expected FOR");
+        };
+        let LoopKind::TO {
+            counter,
+            start,
+            end,
+            step,
+        } = &node.kind
+        else {
+            panic!("expected FOR ... TO");
+        };
+        assert_eq!(counter.name, "I");
+        assert!(matches!(**start, Expression::CONSTANT(ref c) if c.value == "10"));
+        assert!(matches!(**end, Expression::CONSTANT(ref c) if c.value == "0"));
+        assert!(matches!(step.as_deref(), Some(Expression::UNARY_OP(_))));
+        assert!(matches!(
+            node.body[..],
+            [Statement::LINE(_), Statement::PRINT(_), Statement::LINE(_)]
+        ));
+        let Statement::LOOP(node) = &statements[5] else {
+            panic!("expected FOR");
+        };
+        assert!(matches!(&node.kind, LoopKind::TO { step: None, .. }));
+        let statements = parse_script("FOR i = 0, i < 10, i += 1\nPRINT i\nNEXT");
+        let Statement::LOOP(node) = &statements[1] else {
+            panic!("expected FOR");
+        };
+        let LoopKind::CSTYLE {
+            counter,
+            condition,
+            step,
+            ..
+        } = &node.kind
+        else {
+            panic!("expected a C-style FOR");
+        };
+        assert_eq!(counter.name, "I");
+        assert!(matches!(**condition, Expression::BINARY_OP(_)));
+        assert!(
+            matches!(&step.value, Expression::BINARY_OP(b) if b.operation == BinaryOpKind::ADD)
+        );
+        // the step has to be an assignment
+        let tokens = Lexer::new("FOR i = 0, i < 10, PRINT i\nNEXT".into())
+            .tokenize()
+            .unwrap();
+        assert!(
+            Parser::new(tokens, EvaluationContext::FileLevel)
+                .parse()
+                .is_err()
+        );
+        // NEXT can only name the counter of its loop
+        let tokens = Lexer::new("FOR i = 1 TO 2\nNEXT j".into())
+            .tokenize()
+            .unwrap();
+        assert!(
+            Parser::new(tokens, EvaluationContext::FileLevel)
+                .parse()
+                .is_err()
+        );
+    }
+    #[test]
+    fn test_loops() {
+        let statements =
+            parse_script("WHILE x < 3 BEGIN\nCONTINUE\nFIN\nDO\nEXIT\nLOOP UNTIL x = 3");
+        let Statement::LOOP(node) = &statements[1] else {
+            panic!("expected WHILE");
+        };
+        assert!(matches!(node.kind, LoopKind::WHILE { .. }));
+        assert!(matches!(
+            node.body[..],
+            [
+                Statement::LINE(_),
+                Statement::CONTINUE(_),
+                Statement::LINE(_)
+            ]
+        ));
+        let Statement::LOOP(node) = &statements[3] else {
+            panic!("expected DO");
+        };
+        assert!(matches!(node.kind, LoopKind::UNTIL { .. }));
+        assert!(matches!(
+            node.body[..],
+            [Statement::LINE(_), Statement::EXIT(_), Statement::LINE(_)]
+        ));
+        let statements = parse_script("CLEAR\nBEGIN\nCSCOPE\nFIN");
+        assert!(matches!(statements[1], Statement::CLEAR(_)));
+        let Statement::SCOPE(scope) = &statements[3] else {
+            panic!("expected BEGIN");
+        };
+        assert!(matches!(scope.statements[1], Statement::CSCOPE(_)));
+        let tokens = Lexer::new("DO\nPRINT 1\nLOOP".into()).tokenize().unwrap();
+        assert!(
+            Parser::new(tokens, EvaluationContext::FileLevel)
+                .parse()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_if_else() {
+        let statements =
+            parse_script("IF x > 1 THEN PRINT 1 ELSEIF x > 0 GOTO 10 ELSE BEGIN\nPRINT 3\nFIN");
+        let Statement::IF(if_statement) = &statements[1] else {
+            panic!("expected IF");
+        };
+        assert!(matches!(*if_statement.action, Statement::PRINT(_)));
+        let Some(Statement::IF(else_if)) = if_statement.else_action.as_deref() else {
+            panic!("expected ELSEIF");
+        };
+        assert!(matches!(*else_if.action, Statement::GOTO(_)));
+        assert!(matches!(
+            else_if.else_action.as_deref(),
+            Some(Statement::SCOPE(_))
+        ));
+    }
+    #[test]
+    fn test_select() {
+        let statements = parse_script(
+            "SELECT CASE x\nCASE 1, 2\nPRINT 1\nCASE 3 TO 9, IS > 20\nCASE ELSE\nPRINT 2\nEND SELECT\nPRINT 3",
+        );
+        let Statement::SELECT(select) = &statements[1] else {
+            panic!("expected SELECT");
+        };
+        assert_eq!(select.cases.len(), 2);
+        assert!(matches!(
+            select.cases[0].conditions[..],
+            [CaseCondition::VALUE(_), CaseCondition::VALUE(_)]
+        ));
+        assert!(matches!(
+            select.cases[1].conditions[..],
+            [
+                CaseCondition::RANGE(..),
+                CaseCondition::IS(BinaryOpKind::GT, _)
+            ]
+        ));
+        // every body starts with the line of its CASE and the last one ends with the line of END SELECT
+        assert_eq!(line_numbers(&select.cases[0].body), vec![2, 3]);
+        assert_eq!(line_numbers(&select.cases[1].body), vec![4]);
+        assert_eq!(
+            line_numbers(select.else_body.as_ref().unwrap()),
+            vec![5, 6, 7]
+        );
+        assert_eq!(line_numbers(&statements), vec![1, 8]);
+    }
+    #[test]
+    fn test_labels() {
+        let statements = parse_script(
+            "GOTO @end\n@top:\n10 @next: MENU 1 GOSUB @Top, @next\nRESTORE @END\n@end: END",
+        );
+        assert!(matches!(
+            statements[..],
+            [
+                Statement::LINE(_),
+                Statement::GOTO(JumpNode {
+                    line_number: 12,
+                    ..
+                }),
+                Statement::LINE(_),
+                Statement::LINE(_),
+                Statement::MENU(_),
+                Statement::LINE(_),
+                Statement::RESTORE(RestoreNode {
+                    target: Some(JumpNode {
+                        line_number: 12,
+                        ..
+                    }),
+                    ..
+                }),
+                Statement::LINE(_),
+                Statement::END(_)
+            ]
+        ));
+        let Statement::MENU(menu) = &statements[4] else {
+            panic!("expected MENU");
+        };
+        assert!(matches!(
+            menu.actions[..],
+            [
+                Statement::GOSUB(JumpNode { line_number: 2, .. }),
+                Statement::GOSUB(JumpNode {
+                    line_number: 10,
+                    ..
+                })
+            ]
+        ));
+
+        let tokens = Lexer::new("GOTO @missing\nGOSUB LIB, @top".into())
+            .tokenize()
+            .unwrap();
+        let errors = Parser::new(tokens, EvaluationContext::FileLevel)
+            .parse()
+            .unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [
+                ParserError::MissingToken { expected, .. },
+                ParserError::UndefinedLabel { label, .. }
+            ] if expected == "line number" && label == "missing"
+        ));
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "2:12: expected line number",
+                "1:6: the label @missing isn't declared in this file"
+            ]
+        );
+        let tokens = Lexer::new("GOTO @missing\n@a: PRINT 1".into())
+            .tokenize()
+            .unwrap();
+        let errors = Parser::new(tokens, EvaluationContext::FileLevel)
+            .parse()
+            .unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [ParserError::UndefinedLabel { label, .. }] if label == "missing"
+        ));
+        // the labels are still checked when other lines don't parse, even the jumps of those lines
+        let tokens = Lexer::new("GOTO @missing\nWHILE TRUE\nGOSUB @gone\nPRINT (\nWEND".into())
+            .tokenize()
+            .unwrap();
+        let errors = Parser::new(tokens, EvaluationContext::FileLevel)
+            .parse()
+            .unwrap_err();
+        assert!(
+            matches!(
+                &errors[..],
+                [
+                    ParserError::UnexpectedToken { .. },
+                    ParserError::UndefinedLabel { label: missing, .. },
+                    ParserError::UndefinedLabel { label: gone, .. }
+                ] if missing == "missing" && gone == "gone"
+            ),
+            "{:#?}",
+            errors
+        );
     }
 }
@@ -1,4 +1,6 @@
-use crate::stdlib::{ENC_FUNCTIONS, POS_FUNCTIONS};
+use std::{collections::HashSet, fmt};
+
+use crate::stdlib::find_function;
 use crate::usbcompiler::errors::error::LexerError;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -6,19 +8,50 @@ pub enum TokenKind {
     // Add more token variants as needed
     Number(String),
     StringLiteral(String),
+    /// a hex color e.g. `#FFF` or `#FF8800`, including the `#`
+    ColorLiteral(String),
+    /// a named jump target e.g. `@menu`, without the `@`
+    Label(String),
     Identifier(String, bool),
     Keyword(String),
     Operator(String),
     Newline,
     Colon,
+    Semicolon,
     Comma,
     ParenOpen,
     ParenClose,
     BrackOpen,
     BrackClose,
+    Dot,
     EOF,
 }
 
+/// the token the way it is written in a program
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            TokenKind::Number(text)
+            | TokenKind::ColorLiteral(text)
+            | TokenKind::Identifier(text, _)
+            | TokenKind::Keyword(text)
+            | TokenKind::Operator(text) => write!(f, "{}", text),
+            TokenKind::StringLiteral(text) => write!(f, "\"{}\"", text),
+            TokenKind::Label(label) => write!(f, "@{}", label),
+            TokenKind::Newline => write!(f, "end of line"),
+            TokenKind::Colon => write!(f, ":"),
+            TokenKind::Semicolon => write!(f, ";"),
+            TokenKind::Comma => write!(f, ","),
+            TokenKind::ParenOpen => write!(f, "("),
+            TokenKind::ParenClose => write!(f, ")"),
+            TokenKind::BrackOpen => write!(f, "["),
+            TokenKind::BrackClose => write!(f, "]"),
+            TokenKind::Dot => write!(f, "."),
+            TokenKind::EOF => write!(f, "end of file"),
+        };
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
@@ -49,6 +82,7 @@ enum CharType {
     Quote,
 }
 
+/// the line and column of the first character, both counted from 1, and the number of characters
 #[derive(Debug, Clone, PartialEq)]
 pub struct Span {
     pub line: usize,
@@ -69,6 +103,8 @@ pub struct Lexer {
     position_flat: usize,
     position_span: Span,
     current_char: Option<char>,
+    /// the warnings allowed by `ALLOW <warning> [, <warning> ...]` comments, by the line they are on
+    allowed: HashSet<(usize, String)>,
 }
 
 type TokenRecognizer = fn(&mut Lexer, char) -> Result<Option<Token>, LexerError>;
@@ -79,7 +115,9 @@ impl Lexer {
             input: script.chars().collect(),
             position_flat: 0,
             current_char: None,
-            position_span: Span::new(1, 1, 1),
+            // advancing onto the first character moves to column 1
+            position_span: Span::new(1, 0, 1),
+            allowed: HashSet::new(),
         };
 
         lexer.advance();
@@ -94,7 +132,7 @@ impl Lexer {
     fn peek_word(&mut self) -> Option<(Span, CharType, String)> {
         let current_char = &self.current_char;
 
-        if let None = current_char {
+        if current_char.is_none() {
             return None;
         }
 
@@ -112,13 +150,11 @@ impl Lexer {
 
         while let Some(c) = self.peek(peek_pos) {
             let new_char_type = Lexer::get_char_type(&c);
-            if last_char_type != CharType::None {
-                if last_char_type != new_char_type
-                    && !(last_char_type == CharType::Alphabetic
-                        && new_char_type == CharType::Numeric)
-                {
-                    break;
-                }
+            if last_char_type != CharType::None
+                && last_char_type != new_char_type
+                && !(last_char_type == CharType::Alphabetic && new_char_type == CharType::Numeric)
+            {
+                break;
             }
 
             last_char_type = new_char_type;
@@ -126,7 +162,7 @@ impl Lexer {
             peek_pos += 1;
         }
 
-        let first_char = total.as_bytes()[0].clone() as char;
+        let first_char = total.as_bytes()[0] as char;
         last_char_type = Lexer::get_char_type(&first_char);
 
         return Some((
@@ -141,7 +177,7 @@ impl Lexer {
         let mut peek_pos = 0;
         while let Some(c) = self.peek(peek_pos) {
             if !&c.is_whitespace() {
-                return Some((peek_pos, c.clone()));
+                return Some((peek_pos, c));
             }
             peek_pos += 1;
         }
@@ -150,9 +186,7 @@ impl Lexer {
 
     fn consume(&mut self, count: usize) -> Option<(Span, String)> {
         let mut total = String::new();
-        if let None = self.current_char {
-            return None;
-        }
+        self.current_char?;
 
         let start_line = self.position_span.line;
         let start_column = self.position_span.column;
@@ -170,6 +204,46 @@ impl Lexer {
             total,
         ));
     }
+    /// returns true if the stream is positioned on the start of a `//` or `REM` comment
+    fn at_comment(&mut self) -> bool {
+        if let Some('/') = self.current_char {
+            return self.peek(0) == Some('/');
+        }
+        if let Some((_, CharType::Alphabetic, word)) = self.peek_word() {
+            return word.to_uppercase() == "REM";
+        }
+        return false;
+    }
+    /// skips everything up to (but not including) the next newline, remembering the warnings an `ALLOW` comment allows on its line
+    fn consume_comment(&mut self) {
+        let line = self.position_span.line;
+        let mut comment = String::new();
+        while let Some(c) = self.current_char {
+            if c == '\n' {
+                break;
+            }
+            comment.push(c);
+            self.advance();
+        }
+        // drop the `//` or `REM`
+        let text: String = comment
+            .chars()
+            .skip(if comment.starts_with('/') { 2 } else { 3 })
+            .collect();
+        let mut words = text
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|w| !w.is_empty());
+        if words
+            .next()
+            .is_some_and(|w| w.eq_ignore_ascii_case("ALLOW"))
+        {
+            self.allowed.extend(words.map(|w| (line, w.to_uppercase())));
+        }
+    }
+    /// the warnings allowed by `ALLOW` comments as (line, warning name) pairs, complete once [`Lexer::tokenize`] is done
+    pub fn allowed(&self) -> HashSet<(usize, String)> {
+        return self.allowed.clone();
+    }
     fn consume_whitespace(&mut self) {
         while let Some(c) = self.current_char {
             if c == '\n' || !c.is_whitespace() {
@@ -224,7 +298,7 @@ impl Lexer {
             self.advance();
         }
 
-        if let None = self.current_char {
+        if self.current_char.is_none() {
             return Err(LexerError::UnterminatedString(Span::new(
                 start_line, start_col, 0,
             )));
@@ -235,20 +309,19 @@ impl Lexer {
             literal,
         ));
     }
+    /// move onto the next character, `position_span` is the span of the new current character.
+    /// A newline is the last character of its line, the line after it starts with the character following it
     fn advance(&mut self) {
         if self.position_flat < self.input.len() {
-            let c = self.input[self.position_flat];
-            self.current_char = Some(c);
-            self.position_flat += 1;
-
-            if c == '\n' {
+            if self.current_char == Some('\n') {
                 self.position_span.line += 1;
                 self.position_span.column = 1;
             } else {
                 self.position_span.column += 1;
             }
-
-            self.position_span.length += 1;
+            let c = self.input[self.position_flat];
+            self.current_char = Some(c);
+            self.position_flat += 1;
         } else {
             self.current_char = None;
         }
@@ -257,7 +330,7 @@ impl Lexer {
     /// advance the through the stream by `count` steps
     fn skip(&mut self, count: usize) {
         let mut index = 0;
-        while let Some(_) = self.current_char {
+        while self.current_char.is_some() {
             if index >= count {
                 break;
             }
@@ -269,7 +342,7 @@ impl Lexer {
     fn get_char_type(character: &char) -> CharType {
         return if character.is_whitespace() {
             CharType::Whitespace
-        } else if character.is_alphabetic() {
+        } else if character.is_alphabetic() || *character == '_' || *character == '$' {
             CharType::Alphabetic
         } else if character.is_ascii_digit() {
             CharType::Numeric
@@ -277,12 +350,12 @@ impl Lexer {
             || UNIXSOFT_OPERATORS.contains(&character.to_string().as_str())
         {
             CharType::OperatorSymbol
-        } else if UNIXSOFT_DELIMITERS.contains(&character) {
+        } else if UNIXSOFT_DELIMITERS.contains(character) {
             CharType::Delimiter
-        } else if character.clone() == '\"' {
+        } else if *character == '\"' {
             CharType::Quote
         } else {
-            panic!("Invalid symbol detected");
+            CharType::None
         };
     }
     /// get all the symbol handlers that resolve symbols into tokens.
@@ -294,7 +367,9 @@ impl Lexer {
             Self::handle_newline,
             Self::handle_delimiter,
             Self::handle_string_literal,
-            Self::handle_integer_literal,
+            Self::handle_number_literal,
+            Self::handle_color_literal,
+            Self::handle_label,
             Self::handle_operator,
             Self::handle_keyword,
             Self::handle_identifier,
@@ -302,6 +377,9 @@ impl Lexer {
     }
     pub fn next_token(&mut self) -> Result<Option<Token>, LexerError> {
         self.consume_whitespace();
+        if self.at_comment() {
+            self.consume_comment();
+        }
 
         let current = match self.current_char {
             Some(c) => c,
@@ -315,25 +393,24 @@ impl Lexer {
                 return eof;
             }
         };
+        let span = self.position_span.clone();
         let handlers = self.get_handlers();
         let mut token: Option<Token> = None;
         for handler in handlers {
             let result = handler(self, current);
             match result {
-                Ok(found) => match found {
-                    Some(t) => {
-                        token = Some(t);
-                        break;
-                    }
-                    _ => (),
-                },
+                Ok(Some(t)) => {
+                    token = Some(t);
+                    break;
+                }
+                Ok(None) => (),
                 Err(e) => return Err(e),
             }
         }
 
         self.advance();
-        if let None = token {
-            return Err(LexerError::InvalidChar(self.position_span.clone(), current));
+        if token.is_none() {
+            return Err(LexerError::InvalidChar(span, current));
         }
 
         return Ok(token);
@@ -359,7 +436,7 @@ impl Lexer {
             }
         }
 
-        if errors.len() > 0 {
+        if !errors.is_empty() {
             return Err((tokens, errors));
         }
         return Ok(tokens);
@@ -373,11 +450,15 @@ impl Lexer {
         } else if character == '[' {
             kind = TokenKind::BrackOpen;
         } else if character == ']' {
-            kind = TokenKind::BrackOpen;
+            kind = TokenKind::BrackClose;
         } else if character == ',' {
             kind = TokenKind::Comma;
         } else if character == ':' {
             kind = TokenKind::Colon;
+        } else if character == ';' {
+            kind = TokenKind::Semicolon;
+        } else if character == '.' {
+            kind = TokenKind::Dot;
         } else {
             return Ok(None);
         }
@@ -412,17 +493,26 @@ impl Lexer {
             position_span: result.0,
         }));
     }
-    fn handle_integer_literal(&mut self, character: char) -> Result<Option<Token>, LexerError> {
+    fn handle_number_literal(&mut self, character: char) -> Result<Option<Token>, LexerError> {
         if !character.is_ascii_digit() {
             return Ok(None);
         }
 
         let word = self.consume_word();
-        if let Some(tuple) = word {
+        if let Some(mut tuple) = word {
             match tuple.1 {
                 CharType::Numeric => (),
                 _ => panic!("unexpected token: {:#?} of type {:#?}", tuple.2, tuple.1),
             }
+            // float literals are an integer word, a dot and another integer word e.g. 12.5
+            if self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+                self.skip(2);
+                if let Some(fraction) = self.consume_word() {
+                    tuple.2.push('.');
+                    tuple.2.push_str(&fraction.2);
+                    tuple.0.length = tuple.2.chars().count();
+                }
+            }
 
             let token = Some(Token {
                 kind: TokenKind::Number(tuple.2),
@@ -433,7 +523,57 @@ impl Lexer {
         }
         return Err(LexerError::UnexpectedEof);
     }
-    fn handle_operator(&mut self, character: char) -> Result<Option<Token>, LexerError> {
+    fn handle_color_literal(&mut self, character: char) -> Result<Option<Token>, LexerError> {
+        if character != '#' {
+            return Ok(None);
+        }
+        let start = self.position_span.clone();
+        let mut literal = String::from("#");
+        while let Some(c) = self.peek(0) {
+            if !c.is_ascii_alphanumeric() {
+                break;
+            }
+            literal.push(c);
+            self.advance();
+        }
+        let span = Span::new(start.line, start.column, literal.chars().count());
+        let digits = &literal[1..];
+        if !(digits.len() == 3 || digits.len() == 6)
+            || !digits.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            self.advance();
+            return Err(LexerError::InvalidNumberLiteral(span, literal));
+        }
+        return Ok(Some(Token {
+            kind: TokenKind::ColorLiteral(literal),
+            position_flat: self.position_flat,
+            position_span: span,
+        }));
+    }
+    fn handle_label(&mut self, character: char) -> Result<Option<Token>, LexerError> {
+        if character != '@' {
+            return Ok(None);
+        }
+        let start = self.position_span.clone();
+        let mut name = String::new();
+        while let Some(c) = self.peek(0) {
+            if !c.is_alphanumeric() && c != '_' {
+                break;
+            }
+            name.push(c);
+            self.advance();
+        }
+        if name.is_empty() {
+            self.advance();
+            return Err(LexerError::InvalidChar(start, character));
+        }
+        return Ok(Some(Token {
+            kind: TokenKind::Label(name.clone()),
+            position_flat: self.position_flat,
+            position_span: Span::new(start.line, start.column, name.chars().count() + 1),
+        }));
+    }
+    fn handle_operator(&mut self, _character: char) -> Result<Option<Token>, LexerError> {
         let word = self.peek_word();
 
         if let Some(t) = word {
@@ -459,17 +599,16 @@ impl Lexer {
 
         let word = self.peek_word();
 
-        if let Some(t) = word {
-            if APPLESOFT_KEYWORDS.contains(&t.2.to_uppercase().as_str())
-                || UNIXSOFT_KEYWORDS.contains(&t.2.to_uppercase().as_str())
-            {
-                self.consume_word();
-                return Ok(Some(Token {
-                    kind: TokenKind::Keyword(t.2),
-                    position_flat: self.position_flat,
-                    position_span: t.0,
-                }));
-            }
+        if let Some(t) = word
+            && (APPLESOFT_KEYWORDS.contains(&t.2.to_uppercase().as_str())
+                || UNIXSOFT_KEYWORDS.contains(&t.2.to_uppercase().as_str()))
+        {
+            self.consume_word();
+            return Ok(Some(Token {
+                kind: TokenKind::Keyword(t.2),
+                position_flat: self.position_flat,
+                position_span: t.0,
+            }));
         }
         return Ok(None);
     }
@@ -477,17 +616,13 @@ impl Lexer {
         if !character.is_ascii_alphabetic() {
             return Ok(None);
         }
-        println!(
-            "Handling ascii alphabetic for identifier starting with: {}",
-            character
-        );
-
         let word = self.peek_word();
 
         if let Some(t) = word {
             self.consume_word();
+            let is_function = find_function(&t.2).is_some();
             return Ok(Some(Token {
-                kind: TokenKind::Identifier(t.2),
+                kind: TokenKind::Identifier(t.2, is_function),
                 position_flat: self.position_flat,
                 position_span: t.0,
             }));
@@ -496,7 +631,7 @@ impl Lexer {
     }
 }
 
-pub const APPLESOFT_KEYWORDS: &'static [&'static str] = &[
+pub const APPLESOFT_KEYWORDS: &[&str] = &[
     "END", "FOR", "NEXT", "DATA", "INPUT", "DEL", "DIM", "READ", "GR", "TEXT", "PR#", "IN#",
     "CALL", "PLOT", "HLIN", "VLIN", "HGR2", "HGR", "HCOLOR", "HPLOT", "DRAW", "XDRAW", "HTAB",
     "HOME", "ROT=", "SCALE=", "SHLOAD", "TRACE", "NOTRACE", "NORMAL", "INVERSE", "FLASH", "COLOR",
@@ -506,19 +641,85 @@ pub const APPLESOFT_KEYWORDS: &'static [&'static str] = &[
     "STEP",
 ];
 
-pub const APPLESOFT_FUNCTIONS: &'static [&'static str] = &[
+pub const APPLESOFT_FUNCTIONS: &[&str] = &[
     "SGN", "ABS", "USR", "FRE", "SCRN", "PDL", "POS", "SQR", "RND", "LOG", "EXP", "COS", "SIN",
     "TAN", "ATN", "PEEK", "LEN", "STR", "VAL", "ASC", "CHR", "LEFT", "RIGHT", "MID",
 ];
-pub const APPLESOFT_OPERATORS: &'static [&'static str] =
-    &["+", "-", "*", "/", "^", ">", "=", "<", "AND", "OR", "NOT"];
-pub const UNIXSOFT_KEYWORDS: &'static [&'static str] = &[
-    "TRUE", "FALSE", "//", "DATE", "DAY", "HOUR", "MINUTE", "SECOND", "TIME", "BEGIN", "FIN",
-    "MENU", "POPTIONS", "CSCOPE", "ENUM", "PENUM",
+pub const APPLESOFT_OPERATORS: &[&str] = &[
+    "+", "-", "*", "/", "^", ">", "=", "<", "<>", "AND", "OR", "NOT",
 ];
-pub const UNIXSOFT_FUNCTIONS: &'static [&'static str] = &["INT", "FLOAT", "BOOL"];
-pub const UNIXSOFT_OPERATORS: &'static [&'static str] = &[">=", "<=", "!="];
-pub const UNIXSOFT_DELIMITERS: [char; 6] = ['(', ')', '[', ']', ',', ':'];
+pub const UNIXSOFT_KEYWORDS: &[&str] = &[
+    "TRUE",
+    "FALSE",
+    "//",
+    "DATE",
+    "DAY",
+    "HOUR",
+    "MINUTE",
+    "SECOND",
+    "TIME",
+    "BEGIN",
+    "FIN",
+    "MENU",
+    "POPTIONS",
+    "CSCOPE",
+    "ENUM",
+    "PENUM",
+    "AS",
+    "RET",
+    "IMPORT",
+    "OPEN",
+    "CLOSE",
+    "READLINE",
+    "READALL",
+    "WRITE",
+    "APPEND",
+    "FCOLOR",
+    "BCOLOR",
+    "ICOLOR",
+    "RCOLOR",
+    "SCREEN",
+    "RANDOMIZE",
+    "ERR",
+    "THROW",
+    "RECORD",
+    "FUNCTION",
+    "MAP",
+    "OF",
+    "PUSH",
+    "INSERT",
+    "REMOVE",
+    "HAS",
+    "KEYS",
+    "EACH",
+    "IN",
+    "ARGC",
+    "SETENV",
+    "SHELL",
+    "EXEC",
+    "STDERR",
+    "MKDIR",
+    "DELETE",
+    "RENAME",
+    "COPY",
+    "WHILE",
+    "WEND",
+    "DO",
+    "LOOP",
+    "UNTIL",
+    "EXIT",
+    "CONTINUE",
+    "ELSE",
+    "ELSEIF",
+    "SELECT",
+    "CASE",
+    "IS",
+];
+pub const UNIXSOFT_FUNCTIONS: &[&str] = &["INT", "FLOAT", "BOOL"];
+pub const UNIXSOFT_OPERATORS: &[&str] = &[
+    ">=", "<=", "!=", "==", "\\", "MOD", "BAND", "BOR", "BXOR", "SHL", "SHR", "+=", "-=", "*=",
+];
+pub const UNIXSOFT_DELIMITERS: [char; 8] = ['(', ')', '[', ']', ',', ':', ';', '.'];
 
 #[cfg(test)]
 mod tests {
@@ -608,7 +809,7 @@ mod tests {
         let input = "10 PRINT5 10";
         let expected_tokens = vec![
             TokenKind::Number("10".into()),
-            TokenKind::Identifier("PRINT5".into()),
+            TokenKind::Identifier("PRINT5".into(), false),
             TokenKind::Number("10".into()),
         ];
         assert_script_tokens(input, expected_tokens, true);
@@ -636,6 +837,30 @@ mod tests {
         assert_script_tokens(input, expected_tokens, true);
     }
     #[test]
+    fn test_spans() {
+        let tokens = Lexer::new("PRINT x\n  y".into()).tokenize().unwrap();
+        let spans: Vec<Span> = tokens.into_iter().map(|t| t.position_span).collect();
+        // a newline ends its line, the span of each token only covers the token
+        assert_eq!(
+            spans[..4],
+            [
+                Span::new(1, 1, 5),
+                Span::new(1, 7, 1),
+                Span::new(1, 8, 1),
+                Span::new(2, 3, 1)
+            ]
+        );
+        let errors = Lexer::new("PRINT 1 ~\nPRINT 2".into())
+            .tokenize()
+            .unwrap_err()
+            .1;
+        assert!(matches!(
+            &errors[..],
+            [LexerError::InvalidChar(span, '~')] if *span == Span::new(1, 9, 1)
+        ));
+        assert_eq!(errors[0].to_string(), "1:9: invalid character `~`");
+    }
+    #[test]
     fn test_string_simple_literals() {
         let input = "10 PRINT \"Hello, World\"";
         let expected_tokens = vec![
@@ -668,12 +893,12 @@ mod tests {
         let input = "LET X = 10\nPRINT X + 5";
         let expected_tokens = vec![
             TokenKind::Keyword("LET".into()),
-            TokenKind::Identifier("X".into()),
+            TokenKind::Identifier("X".into(), false),
             TokenKind::Operator("=".into()),
             TokenKind::Number("10".into()),
             TokenKind::Newline,
             TokenKind::Keyword("PRINT".into()),
-            TokenKind::Identifier("X".into()),
+            TokenKind::Identifier("X".into(), false),
             TokenKind::Operator("+".into()),
             TokenKind::Number("5".into()),
         ];
@@ -685,7 +910,7 @@ mod tests {
         let input = "PRINT X <= 5";
         let expected_tokens = vec![
             TokenKind::Keyword("PRINT".into()),
-            TokenKind::Identifier("X".into()),
+            TokenKind::Identifier("X".into(), false),
             TokenKind::Operator("<=".into()),
             TokenKind::Number("5".into()),
         ];
@@ -694,36 +919,49 @@ mod tests {
         let input = "LET X = TRUE AND TRUE";
         let expected_tokens = vec![
             TokenKind::Keyword("LET".into()),
-            TokenKind::Identifier("X".into()),
+            TokenKind::Identifier("X".into(), false),
             TokenKind::Operator("=".into()),
             TokenKind::Keyword("TRUE".into()),
             TokenKind::Operator("AND".into()),
             TokenKind::Keyword("TRUE".into()),
         ];
         assert_script_tokens(input, expected_tokens, true);
+
+        let input = "PRINT X == 5 OR X <> 6";
+        let expected_tokens = vec![
+            TokenKind::Keyword("PRINT".into()),
+            TokenKind::Identifier("X".into(), false),
+            TokenKind::Operator("==".into()),
+            TokenKind::Number("5".into()),
+            TokenKind::Operator("OR".into()),
+            TokenKind::Identifier("X".into(), false),
+            TokenKind::Operator("<>".into()),
+            TokenKind::Number("6".into()),
+        ];
+        assert_script_tokens(input, expected_tokens, true);
     }
     #[test]
     fn test_delimiters() {
         let input = "DEF my_func x,y,z = x+y+z\nLET a = my_func(1,2,3)";
         let expected_tokens = vec![
             TokenKind::Keyword("DEF".into()),
-            TokenKind::Identifier("my_func".into()),
-            TokenKind::Identifier("x".into()),
+            TokenKind::Identifier("my_func".into(), false),
+            TokenKind::Identifier("x".into(), false),
             TokenKind::Comma,
-            TokenKind::Identifier("y".into()),
+            TokenKind::Identifier("y".into(), false),
             TokenKind::Comma,
-            TokenKind::Identifier("z".into()),
+            TokenKind::Identifier("z".into(), false),
             TokenKind::Operator("=".into()),
-            TokenKind::Identifier("x".into()),
+            TokenKind::Identifier("x".into(), false),
             TokenKind::Operator("+".into()),
-            TokenKind::Identifier("y".into()),
+            TokenKind::Identifier("y".into(), false),
             TokenKind::Operator("+".into()),
-            TokenKind::Identifier("z".into()),
+            TokenKind::Identifier("z".into(), false),
             TokenKind::Newline,
             TokenKind::Keyword("LET".into()),
-            TokenKind::Identifier("a".into()),
+            TokenKind::Identifier("a".into(), false),
             TokenKind::Operator("=".into()),
-            TokenKind::Identifier("my_func".into()),
+            TokenKind::Identifier("my_func".into(), false),
             TokenKind::ParenOpen,
             TokenKind::Number("1".into()),
             TokenKind::Comma,
@@ -732,5 +970,72 @@ mod tests {
             TokenKind::Number("3".into()),
             TokenKind::ParenClose,
         ];
+        assert_script_tokens(input, expected_tokens, true);
+    }
+    #[test]
+    fn test_field_access() {
+        let input = "p.home.x = 1.5";
+        let expected_tokens = vec![
+            TokenKind::Identifier("p".into(), false),
+            TokenKind::Dot,
+            TokenKind::Keyword("home".into()),
+            TokenKind::Dot,
+            TokenKind::Identifier("x".into(), false),
+            TokenKind::Operator("=".into()),
+            TokenKind::Number("1.5".into()),
+        ];
+        assert_script_tokens(input, expected_tokens, true);
+    }
+    #[test]
+    fn test_color_literals() {
+        let input = "BCOLOR #FFF\nFCOLOR #ff8800";
+        let expected_tokens = vec![
+            TokenKind::Keyword("BCOLOR".into()),
+            TokenKind::ColorLiteral("#FFF".into()),
+            TokenKind::Newline,
+            TokenKind::Keyword("FCOLOR".into()),
+            TokenKind::ColorLiteral("#ff8800".into()),
+        ];
+        assert_script_tokens(input, expected_tokens, true);
+
+        let result = Lexer::new("FCOLOR #FFFF".into()).tokenize();
+        assert!(matches!(
+            result.unwrap_err().1[..],
+            [LexerError::InvalidNumberLiteral(..)]
+        ));
+    }
+    #[test]
+    fn test_labels() {
+        let input = "@main_loop: PRINT 1\nGOTO @main_loop";
+        let expected_tokens = vec![
+            TokenKind::Label("main_loop".into()),
+            TokenKind::Colon,
+            TokenKind::Keyword("PRINT".into()),
+            TokenKind::Number("1".into()),
+            TokenKind::Newline,
+            TokenKind::Keyword("GOTO".into()),
+            TokenKind::Label("main_loop".into()),
+        ];
+        assert_script_tokens(input, expected_tokens, true);
+
+        let result = Lexer::new("GOTO @".into()).tokenize();
+        assert!(matches!(
+            result.unwrap_err().1[..],
+            [LexerError::InvalidChar(_, '@')]
+        ));
+    }
+    #[test]
+    fn test_allow_comments() {
+        let mut lexer = Lexer::new(
+            "PRINT X // ALLOW unassigned\nPRINT X REM Allow EQUALITY,UNREACHABLE\n// ALLOWS X"
+                .into(),
+        );
+        lexer.tokenize().unwrap();
+        let expected = HashSet::from([
+            (1, "UNASSIGNED".to_string()),
+            (2, "EQUALITY".to_string()),
+            (2, "UNREACHABLE".to_string()),
+        ]);
+        assert_eq!(lexer.allowed(), expected);
     }
 }
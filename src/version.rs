@@ -1,6 +1,6 @@
-const BINARY: &'static str = "UnixSoft-BASIC";
-const VERSION: &'static str = "0.0.1";
-const ARC: &'static str = "x86_64_linux";
+const BINARY: &str = "UnixSoft-BASIC";
+const VERSION: &str = "0.0.1";
+const ARC: &str = "x86_64_linux";
 pub struct Version;
 impl Version {
     pub fn print() {